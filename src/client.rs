@@ -1,62 +1,345 @@
-use crate::core::Paths;
-use crate::core::ipc::{Request, Response};
-use anyhow::Result;
-use std::path::Path;
+use crate::core::event::{EventFilter, JobEvent};
+use crate::core::hooks::Hooks;
+use crate::core::ipc::{FEATURES, NewJob, PROTOCOL_VERSION, Request, Response};
+use crate::core::output::OutputChunk;
+use crate::core::webhook::Webhook;
+use crate::core::{Job, Paths, Status, detect_project, paths};
+use crate::error::Error;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::process::Stdio;
 use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
 use tokio::process::Command;
 
+type Result<T> = std::result::Result<T, Error>;
+
+/// Largest response frame accepted
+const MAX_FRAME: usize = 10 * 1024 * 1024;
+
+/// A job to start with [`DaemonClient::run`]. Build it with [`RunSpec::new`]
+/// and set the fields you need; more may be added.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct RunSpec {
+    /// Shell command, run with `sh -c`
+    pub command: String,
+    pub name: Option<String>,
+    /// Working directory; defaults to the current one
+    pub cwd: Option<PathBuf>,
+    /// Project root the job is grouped under; defaults to the one containing `cwd`
+    pub project: Option<PathBuf>,
+    /// Stop the job after this long
+    pub timeout: Option<Duration>,
+    /// Return the job started earlier with this key instead of starting another
+    pub idempotency_key: Option<String>,
+    /// Rotate the log past this many bytes
+    pub log_max_size: Option<u64>,
+    /// Rotated log segments to keep
+    pub log_keep: Option<u32>,
+    /// Commands the daemon runs when the job finishes
+    pub hooks: Hooks,
+    /// Endpoints the daemon posts the job to when it finishes
+    pub webhooks: Vec<Webhook>,
+    /// Desktop notification when it finishes; `None` follows config
+    pub notify: Option<bool>,
+}
+
+impl RunSpec {
+    #[must_use]
+    pub fn new(command: impl Into<String>) -> Self {
+        Self {
+            command: command.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// What a subscription delivers
+#[derive(Debug)]
+pub enum SubscriptionEvent {
+    /// More output, in order
+    Output(Vec<OutputChunk>),
+    /// The job reached a terminal state; nothing follows
+    Finished(Box<Job>),
+}
+
+/// A connection to the jb daemon.
+///
+/// Each method sends one request and waits for its reply. Requests on one
+/// connection are handled in order; open more connections to wait on several
+/// jobs at once.
+///
+/// Connecting exchanges protocol versions with the daemon; one too old to
+/// answer fails with [`Error::Incompatible`].
 pub struct DaemonClient {
     stream: UnixStream,
+    /// Protocol version agreed with the daemon; 0 without a handshake
+    version: u32,
+    /// Features the daemon announced
+    features: Vec<String>,
 }
 
 impl DaemonClient {
-    async fn connect_to(socket_path: impl AsRef<Path>) -> Result<Self> {
-        let stream = UnixStream::connect(socket_path).await?;
-        Ok(Self { stream })
+    /// Connect to the daemon of the state directory at `paths`, without starting one
+    pub async fn connect_to(paths: &Paths) -> Result<Self> {
+        let mut client = Self::connect_without_handshake_to(paths).await?;
+        client.handshake().await?;
+        Ok(client)
+    }
+
+    /// Connect without exchanging versions, so replies come in their version 0
+    /// form. Only requests every daemon understands, like `Ping` and
+    /// `Shutdown`, are safe; it is how an outdated daemon is stopped.
+    pub async fn connect_without_handshake_to(paths: &Paths) -> Result<Self> {
+        let stream = UnixStream::connect(paths.socket()).await?;
+        Ok(Self {
+            stream,
+            version: 0,
+            features: Vec::new(),
+        })
+    }
+
+    /// [`Self::connect_without_handshake_to`] the default state directory
+    pub async fn connect_without_handshake() -> Result<Self> {
+        Self::connect_without_handshake_to(&default_paths()?).await
+    }
+
+    /// Connect to a running daemon without starting one
+    pub async fn connect() -> Result<Self> {
+        Self::connect_to(&default_paths()?).await
     }
 
     /// Connect to daemon, starting it if not running
     pub async fn connect_or_start() -> Result<Self> {
-        let paths = Paths::new()?;
+        Self::connect_or_start_at(&default_paths()?).await
+    }
 
-        // Try connecting first
-        if let Ok(client) = Self::connect_to(paths.socket()).await {
-            return Ok(client);
+    /// Connect to the daemon of the state directory at `paths`, starting it if
+    /// not running. The daemon is started from this executable when it is jb,
+    /// and otherwise from `jb` on the `PATH`.
+    pub async fn connect_or_start_at(paths: &Paths) -> Result<Self> {
+        // Try connecting first. A daemon that is up but outdated stays up:
+        // starting another would fight it for the socket.
+        match Self::connect_to(paths).await {
+            Ok(client) => return Ok(client),
+            Err(e @ Error::Incompatible(_)) => return Err(e),
+            Err(_) => {}
         }
 
         // Daemon not running, start it
-        start_daemon()?;
+        start_daemon(paths)?;
 
         // Wait for daemon to be ready
         for _ in 0..50 {
             tokio::time::sleep(Duration::from_millis(100)).await;
-            if let Ok(client) = Self::connect_to(paths.socket()).await {
-                return Ok(client);
+            match Self::connect_to(paths).await {
+                Ok(client) => return Ok(client),
+                Err(e @ Error::Incompatible(_)) => return Err(e),
+                Err(_) => {}
+            }
+        }
+
+        Err(Error::StartFailed)
+    }
+
+    /// Protocol version agreed with the daemon
+    #[must_use]
+    pub fn protocol_version(&self) -> u32 {
+        self.version
+    }
+
+    /// Whether the daemon announced `feature` (see [`FEATURES`])
+    #[must_use]
+    pub fn supports(&self, feature: &str) -> bool {
+        self.features.iter().any(|f| f == feature)
+    }
+
+    /// Offer our protocol version and features, and keep what the daemon agrees to
+    async fn handshake(&mut self) -> Result<()> {
+        self.write_request(&Request::Hello {
+            version: PROTOCOL_VERSION,
+            features: FEATURES.iter().map(ToString::to_string).collect(),
+        })
+        .await?;
+        // A daemon from before the handshake can't parse `Hello` and hangs up
+        let reply = match self.recv().await {
+            Ok(reply) => reply,
+            Err(Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(Error::Incompatible(
+                    "the daemon predates protocol versions".into(),
+                ));
+            }
+            Err(e) => return Err(e),
+        };
+        match reply {
+            Response::Hello { version, features } => {
+                self.version = version;
+                self.features = features;
+                Ok(())
             }
+            Response::Failed(error) => Err(Error::Incompatible(error.message)),
+            other => Err(Error::Incompatible(format!(
+                "it answered the handshake with {}",
+                variant_name(&other)
+            ))),
+        }
+    }
+
+    /// Start a job. With an idempotency key that was used before, the earlier
+    /// job is returned instead.
+    pub async fn run(&mut self, spec: &RunSpec) -> Result<Job> {
+        let NewJob {
+            command,
+            name,
+            cwd,
+            project,
+            timeout_secs,
+            idempotency_key,
+            log_max_size,
+            log_keep,
+            hooks,
+            webhooks,
+            notify,
+        } = new_job(spec, &mut HashMap::new())?;
+        let request = Request::Run {
+            command,
+            name,
+            cwd,
+            project,
+            timeout_secs,
+            idempotency_key,
+            log_max_size,
+            log_keep,
+            hooks: Box::new(hooks),
+            webhooks,
+            notify,
+        };
+        match self.send(request).await? {
+            Response::Job(job) => Ok(*job),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Start several jobs in one request, returning them in the same order.
+    /// Either all are created or, if any is refused (e.g. its name is in
+    /// use), none are. Jobs with an idempotency key used before come back as
+    /// the earlier job.
+    pub async fn run_many(&mut self, specs: &[RunSpec]) -> Result<Vec<Job>> {
+        if !self.supports("batch") {
+            return Err(Error::Incompatible("it can't start jobs in a batch".into()));
+        }
+        // Jobs in one directory share a project, found once
+        let mut projects = HashMap::new();
+        let jobs = specs
+            .iter()
+            .map(|spec| new_job(spec, &mut projects))
+            .collect::<Result<Vec<_>>>()?;
+        match self.send(Request::RunMany { jobs }).await? {
+            Response::Jobs(jobs) => Ok(jobs),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Stop a running job: SIGTERM to its process group, or SIGKILL with `force`.
+    pub async fn stop(&mut self, id: &str, force: bool) -> Result<()> {
+        let request = Request::Stop {
+            id: id.to_string(),
+            force,
+        };
+        match self.send(request).await? {
+            Response::Ok => Ok(()),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Wait for a job to reach a terminal state and return it. Fails with
+    /// [`Error::Timeout`] if `timeout` passes first.
+    pub async fn wait(&mut self, id: &str, timeout: Option<Duration>) -> Result<Job> {
+        let request = Request::Wait {
+            id: id.to_string(),
+            timeout_secs: timeout.map(whole_secs),
+        };
+        match self.send(request).await? {
+            Response::Job(job) => Ok(*job),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// A job by exact ID
+    pub async fn status(&mut self, id: &str) -> Result<Job> {
+        match self.send(Request::Status { id: id.to_string() }).await? {
+            Response::Job(job) => Ok(*job),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Jobs, newest first, optionally only those with `status`
+    pub async fn list(&mut self, status: Option<Status>, limit: Option<usize>) -> Result<Vec<Job>> {
+        let request = Request::List {
+            status: status.map(|s| s.as_str().to_string()),
+            limit,
+        };
+        match self.send(request).await? {
+            Response::Jobs(jobs) => Ok(jobs),
+            other => Err(unexpected(other)),
         }
+    }
 
-        anyhow::bail!("Daemon failed to start within 5 seconds")
+    /// Stream a job's output from `from_offset` (a position in its whole
+    /// output). The connection is given over to the subscription.
+    pub async fn subscribe(mut self, id: &str, from_offset: u64) -> Result<Subscription> {
+        self.write_request(&Request::Subscribe {
+            id: id.to_string(),
+            from_offset,
+        })
+        .await?;
+        Ok(Subscription { client: self })
     }
 
+    /// Watch job lifecycle events matching `filter`. Events from the moment
+    /// this returns are delivered; the connection is given over to the watch.
+    pub async fn events(mut self, filter: EventFilter) -> Result<Events> {
+        match self.send(Request::Events { filter }).await? {
+            Response::Ok => Ok(Events { client: self }),
+            other => Err(unexpected(other)),
+        }
+    }
+
+    /// Send a raw protocol request and return the reply. Error replies are
+    /// returned as `Err`.
     pub async fn send(&mut self, request: Request) -> Result<Response> {
-        // Write request
-        let data = serde_json::to_vec(&request)?;
+        self.write_request(&request).await?;
+        match self.recv().await? {
+            Response::Failed(error) => Err(error.into()),
+            // Replies to a connection without a handshake
+            Response::Error(message) => Err(Error::Daemon(message)),
+            Response::UserError(message) => Err(Error::Rejected(message)),
+            response => Ok(response),
+        }
+    }
+
+    async fn write_request(&mut self, request: &Request) -> Result<()> {
+        let data = serde_json::to_vec(request)?;
         #[allow(clippy::cast_possible_truncation)] // messages are always < 4GB
         let len = (data.len() as u32).to_be_bytes();
         self.stream.write_all(&len).await?;
         self.stream.write_all(&data).await?;
         self.stream.flush().await?;
+        Ok(())
+    }
 
-        // Read response
+    /// Read the next response frame
+    async fn recv(&mut self) -> Result<Response> {
         let mut len_buf = [0u8; 4];
         self.stream.read_exact(&mut len_buf).await?;
         let len = u32::from_be_bytes(len_buf) as usize;
 
-        if len > 10 * 1024 * 1024 {
-            anyhow::bail!("Response too large: {len} bytes");
+        if len > MAX_FRAME {
+            return Err(Error::UnexpectedResponse(format!(
+                "response too large: {len} bytes"
+            )));
         }
 
         let mut buf = vec![0u8; len];
@@ -67,13 +350,102 @@ impl DaemonClient {
     }
 }
 
-fn start_daemon() -> Result<()> {
-    // Use same binary with "daemon" subcommand
-    let exe = std::env::current_exe()?;
+/// A job's output as the daemon pushes it
+pub struct Subscription {
+    client: DaemonClient,
+}
+
+impl Subscription {
+    /// The next output, or the finished job. Fails if the daemon goes away.
+    pub async fn next(&mut self) -> Result<SubscriptionEvent> {
+        match self.client.recv().await? {
+            Response::Output(chunks) => Ok(SubscriptionEvent::Output(chunks)),
+            Response::Finished(job) => Ok(SubscriptionEvent::Finished(job)),
+            Response::Failed(error) => Err(error.into()),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+/// Job lifecycle events as the daemon pushes them
+pub struct Events {
+    client: DaemonClient,
+}
+
+impl Events {
+    /// The next event. Fails if the daemon goes away.
+    pub async fn next(&mut self) -> Result<JobEvent> {
+        match self.client.recv().await? {
+            Response::Event(event) => Ok(*event),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: Response) -> Error {
+    Error::UnexpectedResponse(variant_name(&response))
+}
+
+/// `Pong` for `Response::Pong { .. }`
+fn variant_name(response: &Response) -> String {
+    let kind = format!("{response:?}");
+    kind.split(['(', ' '])
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
 
-    // Spawn daemon detached
-    Command::new(&exe)
-        .arg("daemon")
+/// The job `spec` describes, with its directory and project filled in.
+/// `projects` caches the project found for each directory.
+fn new_job(spec: &RunSpec, projects: &mut HashMap<PathBuf, PathBuf>) -> Result<NewJob> {
+    let cwd = match &spec.cwd {
+        Some(cwd) => cwd.clone(),
+        None => std::env::current_dir()?,
+    };
+    let project = match &spec.project {
+        Some(project) => project.clone(),
+        None => projects
+            .entry(cwd.clone())
+            .or_insert_with(|| detect_project(&cwd))
+            .clone(),
+    };
+    Ok(NewJob {
+        command: spec.command.clone(),
+        name: spec.name.clone(),
+        cwd: cwd.to_string_lossy().to_string(),
+        project: project.to_string_lossy().to_string(),
+        timeout_secs: spec.timeout.map(whole_secs),
+        idempotency_key: spec.idempotency_key.clone(),
+        log_max_size: spec.log_max_size,
+        log_keep: spec.log_keep,
+        hooks: spec.hooks.clone(),
+        webhooks: spec.webhooks.clone(),
+        notify: spec.notify,
+    })
+}
+
+fn default_paths() -> Result<Paths> {
+    Paths::new().map_err(|e| Error::Home(e.to_string()))
+}
+
+/// Seconds in `duration`, rounded up so a short timeout isn't zero
+fn whole_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// The jb executable to start the daemon from
+fn jb_exe() -> PathBuf {
+    match std::env::current_exe() {
+        Ok(exe) if exe.file_stem().is_some_and(|stem| stem == "jb") => exe,
+        _ => PathBuf::from("jb"),
+    }
+}
+
+fn start_daemon(paths: &Paths) -> Result<()> {
+    // Spawn daemon detached, pinned to the same state root as this client
+    Command::new(jb_exe())
+        .args(["daemon", "run"])
+        .env(paths::HOME_ENV, paths.root())
         .stdin(Stdio::null())
         .stdout(Stdio::null())
         .stderr(Stdio::null())
@@ -82,3 +454,26 @@ fn start_daemon() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_secs_rounds_up() {
+        assert_eq!(whole_secs(Duration::from_secs(5)), 5);
+        assert_eq!(whole_secs(Duration::from_millis(1500)), 2);
+        assert_eq!(whole_secs(Duration::ZERO), 0);
+    }
+
+    #[test]
+    fn test_unexpected_names_variant() {
+        let err = unexpected(Response::Pong {
+            pid: 1,
+            uptime_secs: 0,
+            running_jobs: 0,
+            total_jobs: 0,
+        });
+        assert_eq!(err.to_string(), "Unexpected response from daemon: Pong");
+    }
+}
@@ -43,14 +43,16 @@ fn delete_jobs(
 
     // Clean up orphaned log files. Query the DB per-file rather than taking a
     // snapshot upfront, so a job spawned between the delete and the scan can't
-    // have its log removed before the daemon opens it.
+    // have its log removed before the daemon opens it. A job's files are all
+    // named `<id>.<ext>[.<n>]` (log, index and rotated segments).
     let log_dir = paths.logs_dir();
     if log_dir.exists() {
         for entry in std::fs::read_dir(&log_dir)? {
             let entry = entry?;
             let path = entry.path();
-            if let Some(stem) = path.file_stem().and_then(|s| s.to_str())
-                && db.get(stem)?.is_none()
+            if let Some(name) = path.file_name().and_then(|s| s.to_str())
+                && let Some((id, _)) = name.split_once('.')
+                && db.get(id)?.is_none()
             {
                 let _ = std::fs::remove_file(&path);
             }
@@ -178,4 +180,22 @@ mod tests {
 
         assert!(!log.exists(), "orphaned log file should be cleaned up");
     }
+
+    #[test]
+    fn test_rotated_segments_follow_their_job() {
+        let (db, paths, _tmp) = setup();
+        db.insert(&job("abc1", Status::Running)).unwrap();
+        let live = crate::core::paths::rotated_path(&paths.log_file("abc1"), 1);
+        let orphan = crate::core::paths::rotated_path(&paths.index_file("xxxx"), 2);
+        std::fs::write(&live, "kept").unwrap();
+        std::fs::write(&orphan, "stale").unwrap();
+
+        delete_jobs("7d", None, false, &db, &paths).unwrap();
+
+        assert!(live.exists(), "rotated log of a live job must survive");
+        assert!(
+            !orphan.exists(),
+            "rotated segment of a deleted job is removed"
+        );
+    }
 }
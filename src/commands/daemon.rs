@@ -1,5 +1,228 @@
+use crate::client::DaemonClient;
+use crate::core::Paths;
+use crate::core::ipc::{Request, Response};
 use anyhow::Result;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::time::Duration;
 
-pub async fn execute() -> Result<()> {
+/// How long `stop` waits for the daemon process to exit
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub async fn run() -> Result<()> {
     crate::daemon::run().await
 }
+
+pub async fn drain(id: &str) -> Result<()> {
+    crate::daemon::spawner::drain(&Paths::new()?, id).await
+}
+
+struct DaemonInfo {
+    pid: u32,
+    uptime_secs: u64,
+    running_jobs: usize,
+    total_jobs: usize,
+}
+
+/// Ping the daemon without auto-starting it. Returns `None` if it isn't reachable.
+async fn ping() -> Option<DaemonInfo> {
+    let mut client = DaemonClient::connect_without_handshake().await.ok()?;
+    match client.send(Request::Ping).await.ok()? {
+        Response::Pong {
+            pid,
+            uptime_secs,
+            running_jobs,
+            total_jobs,
+        } => Some(DaemonInfo {
+            pid,
+            uptime_secs,
+            running_jobs,
+            total_jobs,
+        }),
+        _ => None,
+    }
+}
+
+pub async fn status(json: bool) -> Result<()> {
+    let paths = Paths::new()?;
+    let info = ping().await;
+
+    if json {
+        let status = match &info {
+            Some(i) => serde_json::json!({
+                "running": true,
+                "pid": i.pid,
+                "uptime_secs": i.uptime_secs,
+                "running_jobs": i.running_jobs,
+                "total_jobs": i.total_jobs,
+                "socket": paths.socket(),
+                "log": paths.daemon_log(),
+            }),
+            None => serde_json::json!({
+                "running": false,
+                "socket": paths.socket(),
+                "log": paths.daemon_log(),
+            }),
+        };
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    let Some(info) = info else {
+        println!("Daemon:   stopped");
+        println!("Log:      {}", paths.daemon_log().display());
+        return Ok(());
+    };
+
+    println!("Daemon:   running");
+    println!("PID:      {}", info.pid);
+    println!("Uptime:   {}", format_uptime(info.uptime_secs));
+    println!(
+        "Jobs:     {} running ({} total)",
+        info.running_jobs, info.total_jobs
+    );
+    println!("Socket:   {}", paths.socket().display());
+    println!("Log:      {}", paths.daemon_log().display());
+
+    Ok(())
+}
+
+pub async fn stop(keep_jobs: bool, json: bool) -> Result<()> {
+    let stopped = shutdown(keep_jobs).await?;
+
+    if json {
+        println!("{}", serde_json::json!({ "stopped": stopped }));
+    } else if stopped {
+        println!("Daemon stopped");
+    } else {
+        println!("Daemon not running");
+    }
+    Ok(())
+}
+
+pub async fn restart(keep_jobs: bool, json: bool) -> Result<()> {
+    shutdown(keep_jobs).await?;
+
+    // connect_or_start brings up a fresh daemon
+    DaemonClient::connect_or_start().await?;
+    let info = ping()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("daemon did not respond after restart"))?;
+
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({ "restarted": true, "pid": info.pid })
+        );
+    } else {
+        println!("Daemon restarted (PID {})", info.pid);
+    }
+    Ok(())
+}
+
+/// Ask the daemon to shut down and wait for it to exit.
+/// Returns `false` if no daemon was running.
+async fn shutdown(keep_jobs: bool) -> Result<bool> {
+    let paths = Paths::new()?;
+
+    let Ok(mut client) = DaemonClient::connect_without_handshake().await else {
+        return Ok(false);
+    };
+
+    match client.send(Request::Shutdown { keep_jobs }).await? {
+        Response::Ok => {}
+        _ => anyhow::bail!("Unexpected response from daemon"),
+    }
+    drop(client);
+
+    // The daemon removes its PID file as the last step of shutdown
+    let start = std::time::Instant::now();
+    while paths.pid_file().exists() || DaemonClient::connect_without_handshake().await.is_ok() {
+        if start.elapsed() > STOP_TIMEOUT {
+            anyhow::bail!(
+                "Daemon did not exit within {} seconds",
+                STOP_TIMEOUT.as_secs()
+            );
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+
+    Ok(true)
+}
+
+pub fn logs(tail: Option<usize>, follow: bool) -> Result<()> {
+    let paths = Paths::new()?;
+    let log_path = paths.daemon_log();
+
+    if !log_path.exists() {
+        println!("No daemon log yet");
+        return Ok(());
+    }
+
+    let stdout = std::io::stdout();
+    let mut writer = stdout.lock();
+
+    match tail {
+        Some(n) => {
+            crate::commands::logs::tail_last_n_lines_to_writer(&log_path, n, false, &mut writer)?
+        }
+        None if !follow => {
+            std::io::copy(&mut std::fs::File::open(&log_path)?, &mut writer)?;
+        }
+        None => {}
+    }
+    writer.flush()?;
+
+    if !follow {
+        return Ok(());
+    }
+
+    // Start following from the current end of the log
+    let mut file = std::fs::File::open(&log_path)?;
+    let mut position = file.seek(SeekFrom::End(0))?;
+    let mut buf = vec![0u8; 8192];
+
+    loop {
+        // The log was rotated: continue from the start of the fresh file
+        let len = std::fs::metadata(&log_path).map_or(0, |m| m.len());
+        if len < position {
+            file = std::fs::File::open(&log_path)?;
+            position = 0;
+        }
+
+        file.seek(SeekFrom::Start(position))?;
+        let bytes_read = file.read(&mut buf)?;
+        if bytes_read > 0 {
+            writer.write_all(&buf[..bytes_read])?;
+            writer.flush()?;
+            position += bytes_read as u64;
+        } else {
+            std::thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, mins) = (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60);
+    if days > 0 {
+        format!("{days}d {hours}h")
+    } else if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else if mins > 0 {
+        format!("{mins}m")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uptime() {
+        assert_eq!(format_uptime(42), "42s");
+        assert_eq!(format_uptime(125), "2m");
+        assert_eq!(format_uptime(3 * 3600 + 120), "3h 2m");
+        assert_eq!(format_uptime(2 * 86400 + 5 * 3600), "2d 5h");
+    }
+}
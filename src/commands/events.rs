@@ -0,0 +1,98 @@
+use crate::client::DaemonClient;
+use crate::core::event::{EventFilter, EventKind, JobEvent};
+use crate::core::{Status, detect_project};
+use anyhow::{Context, Result};
+use chrono::SecondsFormat;
+use colored::Colorize;
+use std::path::Path;
+
+pub struct EventsOptions {
+    /// Only jobs in the project containing this directory
+    pub project: Option<String>,
+    pub name: Option<String>,
+    pub status: Option<String>,
+    /// Exit after this many events
+    pub count: Option<usize>,
+    pub json: bool,
+}
+
+pub async fn execute(opts: EventsOptions) -> Result<()> {
+    let project = opts
+        .project
+        .map(|dir| {
+            let dir = Path::new(&dir)
+                .canonicalize()
+                .with_context(|| format!("Invalid project directory: {dir}"))?;
+            anyhow::Ok(detect_project(&dir))
+        })
+        .transpose()?;
+    let status = opts.status.map(|s| s.parse::<Status>()).transpose()?;
+    let filter = EventFilter {
+        project,
+        name: opts.name,
+        status,
+    };
+
+    let client = DaemonClient::connect_or_start().await?;
+    let mut events = client.events(filter).await?;
+    let mut seen = 0;
+    while opts.count.is_none_or(|count| seen < count) {
+        let event = events.next().await.context("Lost connection to daemon")?;
+        if opts.json {
+            println!("{}", serde_json::to_string(&event)?);
+        } else {
+            println!("{}", format_event(&event));
+        }
+        seen += 1;
+    }
+    Ok(())
+}
+
+/// `2026-01-05T12:04:31.120Z finished a3x9 build  failed (exit 2)  cargo build`
+fn format_event(event: &JobEvent) -> String {
+    let job = &event.job;
+    let kind = format!("{:<8}", event.kind.as_str());
+    let kind = match event.kind {
+        EventKind::Created => kind.yellow(),
+        EventKind::Started => kind.cyan(),
+        EventKind::Finished if job.status == Status::Completed => kind.green(),
+        EventKind::Finished => kind.red(),
+        EventKind::Stopped => kind.magenta(),
+    };
+    let outcome = match job.exit_code {
+        Some(code) if job.status.is_terminal() => format!("{} (exit {code})", job.status),
+        _ => job.status.to_string(),
+    };
+    format!(
+        "{} {} {} {:<12} {:<20} {}",
+        event
+            .at
+            .to_rfc3339_opts(SecondsFormat::Millis, true)
+            .dimmed(),
+        kind,
+        job.short_id(),
+        job.name.as_deref().unwrap_or("-"),
+        outcome,
+        job.command
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Job;
+
+    #[test]
+    fn test_format_event() {
+        colored::control::set_override(false);
+        let mut job = Job::new("ab12".into(), "make".into(), "/p".into(), "/p".into());
+        job.name = Some("build".into());
+        job.status = Status::Failed;
+        job.exit_code = Some(2);
+        let line = format_event(&JobEvent::now(job));
+        assert!(
+            line.ends_with(" finished ab12 build        failed (exit 2)      make"),
+            "{line}"
+        );
+    }
+}
@@ -0,0 +1,301 @@
+use crate::core::db::{SNIPPET_END, SNIPPET_START};
+use crate::core::output::{self, Segment};
+use crate::core::{Database, Job, Paths, Status, UserError, parse_timestamp};
+use anyhow::Result;
+use colored::Colorize;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{BufRead, BufReader, IsTerminal, Read};
+
+/// How `jb grep` selects jobs and prints matches
+#[derive(Debug, Default)]
+pub struct GrepOptions {
+    /// Regular expression matched against each line of output
+    pub pattern: String,
+    pub ignore_case: bool,
+    /// Only jobs with this status
+    pub status: Option<String>,
+    /// Only jobs whose name matches this glob
+    pub name: Option<String>,
+    /// Only jobs created at or after this time ("1d" ago or RFC 3339)
+    pub since: Option<String>,
+    /// Lines of context before and after each match
+    pub context: usize,
+    /// Treat the pattern as a full-text query against the search index, and
+    /// rank jobs by relevance instead of scanning every log
+    pub indexed: bool,
+    pub json: bool,
+}
+
+/// A matching line and the lines around it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LineMatch {
+    /// 1-based line number in the job's combined output
+    pub line: usize,
+    pub text: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+pub fn execute(opts: &GrepOptions) -> Result<()> {
+    let status = opts
+        .status
+        .as_deref()
+        .map(str::parse::<Status>)
+        .transpose()?;
+    let since = opts.since.as_deref().map(parse_timestamp).transpose()?;
+
+    let paths = Paths::new()?;
+    let db = Database::open(&paths)?;
+    let colorize = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+
+    if opts.indexed {
+        let hits = db.search_output(&opts.pattern, status, opts.name.as_deref(), since)?;
+        return print_hits(&hits, opts.json, colorize);
+    }
+
+    let regex = RegexBuilder::new(&opts.pattern)
+        .case_insensitive(opts.ignore_case)
+        .build()
+        .map_err(|e| UserError::new(format!("Invalid pattern: {e}")))?;
+    let jobs = db.find(status, opts.name.as_deref(), since)?;
+
+    let mut results = Vec::new();
+    let mut found = false;
+    for job in jobs {
+        let active = Segment::active(&paths, &job.id);
+        if !active.exists() {
+            continue;
+        }
+        let matches = search(output::open_log(&active)?, &regex, opts.context)?;
+        if matches.is_empty() {
+            continue;
+        }
+        if opts.json {
+            results.push(serde_json::json!({
+                "id": job.id,
+                "name": job.name,
+                "status": job.status,
+                "created_at": job.created_at,
+                "matches": matches,
+            }));
+        } else {
+            // Separate jobs the way groups within a job are separated
+            if found && opts.context > 0 {
+                println!("{}", separator(colorize));
+            }
+            print_matches(&job, &matches, &regex, opts.context, colorize);
+        }
+        found = true;
+    }
+
+    if opts.json {
+        println!("{}", serde_json::to_string(&results)?);
+    }
+    // Like grep: exit 1 when nothing matched
+    if !found {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Print ranked index hits: each job with a snippet of its matching output.
+fn print_hits(hits: &[(Job, String)], json: bool, colorize: bool) -> Result<()> {
+    let plain = |snippet: &str| snippet.replace([SNIPPET_START, SNIPPET_END], "");
+    if json {
+        let results: Vec<_> = hits
+            .iter()
+            .map(|(job, snippet)| {
+                serde_json::json!({
+                    "id": job.id,
+                    "name": job.name,
+                    "status": job.status,
+                    "created_at": job.created_at,
+                    "snippet": plain(snippet),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string(&results)?);
+    }
+    for (job, snippet) in hits.iter().filter(|_| !json) {
+        let name = job.name.as_deref().unwrap_or("-");
+        let snippet = if colorize {
+            highlight_snippet(snippet)
+        } else {
+            plain(snippet)
+        };
+        if colorize {
+            println!(
+                "{} {} {}",
+                job.short_id().magenta(),
+                job.status,
+                name.dimmed()
+            );
+        } else {
+            println!("{} {} {name}", job.short_id(), job.status);
+        }
+        for line in snippet.lines().filter(|l| !l.trim().is_empty()) {
+            println!("    {line}");
+        }
+    }
+    if hits.is_empty() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+/// Color the marked terms of an index snippet.
+fn highlight_snippet(snippet: &str) -> String {
+    let mut out = String::with_capacity(snippet.len());
+    let mut rest = snippet;
+    while let Some(start) = rest.find(SNIPPET_START) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start + SNIPPET_START.len_utf8()..];
+        let end = rest.find(SNIPPET_END).unwrap_or(rest.len());
+        out.push_str(&rest[..end].red().bold().to_string());
+        rest = rest.get(end + SNIPPET_END.len_utf8()..).unwrap_or("");
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Every line of `reader` matching `regex`, with up to `context` lines around it.
+fn search(reader: impl Read, regex: &Regex, context: usize) -> Result<Vec<LineMatch>> {
+    let mut reader = BufReader::new(reader);
+    let mut before: VecDeque<String> = VecDeque::with_capacity(context);
+    let mut matches: Vec<LineMatch> = Vec::new();
+    let mut buf = Vec::new();
+    let mut number = 0;
+    loop {
+        buf.clear();
+        if reader.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        number += 1;
+        let line = buf.strip_suffix(b"\n").unwrap_or(&buf);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let text = String::from_utf8_lossy(line).into_owned();
+
+        // Earlier matches still collecting their trailing context
+        for m in matches
+            .iter_mut()
+            .rev()
+            .take_while(|m| number - m.line <= context)
+        {
+            m.after.push(text.clone());
+        }
+        if regex.is_match(&text) {
+            matches.push(LineMatch {
+                line: number,
+                text: text.clone(),
+                before: before.iter().cloned().collect(),
+                after: Vec::new(),
+            });
+        }
+        if context > 0 {
+            if before.len() == context {
+                before.pop_front();
+            }
+            before.push_back(text);
+        }
+    }
+    Ok(matches)
+}
+
+/// Print a job's matches grep-style: `id:line:text` for matches, `id-line-text`
+/// for context, and `--` between groups that aren't adjacent.
+fn print_matches(job: &Job, matches: &[LineMatch], regex: &Regex, context: usize, colorize: bool) {
+    // Overlapping context is printed once
+    let mut lines: BTreeMap<usize, (&str, bool)> = BTreeMap::new();
+    for m in matches {
+        let first = m.line - m.before.len();
+        for (i, text) in m.before.iter().enumerate() {
+            lines.entry(first + i).or_insert((text, false));
+        }
+        lines.insert(m.line, (&m.text, true));
+        for (i, text) in m.after.iter().enumerate() {
+            lines.entry(m.line + 1 + i).or_insert((text, false));
+        }
+    }
+
+    let id = job.short_id();
+    let mut last = None;
+    for (number, (text, is_match)) in lines {
+        if context > 0 && last.is_some_and(|l| number > l + 1) {
+            println!("{}", separator(colorize));
+        }
+        last = Some(number);
+
+        let sep = if is_match { ":" } else { "-" };
+        if colorize {
+            let text = if is_match {
+                regex
+                    .replace_all(text, |caps: &regex::Captures| {
+                        caps[0].red().bold().to_string()
+                    })
+                    .into_owned()
+            } else {
+                text.to_string()
+            };
+            println!(
+                "{}{}{}{}{text}",
+                id.magenta(),
+                sep.cyan(),
+                number.to_string().green(),
+                sep.cyan()
+            );
+        } else {
+            println!("{id}{sep}{number}{sep}{text}");
+        }
+    }
+}
+
+fn separator(colorize: bool) -> String {
+    if colorize {
+        "--".cyan().to_string()
+    } else {
+        "--".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(input: &str, pattern: &str, context: usize) -> Vec<LineMatch> {
+        search(input.as_bytes(), &Regex::new(pattern).unwrap(), context).unwrap()
+    }
+
+    #[test]
+    fn test_search_reports_line_numbers_and_context() {
+        let matches = grep("a\nb\npanic: x\nc\nd\ne\n", "panic", 2);
+        assert_eq!(
+            matches,
+            vec![LineMatch {
+                line: 3,
+                text: "panic: x".into(),
+                before: vec!["a".into(), "b".into()],
+                after: vec!["c".into(), "d".into()],
+            }]
+        );
+    }
+
+    #[test]
+    fn test_search_overlapping_context() {
+        let matches = grep("err 1\nok\nerr 2\r\nok\n", "^err", 1);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].after, vec!["ok"]);
+        assert_eq!(matches[1].before, vec!["ok"]);
+        assert_eq!(matches[1].text, "err 2");
+        assert_eq!(matches[1].after, vec!["ok"]);
+    }
+
+    #[test]
+    fn test_search_without_context_or_trailing_newline() {
+        let matches = grep("x\ny", "y", 0);
+        assert_eq!(matches.len(), 1);
+        assert_eq!(matches[0].line, 2);
+        assert!(matches[0].before.is_empty() && matches[0].after.is_empty());
+    }
+}
@@ -1,4 +1,4 @@
-use crate::core::{Database, Paths, Status};
+use crate::core::{Database, Job, Paths, Status};
 use anyhow::Result;
 use colored::Colorize;
 
@@ -10,7 +10,30 @@ fn query_jobs(
     limit: Option<usize>,
     all: bool,
     db: &Database,
-) -> Result<Vec<crate::core::Job>> {
+) -> Result<Vec<Job>> {
+    let (status, limit) = status_and_limit(status_filter, failed, limit, all)?;
+    db.list(status, limit)
+}
+
+/// Like `query_jobs`, but only jobs whose indexed output contains `text`
+fn query_jobs_containing(
+    text: &str,
+    status_filter: Option<String>,
+    failed: bool,
+    limit: Option<usize>,
+    all: bool,
+    db: &Database,
+) -> Result<Vec<Job>> {
+    let (status, limit) = status_and_limit(status_filter, failed, limit, all)?;
+    db.list_output_contains(Some(text), status, limit)
+}
+
+fn status_and_limit(
+    status_filter: Option<String>,
+    failed: bool,
+    limit: Option<usize>,
+    all: bool,
+) -> Result<(Option<Status>, Option<usize>)> {
     let status = if failed {
         Some(Status::Failed)
     } else {
@@ -23,7 +46,7 @@ fn query_jobs(
         Some(limit.unwrap_or(DEFAULT_LIMIT))
     };
 
-    db.list(status, effective_limit)
+    Ok((status, effective_limit))
 }
 
 pub fn execute(
@@ -31,6 +54,7 @@ pub fn execute(
     failed: bool,
     limit: Option<usize>,
     all: bool,
+    output_contains: Option<&str>,
     json: bool,
 ) -> Result<()> {
     let paths = Paths::new()?;
@@ -39,7 +63,10 @@ pub fn execute(
     // Check for orphaned jobs (dead processes still marked running)
     db.recover_orphans();
 
-    let jobs = query_jobs(status_filter, failed, limit, all, &db)?;
+    let jobs = match output_contains {
+        Some(text) => query_jobs_containing(text, status_filter, failed, limit, all, &db)?,
+        None => query_jobs(status_filter, failed, limit, all, &db)?,
+    };
 
     if json {
         println!("{}", serde_json::to_string(&jobs)?);
@@ -224,4 +251,17 @@ mod tests {
         assert_eq!(jobs.len(), 1);
         assert_eq!(jobs[0].id, "a");
     }
+
+    #[test]
+    fn test_output_contains_filters_by_indexed_output() {
+        let (db, _tmp) = setup();
+        db.insert(&job("a", Status::Failed)).unwrap();
+        db.insert(&job("b", Status::Failed)).unwrap();
+        db.index_output("a", "error: disk full\n").unwrap();
+        db.index_output("b", "all good\n").unwrap();
+
+        let jobs = query_jobs_containing("disk full", None, true, None, true, &db).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].id, "a");
+    }
 }
@@ -1,11 +1,19 @@
-use crate::core::{Database, Paths};
+use crate::client::{DaemonClient, Subscription, SubscriptionEvent};
+use crate::core::asciicast;
+use crate::core::excerpt::{Excerpt, ExcerptLine, LineLevel};
+use crate::core::output::{
+    self, Cursor, CursorRead, IndexedOutput, LineSplitter, OutputChunk, OutputTail, ResumeFilter,
+    Segment, Stream, StreamReader, TimedLine, TimedLines,
+};
+use crate::core::{Database, Job, Paths, Status, UserError, parse_timestamp};
+use crate::error::Error;
 use anyhow::Result;
-use colored::Colorize;
+use chrono::{DateTime, SecondsFormat, Utc};
+use colored::{Color, Colorize};
+use std::collections::{HashSet, VecDeque};
 use std::io::{BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 use std::process::{Command, Stdio};
-use std::sync::Arc;
-use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
 type WriterFn = Box<dyn FnOnce(&mut dyn Write) -> Result<()>>;
@@ -14,7 +22,68 @@ fn should_colorize() -> bool {
     std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none()
 }
 
-pub fn execute(id: &str, tail: Option<usize>, follow: bool, pager: bool) -> Result<()> {
+/// How `jb logs` selects and prints output
+#[derive(Debug, Default)]
+pub struct LogOptions {
+    /// Show only the last N lines
+    pub tail: Option<usize>,
+    pub follow: bool,
+    pub pager: bool,
+    /// Only this stream (default: both, interleaved)
+    pub stream: Option<Stream>,
+    /// Prefix each line with the time it was received
+    pub timestamps: bool,
+    /// Only lines received at or after this time ("5m" ago or RFC 3339)
+    pub since: Option<String>,
+    /// Only lines received before this time
+    pub until: Option<String>,
+    /// Show an excerpt of about this many tokens: head, tail and error lines
+    pub budget: Option<usize>,
+    /// Only output after this cursor (from a previous read), and the next cursor
+    pub since_cursor: Option<String>,
+    pub format: LogFormat,
+    pub json: bool,
+}
+
+/// How `jb logs` writes the output
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LogFormat {
+    /// Plain text, colorized on a terminal
+    #[default]
+    Text,
+    /// An asciinema v2 recording with the real timing
+    Asciicast,
+}
+
+/// Time window for timestamp filtering
+#[derive(Debug, Clone, Copy, Default)]
+struct TimeRange {
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl TimeRange {
+    fn is_set(self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    fn contains(self, at: Option<DateTime<Utc>>) -> bool {
+        let Some(at) = at else {
+            return !self.is_set();
+        };
+        self.since.is_none_or(|s| at >= s) && self.until.is_none_or(|u| at < u)
+    }
+}
+
+pub async fn execute(id: &str, opts: &LogOptions) -> Result<()> {
+    let range = TimeRange {
+        since: opts.since.as_deref().map(parse_timestamp).transpose()?,
+        until: opts.until.as_deref().map(parse_timestamp).transpose()?,
+    };
+    let (tail, follow, pager, stream) = (opts.tail, opts.follow, opts.pager, opts.stream);
+    // Timestamps live in the chunk index, so these modes read through it
+    let timed = opts.timestamps || range.is_set();
+
     let paths = Paths::new()?;
     let db = Database::open(&paths)?;
 
@@ -25,14 +94,32 @@ pub fn execute(id: &str, tail: Option<usize>, follow: bool, pager: bool) -> Resu
     }
 
     let job = db.resolve(id)?;
-    let log_path = paths.log_file(&job.id);
+    let active = Segment::active(&paths, &job.id);
+
+    if let Some(token) = &opts.since_cursor {
+        let cursor: Cursor = token
+            .parse()
+            .map_err(|e| UserError::new(format!("Invalid cursor '{token}': {e}")))?;
+        return read_since_cursor(&job, &active, cursor, stream, opts.json);
+    }
+
+    if opts.format == LogFormat::Asciicast {
+        let (start, events) = open_cast(&active, &job, stream)?;
+        let mut out = std::io::stdout().lock();
+        writeln!(out, "{}", asciicast::header(&job, start))?;
+        for event in events {
+            writeln!(out, "{}", event?.to_line())?;
+        }
+        return Ok(());
+    }
 
     if follow {
-        return follow_logs(&db, &paths, &job.id, &log_path);
+        let printer = FollowPrinter::new(&job.id, stream, timed, opts.timestamps, range);
+        return follow_output(&paths, &job.id, stream, printer).await;
     }
 
     // Non-follow mode: read existing content
-    if !log_path.exists() {
+    if !active.exists() {
         println!("No output yet");
         return Ok(());
     }
@@ -40,41 +127,301 @@ pub fn execute(id: &str, tail: Option<usize>, follow: bool, pager: bool) -> Resu
     let colorize = should_colorize();
     let use_pager = colorize && pager;
 
-    if let Some(n) = tail {
-        // Efficient tail: read last N lines without loading entire file
-        if use_pager {
-            output_with_pager(|| tail_lines_to_writer(&log_path, n, colorize))?;
-        } else {
-            let stdout = std::io::stdout();
-            let mut writer = stdout.lock();
-            tail_lines_to_writer(&log_path, n, colorize)(&mut writer)?;
+    let timestamps = opts.timestamps;
+    let content_fn = || -> Result<WriterFn> {
+        if let Some(budget) = opts.budget {
+            let lines = open_lines(
+                &active,
+                &job.id,
+                stream,
+                timed.then_some((range, timestamps)),
+            )?;
+            return Ok(excerpt_to_writer(lines, budget, colorize));
         }
-    } else {
-        // Stream entire file
-        if use_pager {
-            output_with_pager(|| stream_file_to_writer(&log_path, colorize))?;
-        } else {
-            let stdout = std::io::stdout();
-            let mut writer = stdout.lock();
-            stream_file_to_writer(&log_path, colorize)(&mut writer)?;
+        if timed {
+            let lines = open_timed_lines(&active, &job.id, stream)?;
+            return Ok(timed_lines_to_writer(
+                lines, range, tail, timestamps, colorize,
+            ));
         }
+        // A single plain file can be tailed by seeking; segments and compressed
+        // logs are read front to back
+        let single_file = active.existing().len() == 1 && active.log.exists();
+        Ok(match (stream, tail) {
+            // Efficient tail: read last N lines without loading entire file
+            (None, Some(n)) if single_file => tail_lines_to_writer(&active.log, n, colorize),
+            (None, Some(n)) => tail_reader_to_writer(output::open_log(&active)?, n, colorize),
+            // Stream the whole log, across rotated segments
+            (None, None) => stream_reader_to_writer(output::open_log(&active)?, colorize),
+            (Some(s), tail) => {
+                let reader = open_stream(&active, &job.id, s)?;
+                match tail {
+                    Some(n) => tail_reader_to_writer(reader, n, colorize),
+                    None => stream_reader_to_writer(reader, colorize),
+                }
+            }
+        })
+    };
+
+    if use_pager {
+        let content = content_fn()?;
+        output_with_pager(|| content)?;
+    } else {
+        let stdout = std::io::stdout();
+        let mut writer = stdout.lock();
+        content_fn()?(&mut writer)?;
     }
 
     Ok(())
 }
 
-fn colorize_line(line: &str) -> String {
-    let lower = line.to_lowercase();
-    if lower.contains("error") || lower.contains("fatal") || lower.contains("panic") {
-        line.red().to_string()
-    } else if lower.contains("warn") {
-        line.yellow().to_string()
-    } else if lower.contains("info") {
-        line.blue().to_string()
-    } else if lower.contains("debug") || lower.contains("trace") {
-        line.dimmed().to_string()
+/// Print the output after `cursor` and the cursor to continue from. The job's
+/// status is read first, so once it is terminal the output is complete.
+fn read_since_cursor(
+    job: &Job,
+    active: &Segment,
+    cursor: Cursor,
+    stream: Option<Stream>,
+    json: bool,
+) -> Result<()> {
+    let read = read_after_cursor(job, active, cursor, stream, None)?;
+
+    if json {
+        let value = serde_json::json!({
+            "id": job.id,
+            "status": job.status,
+            "cursor": read.cursor.to_string(),
+            "data": String::from_utf8_lossy(&read.data),
+            "bytes": read.data.len(),
+            "skipped": read.skipped,
+            "reset": read.reset,
+        });
+        println!("{value}");
     } else {
-        line.to_string()
+        let mut out = std::io::stdout().lock();
+        out.write_all(&read.data)?;
+        out.flush()?;
+        eprintln!("Next cursor: {}", read.cursor);
+    }
+    Ok(())
+}
+
+/// Read a job's output after `cursor`, at most `max_bytes` if given.
+pub(crate) fn read_after_cursor(
+    job: &Job,
+    active: &Segment,
+    cursor: Cursor,
+    stream: Option<Stream>,
+    max_bytes: Option<usize>,
+) -> Result<CursorRead> {
+    if !active.exists() {
+        Ok(CursorRead {
+            cursor,
+            ..CursorRead::default()
+        })
+    } else if let Some(output) = IndexedOutput::load(active)? {
+        CursorRead::from_index(&output, cursor, stream, max_bytes)
+    } else if let Some(s) = stream {
+        Err(no_stream_capture(&job.id, s).into())
+    } else {
+        CursorRead::from_log(active, cursor, max_bytes)
+    }
+}
+
+/// Open one stream of a job's output via its chunk index.
+fn open_stream(active: &Segment, job_id: &str, stream: Stream) -> Result<StreamReader> {
+    let Some(output) = IndexedOutput::load(active)? else {
+        anyhow::bail!(no_stream_capture(job_id, stream));
+    };
+    Ok(StreamReader::new(output, stream))
+}
+
+fn no_stream_capture(job_id: &str, stream: Stream) -> UserError {
+    let name = match stream {
+        Stream::Stdout => "stdout",
+        Stream::Stderr => "stderr",
+    };
+    UserError::new(format!(
+        "Job {job_id} has no separate {name} capture (recorded by an older jb)"
+    ))
+}
+
+/// Open a job's output as timestamped lines via its chunk index.
+fn open_timed_lines(active: &Segment, job_id: &str, stream: Option<Stream>) -> Result<TimedLines> {
+    let output = IndexedOutput::load(active)?;
+    let Some(output) = output.filter(|o| o.chunks().all(|c| c.at.is_some())) else {
+        anyhow::bail!(no_timestamps(job_id));
+    };
+    Ok(TimedLines::new(output, stream))
+}
+
+pub(crate) type CastEvents = Box<dyn Iterator<Item = Result<asciicast::Event>>>;
+
+/// A job's output as terminal output events, timed from the job's start, which
+/// is also returned. Needs the chunk index for the timing.
+pub(crate) fn open_cast(
+    active: &Segment,
+    job: &Job,
+    stream: Option<Stream>,
+) -> Result<(DateTime<Utc>, CastEvents)> {
+    if !active.exists() {
+        let start = job.started_at.unwrap_or(job.created_at);
+        return Ok((start, Box::new(std::iter::empty())));
+    }
+    let output = IndexedOutput::load(active)?;
+    let Some(output) = output.filter(|o| o.chunks().all(|c| c.at.is_some())) else {
+        anyhow::bail!(no_timestamps(&job.id));
+    };
+    let first = output.chunks().next().and_then(|c| c.at);
+    let start = match (job.started_at, first) {
+        (Some(started), Some(first)) => started.min(first),
+        (started, first) => started.or(first).unwrap_or(job.created_at),
+    };
+
+    let mut recorder = asciicast::Recorder::new(start);
+    let events = output
+        .read_chunks(stream)
+        .filter_map(move |chunk| match chunk {
+            Ok((chunk, data)) => recorder.push(chunk.at, &data).map(Ok),
+            Err(e) => Some(Err(e.into())),
+        });
+    Ok((start, Box::new(events)))
+}
+
+/// Error for a job without the chunk index a stream or timestamp view needs
+fn no_index_error(job_id: &str, stream: Option<Stream>) -> UserError {
+    match stream {
+        Some(s) => no_stream_capture(job_id, s),
+        None => no_timestamps(job_id),
+    }
+}
+
+fn no_timestamps(job_id: &str) -> UserError {
+    UserError::new(format!(
+        "Job {job_id} has no output timestamps (recorded by an older jb)"
+    ))
+}
+
+type Lines = Box<dyn Iterator<Item = Result<String>>>;
+
+/// A job's output as plain lines: the combined log or one stream, or with `timed`,
+/// lines in a time range (optionally with their timestamps).
+fn open_lines(
+    active: &Segment,
+    job_id: &str,
+    stream: Option<Stream>,
+    timed: Option<(TimeRange, bool)>,
+) -> Result<Lines> {
+    if let Some((range, timestamps)) = timed {
+        let lines = open_timed_lines(active, job_id, stream)?;
+        return Ok(Box::new(lines.filter_map(move |line| match line {
+            Ok(line) if range.contains(line.at) => {
+                Some(Ok(format_timed_line(&line, timestamps, false)))
+            }
+            Ok(_) => None,
+            Err(e) => Some(Err(e.into())),
+        })));
+    }
+    let reader: Box<dyn Read> = match stream {
+        Some(s) => Box::new(open_stream(active, job_id, s)?),
+        None => output::open_log(active)?,
+    };
+    Ok(Box::new(
+        BufReader::new(reader)
+            .lines()
+            .map(|l| l.map_err(Into::into)),
+    ))
+}
+
+/// Print a budgeted excerpt of `lines`, marking the elided stretches.
+fn excerpt_to_writer(lines: Lines, budget: usize, colorize: bool) -> WriterFn {
+    Box::new(move |writer: &mut dyn Write| {
+        let mut excerpt = Excerpt::new(budget);
+        for line in lines {
+            excerpt.push(&line?);
+        }
+        for line in excerpt.finish() {
+            match line {
+                ExcerptLine::Line { text, .. } if colorize => {
+                    writeln!(writer, "{}", colorize_line(&text))?;
+                }
+                ExcerptLine::Line { text, .. } => writeln!(writer, "{text}")?,
+                ExcerptLine::Skipped(n) => {
+                    let marker = format!("[... {n} lines skipped ...]");
+                    if colorize {
+                        writeln!(writer, "{}", marker.dimmed())?;
+                    } else {
+                        writeln!(writer, "{marker}")?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn timed_lines_to_writer(
+    lines: TimedLines,
+    range: TimeRange,
+    tail: Option<usize>,
+    timestamps: bool,
+    colorize: bool,
+) -> WriterFn {
+    Box::new(move |writer: &mut dyn Write| {
+        let selected = lines.filter(|l| l.as_ref().map_or(true, |l| range.contains(l.at)));
+        match tail {
+            Some(n) => {
+                let mut last: VecDeque<TimedLine> = VecDeque::with_capacity(n + 1);
+                for line in selected {
+                    last.push_back(line?);
+                    if last.len() > n {
+                        last.pop_front();
+                    }
+                }
+                for line in last {
+                    writeln!(writer, "{}", format_timed_line(&line, timestamps, colorize))?;
+                }
+            }
+            None => {
+                for line in selected {
+                    writeln!(
+                        writer,
+                        "{}",
+                        format_timed_line(&line?, timestamps, colorize)
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+fn format_timed_line(line: &TimedLine, timestamps: bool, colorize: bool) -> String {
+    let text = if colorize {
+        colorize_line(&line.text)
+    } else {
+        line.text.clone()
+    };
+    match line.at {
+        Some(at) if timestamps => {
+            let stamp = at.to_rfc3339_opts(SecondsFormat::Millis, true);
+            if colorize {
+                format!("{} {text}", stamp.dimmed())
+            } else {
+                format!("{stamp} {text}")
+            }
+        }
+        _ => text,
+    }
+}
+
+fn colorize_line(line: &str) -> String {
+    match LineLevel::detect(line) {
+        Some(LineLevel::Error) => line.red().to_string(),
+        Some(LineLevel::Warn) => line.yellow().to_string(),
+        Some(LineLevel::Info) => line.blue().to_string(),
+        Some(LineLevel::Debug) => line.dimmed().to_string(),
+        None => line.to_string(),
     }
 }
 
@@ -113,13 +460,33 @@ where
     Ok(())
 }
 
-fn stream_file_to_writer(path: &Path, colorize: bool) -> WriterFn {
-    let path = path.to_path_buf();
+fn stream_reader_to_writer(reader: impl Read + 'static, colorize: bool) -> WriterFn {
+    Box::new(move |writer: &mut dyn Write| write_lines(BufReader::new(reader), colorize, writer))
+}
+
+fn write_lines(reader: impl BufRead, colorize: bool, writer: &mut dyn Write) -> Result<()> {
+    for line in reader.lines() {
+        let line = line?;
+        if colorize {
+            writeln!(writer, "{}", colorize_line(&line))?;
+        } else {
+            writeln!(writer, "{}", line)?;
+        }
+    }
+    Ok(())
+}
+
+/// Tail a reader that can't seek backwards: keep the last N lines in a ring buffer.
+fn tail_reader_to_writer(reader: impl Read + 'static, n: usize, colorize: bool) -> WriterFn {
     Box::new(move |writer: &mut dyn Write| {
-        let file = std::fs::File::open(&path)?;
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line?;
+        let mut last: VecDeque<String> = VecDeque::with_capacity(n + 1);
+        for line in BufReader::new(reader).lines() {
+            last.push_back(line?);
+            if last.len() > n {
+                last.pop_front();
+            }
+        }
+        for line in last {
             if colorize {
                 writeln!(writer, "{}", colorize_line(&line))?;
             } else {
@@ -137,7 +504,7 @@ fn tail_lines_to_writer(path: &Path, n: usize, colorize: bool) -> WriterFn {
 
 /// Read last N lines from a file without loading the entire file into memory.
 /// Uses backward chunk reading to find line boundaries efficiently.
-fn tail_last_n_lines_to_writer(
+pub(crate) fn tail_last_n_lines_to_writer(
     path: &Path,
     n: usize,
     colorize: bool,
@@ -192,158 +559,426 @@ fn tail_last_n_lines_to_writer(
 
     // Stream from start_pos to end
     file.seek(SeekFrom::Start(start_pos))?;
-    let reader = BufReader::new(file);
-    for line in reader.lines() {
-        let line = line?;
-        if colorize {
-            writeln!(writer, "{}", colorize_line(&line))?;
-        } else {
-            writeln!(writer, "{}", line)?;
+    write_lines(BufReader::new(file), colorize, writer)
+}
+
+/// Follow a job's output until it finishes, then exit with its exit code.
+async fn follow_output(
+    paths: &Paths,
+    job_id: &str,
+    stream: Option<Stream>,
+    mut printer: FollowPrinter,
+) -> Result<()> {
+    let mut follower = JobFollower::connect(paths, job_id, stream, printer.needs_index()).await;
+
+    // On Ctrl+C just stop following (job continues)
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    loop {
+        let event = tokio::select! {
+            event = follower.next() => event?,
+            _ = &mut ctrl_c => return Ok(()),
+        };
+        match event {
+            FollowEvent::Output(chunks) => printer.print(chunks)?,
+            FollowEvent::Finished(job) => {
+                printer.finish()?;
+                return exit_with(&job);
+            }
         }
     }
-
-    Ok(())
 }
 
-fn follow_logs(db: &Database, _paths: &Paths, job_id: &str, log_path: &Path) -> Result<()> {
-    let colorize = should_colorize();
+/// Follow several jobs at once, docker-compose style: lines are interleaved as
+/// they arrive, each prefixed with its job's name (or ID). Exits once all are
+/// done: 0 if every job succeeded, else with the code of the first one that
+/// didn't (1 if it has none).
+pub async fn execute_many(ids: &[String], name: Option<&str>, opts: &LogOptions) -> Result<()> {
+    if !opts.follow {
+        anyhow::bail!(UserError::new(
+            "Several jobs can only be shown together with --follow"
+        ));
+    }
+    let range = TimeRange {
+        since: opts.since.as_deref().map(parse_timestamp).transpose()?,
+        until: None,
+    };
+    let timed = opts.timestamps || range.is_set();
 
-    // Set up Ctrl+C handler - on interrupt, just exit cleanly (job continues)
-    let interrupted = Arc::new(AtomicBool::new(false));
-    let int_clone = Arc::clone(&interrupted);
-    ctrlc_handler(move || {
-        int_clone.store(true, Ordering::SeqCst);
-    });
-
-    // Wait for log file to exist (job might be pending)
-    while !log_path.exists() {
-        if interrupted.load(Ordering::SeqCst) {
-            return Ok(());
+    let paths = Paths::new()?;
+    let db = Database::open(&paths)?;
+    let mut jobs = Vec::new();
+    for id in ids {
+        jobs.push(db.resolve(id)?);
+    }
+    if let Some(pattern) = name {
+        let matched = db.latest_by_name_glob(pattern)?;
+        if matched.is_empty() {
+            anyhow::bail!(UserError::new(format!(
+                "No job has a name matching '{pattern}'"
+            )));
         }
+        jobs.extend(matched);
+    }
+    let mut seen = HashSet::new();
+    jobs.retain(|job| seen.insert(job.id.clone()));
+
+    let colorize = should_colorize();
+    let labels: Vec<String> = jobs
+        .iter()
+        .map(|job| {
+            job.name
+                .clone()
+                .unwrap_or_else(|| job.short_id().to_string())
+        })
+        .collect();
+    let width = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut printers = Vec::new();
+    for (i, (job, label)) in jobs.iter().zip(&labels).enumerate() {
+        let printer = FollowPrinter::new(&job.id, opts.stream, timed, opts.timestamps, range)
+            .with_prefix(job_prefix(label, width, i, colorize));
+        let mut follower =
+            JobFollower::connect(&paths, &job.id, opts.stream, printer.needs_index()).await;
+        printers.push(printer);
 
-        // Check if job still exists and is not terminal
-        if let Some(job) = db.get(job_id)? {
-            if job.status.is_terminal() {
-                // Job finished before creating output
-                eprintln!("Job finished with no output");
-                if let Some(code) = job.exit_code {
-                    std::process::exit(code);
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            loop {
+                let event = follower.next().await;
+                let done = !matches!(event, Ok(FollowEvent::Output(_)));
+                if tx.send((i, event)).is_err() || done {
+                    break;
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    // On Ctrl+C just stop following (jobs continue)
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    let mut remaining = jobs.len();
+    let mut exit_code = 0;
+    while remaining > 0 {
+        let (i, event) = tokio::select! {
+            event = rx.recv() => event.ok_or_else(|| anyhow::anyhow!("follower stopped early"))?,
+            _ = &mut ctrl_c => return Ok(()),
+        };
+        match event? {
+            FollowEvent::Output(chunks) => printers[i].print(chunks)?,
+            FollowEvent::Finished(job) => {
+                printers[i].finish()?;
+                remaining -= 1;
+                if exit_code == 0 && job.status != Status::Completed {
+                    exit_code = job.exit_code.filter(|&c| c != 0).unwrap_or(1);
                 }
-                return Ok(());
             }
-        } else {
-            anyhow::bail!("Job not found");
         }
+    }
 
-        std::thread::sleep(Duration::from_millis(100));
+    if exit_code != 0 {
+        std::process::exit(exit_code);
     }
+    Ok(())
+}
 
-    let mut file = std::fs::File::open(log_path)?;
-    let mut position = 0u64;
-    let mut buf = vec![0u8; 8192];
-    let mut line_buf = String::new();
+/// Prefix colors, cycled through in job order
+const PREFIX_COLORS: [Color; 6] = [
+    Color::Cyan,
+    Color::Yellow,
+    Color::Green,
+    Color::Magenta,
+    Color::Blue,
+    Color::BrightRed,
+];
 
-    loop {
-        if interrupted.load(Ordering::SeqCst) {
-            return Ok(());
+/// `label | `, padded to `width` and colored by position
+fn job_prefix(label: &str, width: usize, index: usize, colorize: bool) -> String {
+    let prefix = format!("{label:<width$} | ");
+    if colorize {
+        prefix
+            .color(PREFIX_COLORS[index % PREFIX_COLORS.len()])
+            .to_string()
+    } else {
+        prefix
+    }
+}
+
+/// Exit with the finished job's exit code, if it has one.
+fn exit_with(job: &Job) -> Result<()> {
+    if let Some(code) = job.exit_code {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// What a followed job did next
+enum FollowEvent {
+    Output(Vec<OutputChunk>),
+    /// The job in its terminal state; its output is complete
+    Finished(Box<Job>),
+}
+
+/// Follows one job's output: pushed by the daemon over a subscription, or read
+/// by polling the log files and the job's row when the daemon can't be reached.
+/// A subscription that drops mid-stream is continued by polling.
+struct JobFollower {
+    paths: Paths,
+    job_id: String,
+    active: Segment,
+    stream: Option<Stream>,
+    /// Read through the chunk index (a stream or timestamps are needed)
+    indexed: bool,
+    subscription: Option<Subscription>,
+    poll: Option<Poll>,
+    /// End of the output delivered so far, in the job's whole output
+    position: u64,
+    /// Skips output already delivered when switching to polling
+    resume: ResumeFilter,
+}
+
+/// State of the polling fallback
+struct Poll {
+    db: Database,
+    tail: Option<OutputTail>,
+    /// Terminal job, reported once its remaining output has been delivered
+    finished: Option<Job>,
+}
+
+/// Poll interval for the fallback
+const POLL_MILLIS: u64 = 100;
+
+impl JobFollower {
+    async fn connect(paths: &Paths, job_id: &str, stream: Option<Stream>, indexed: bool) -> Self {
+        let subscription = match DaemonClient::connect().await {
+            Ok(client) => client.subscribe(job_id, 0).await.ok(),
+            Err(_) => None,
+        };
+        Self {
+            paths: paths.clone(),
+            job_id: job_id.to_string(),
+            active: Segment::active(paths, job_id),
+            stream,
+            indexed,
+            subscription,
+            poll: None,
+            position: 0,
+            resume: ResumeFilter::default(),
         }
+    }
 
-        // Read new content from current position
-        file.seek(SeekFrom::Start(position))?;
-        let bytes_read = file.read(&mut buf)?;
-        if bytes_read > 0 {
-            let chunk = String::from_utf8_lossy(&buf[..bytes_read]);
-            if colorize {
-                // Buffer partial lines for colorization
-                for c in chunk.chars() {
-                    if c == '\n' {
-                        println!("{}", colorize_line(&line_buf));
-                        line_buf.clear();
-                    } else {
-                        line_buf.push(c);
-                    }
+    async fn next(&mut self) -> Result<FollowEvent> {
+        let event = loop {
+            let Some(subscription) = self.subscription.as_mut() else {
+                break self.poll_next().await?;
+            };
+            match subscription.next().await {
+                Ok(SubscriptionEvent::Output(chunks)) => break FollowEvent::Output(chunks),
+                Ok(SubscriptionEvent::Finished(job)) => break FollowEvent::Finished(job),
+                // Daemon went away: poll from where it left off
+                Err(Error::Io(_) | Error::Protocol(_)) => {
+                    self.subscription = None;
+                    self.resume = ResumeFilter::new(self.position);
                 }
-            } else {
-                std::io::stdout().write_all(&buf[..bytes_read])?;
+                Err(e) => return Err(e.into()),
             }
-            std::io::stdout().flush()?;
-            position += bytes_read as u64;
+        };
+
+        let FollowEvent::Output(chunks) = event else {
+            return Ok(event);
+        };
+        let chunks: Vec<OutputChunk> = chunks
+            .into_iter()
+            .filter_map(|c| self.resume.apply(c))
+            .collect();
+        if let Some(last) = chunks.last() {
+            self.position = last.end();
         }
+        Ok(FollowEvent::Output(chunks))
+    }
 
-        // Check job status
-        if let Some(job) = db.get(job_id)? {
-            if job.status.is_terminal() {
-                // Final read to catch any remaining output
-                loop {
-                    file.seek(SeekFrom::Start(position))?;
-                    let bytes_read = file.read(&mut buf)?;
-                    if bytes_read == 0 {
-                        break;
+    async fn poll_next(&mut self) -> Result<FollowEvent> {
+        if self.poll.is_none() {
+            self.poll = Some(Poll {
+                db: Database::open(&self.paths)?,
+                tail: None,
+                finished: None,
+            });
+        }
+        let poll = self.poll.as_mut().expect("just set");
+
+        loop {
+            if let Some(job) = poll.finished.take() {
+                return Ok(FollowEvent::Finished(Box::new(job)));
+            }
+            let job = poll
+                .db
+                .get(&self.job_id)?
+                .ok_or_else(|| anyhow::anyhow!("Job disappeared from database"))?;
+            let terminal = job.status.is_terminal();
+
+            // Wait for the log to exist (job might be pending)
+            if poll.tail.is_none() {
+                if self.active.exists() && (!self.indexed || self.active.index.exists()) {
+                    poll.tail = Some(if self.indexed {
+                        OutputTail::open_indexed(self.active.clone(), self.stream)?
+                    } else {
+                        OutputTail::open(self.active.clone(), self.stream)?
+                    });
+                } else if terminal {
+                    if self.indexed && self.active.exists() {
+                        // Finished job captured before chunk indexes existed
+                        anyhow::bail!(no_index_error(&self.job_id, self.stream));
                     }
-                    let chunk = String::from_utf8_lossy(&buf[..bytes_read]);
-                    if colorize {
-                        for c in chunk.chars() {
-                            if c == '\n' {
-                                println!("{}", colorize_line(&line_buf));
-                                line_buf.clear();
-                            } else {
-                                line_buf.push(c);
-                            }
+                    eprintln!("Job finished with no output");
+                    return Ok(FollowEvent::Finished(Box::new(job)));
+                }
+            }
+
+            if let Some(tail) = poll.tail.as_mut() {
+                let mut chunks = tail.read_output()?;
+                if terminal {
+                    // Final read to catch any remaining output
+                    loop {
+                        let more = tail.read_output()?;
+                        if more.is_empty() {
+                            break;
                         }
-                    } else {
-                        std::io::stdout().write_all(&buf[..bytes_read])?;
+                        chunks.extend(more);
                     }
-                    position += bytes_read as u64;
+                    poll.finished = Some(job);
                 }
-                // Print any remaining partial line
-                if colorize && !line_buf.is_empty() {
-                    println!("{}", colorize_line(&line_buf));
+                if !chunks.is_empty() {
+                    return Ok(FollowEvent::Output(chunks));
                 }
-                std::io::stdout().flush()?;
-
-                // Exit with job's exit code
-                if let Some(code) = job.exit_code {
-                    std::process::exit(code);
+                if terminal {
+                    continue;
                 }
-                return Ok(());
             }
-        } else {
-            anyhow::bail!("Job disappeared from database");
-        }
 
-        // Small sleep before next poll
-        std::thread::sleep(Duration::from_millis(100));
+            tokio::time::sleep(Duration::from_millis(POLL_MILLIS)).await;
+        }
     }
 }
 
-/// Simple Ctrl+C handler without adding ctrlc dependency
-fn ctrlc_handler<F: Fn() + Send + Sync + 'static>(handler: F) {
-    #[cfg(unix)]
-    {
-        use nix::sys::signal::{SigHandler, Signal, signal};
+/// Prints followed output: raw bytes, or whole lines when they carry
+/// timestamps or a job prefix.
+struct FollowPrinter {
+    job_id: String,
+    stream: Option<Stream>,
+    timed: bool,
+    colorize: bool,
+    timestamps: bool,
+    range: TimeRange,
+    line_buf: String,
+    /// Set when printing whole lines
+    splitter: Option<LineSplitter>,
+    /// Printed before each line when following several jobs
+    prefix: String,
+}
+
+impl FollowPrinter {
+    fn new(
+        job_id: &str,
+        stream: Option<Stream>,
+        timed: bool,
+        timestamps: bool,
+        range: TimeRange,
+    ) -> Self {
+        Self {
+            job_id: job_id.to_string(),
+            stream,
+            timed,
+            colorize: should_colorize(),
+            timestamps,
+            range,
+            line_buf: String::new(),
+            splitter: timed.then(LineSplitter::default),
+            prefix: String::new(),
+        }
+    }
+
+    /// Prefix every line (and so print whole lines only).
+    fn with_prefix(mut self, prefix: String) -> Self {
+        self.prefix = prefix;
+        self.splitter.get_or_insert_with(LineSplitter::default);
+        self
+    }
 
-        static HANDLER: std::sync::OnceLock<Box<dyn Fn() + Send + Sync>> =
-            std::sync::OnceLock::new();
+    /// Whether chunks must carry their stream (read through the chunk index)
+    fn needs_index(&self) -> bool {
+        self.stream.is_some() || self.timed
+    }
 
-        extern "C" fn signal_handler(_: i32) {
-            if let Some(h) = HANDLER.get() {
-                h();
+    fn print(&mut self, chunks: Vec<OutputChunk>) -> Result<()> {
+        let mut lines = Vec::new();
+        for chunk in chunks {
+            if chunk.stream.is_none() && self.needs_index() {
+                anyhow::bail!(no_index_error(&self.job_id, self.stream));
+            }
+            if self.stream.is_some_and(|s| chunk.stream != Some(s)) {
+                continue;
             }
+            match self.splitter.as_mut() {
+                Some(splitter) => splitter.push(&chunk.data, chunk.at, &mut lines),
+                None => print_output(&chunk.data, self.colorize, &mut self.line_buf)?,
+            }
+        }
+        if !lines.is_empty() {
+            self.print_lines(&lines)?;
         }
+        Ok(())
+    }
 
-        let _ = HANDLER.set(Box::new(handler));
-        unsafe {
-            let _ = signal(Signal::SIGINT, SigHandler::Handler(signal_handler));
+    /// Flush a trailing partial line once the job has finished.
+    fn finish(&mut self) -> Result<()> {
+        if let Some(line) = self.splitter.as_mut().and_then(LineSplitter::finish) {
+            self.print_lines(&[line])?;
+        } else if self.colorize && !self.line_buf.is_empty() {
+            println!("{}", colorize_line(&self.line_buf));
         }
+        std::io::stdout().flush()?;
+        Ok(())
     }
 
-    #[cfg(not(unix))]
-    {
-        // On non-Unix, just ignore - Ctrl+C will terminate the process
-        let _ = handler;
+    fn print_lines(&self, lines: &[TimedLine]) -> Result<()> {
+        let mut out = std::io::stdout().lock();
+        for line in lines.iter().filter(|l| self.range.contains(l.at)) {
+            writeln!(
+                out,
+                "{}{}",
+                self.prefix,
+                format_timed_line(line, self.timestamps, self.colorize)
+            )?;
+        }
+        out.flush()?;
+        Ok(())
     }
 }
 
+/// Write a chunk of followed output. When colorizing, partial lines are
+/// buffered in `line_buf` until their newline arrives.
+fn print_output(bytes: &[u8], colorize: bool, line_buf: &mut String) -> Result<()> {
+    if colorize {
+        for c in String::from_utf8_lossy(bytes).chars() {
+            if c == '\n' {
+                println!("{}", colorize_line(line_buf));
+                line_buf.clear();
+            } else {
+                line_buf.push(c);
+            }
+        }
+    } else {
+        std::io::stdout().write_all(bytes)?;
+    }
+    std::io::stdout().flush()?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -454,6 +1089,43 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_time_range_bounds() {
+        let t = |ms| DateTime::from_timestamp_millis(ms);
+        let range = TimeRange {
+            since: t(1_000),
+            until: t(2_000),
+        };
+        assert!(!range.contains(t(999)));
+        assert!(range.contains(t(1_000)));
+        assert!(!range.contains(t(2_000)));
+        assert!(!range.contains(None));
+        assert!(TimeRange::default().contains(None));
+    }
+
+    #[test]
+    fn test_format_timed_line() {
+        let line = TimedLine {
+            at: DateTime::from_timestamp_millis(1_760_000_000_123),
+            text: "ready".into(),
+        };
+        assert_eq!(
+            format_timed_line(&line, true, false),
+            "2025-10-09T08:53:20.123Z ready"
+        );
+        assert_eq!(format_timed_line(&line, false, false), "ready");
+    }
+
+    #[test]
+    fn test_job_prefix_padded_to_widest_label() {
+        assert_eq!(job_prefix("api", 7, 0, false), "api     | ");
+        assert_eq!(job_prefix("svc-web", 7, 1, false), "svc-web | ");
+        with_colors(|| {
+            assert!(job_prefix("api", 3, 0, true).contains("\x1b[36m")); // cyan
+            assert!(job_prefix("web", 3, 1, true).contains("\x1b[33m")); // yellow
+        });
+    }
+
     #[test]
     fn test_colorize_line_case_insensitive() {
         with_colors(|| {
@@ -0,0 +1,634 @@
+use crate::client::DaemonClient;
+use crate::commands::logs::read_after_cursor;
+use crate::commands::run::{RunOptions, run_spec};
+use crate::commands::{stop, wait};
+use crate::core::output::{Cursor, Segment, Stream};
+use crate::core::{Database, Job, Paths, Status, UserError, parse_duration};
+use anyhow::Result;
+use schemars::{JsonSchema, schema_for};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::cell::Cell;
+use std::rc::Rc;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::sync::mpsc;
+
+/// The MCP revision this server speaks; older ones a client asks for are accepted
+const PROTOCOL_VERSION: &str = "2025-06-18";
+const SUPPORTED_VERSIONS: &[&str] = &[PROTOCOL_VERSION, "2025-03-26", "2024-11-05"];
+/// First version with tool `outputSchema` and `structuredContent`
+const STRUCTURED_OUTPUT_VERSION: &str = "2025-06-18";
+
+/// `read_logs` page size when the caller doesn't give one
+const DEFAULT_MAX_BYTES: usize = 64 * 1024;
+/// `list_jobs` length when the caller doesn't give one
+const DEFAULT_LIST_LIMIT: usize = 20;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const INSTRUCTIONS: &str = "jb runs shell commands as background jobs that outlive this \
+session. Start one with run_job, then poll job_status or block on wait_job. Read output \
+in pages with read_logs, passing the returned cursor to the next call.";
+
+/// Serve MCP over stdin/stdout until stdin closes. Messages are JSON-RPC, one
+/// per line. Calls are handled concurrently, so a long `wait_job` doesn't hold
+/// up the others.
+pub async fn execute() -> Result<()> {
+    let server = Rc::new(Server::new(Paths::new()?));
+    let (tx, mut rx) = mpsc::unbounded_channel::<Value>();
+    let writer = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = rx.recv().await {
+            let mut line = message.to_string();
+            line.push('\n');
+            stdout.write_all(line.as_bytes()).await?;
+            stdout.flush().await?;
+        }
+        anyhow::Ok(())
+    });
+
+    // The database handle isn't shareable across threads, so calls run as
+    // local tasks
+    let local = tokio::task::LocalSet::new();
+    local
+        .run_until(async move {
+            let mut lines = BufReader::new(tokio::io::stdin()).lines();
+            while let Some(line) = lines.next_line().await? {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let server = Rc::clone(&server);
+                let tx = tx.clone();
+                tokio::task::spawn_local(async move {
+                    if let Some(response) = server.handle_line(&line).await {
+                        let _ = tx.send(response);
+                    }
+                });
+            }
+            anyhow::Ok(())
+        })
+        .await?;
+
+    // The client has gone; calls still in flight have no one to answer
+    writer.abort();
+    Ok(())
+}
+
+/// Answers MCP requests about the jobs under `paths`
+pub struct Server {
+    paths: Paths,
+    /// Agreed in `initialize`; the latest until then
+    version: Cell<&'static str>,
+}
+
+/// A JSON-RPC error, as opposed to a tool that ran and failed
+#[derive(Debug)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl Server {
+    #[must_use]
+    pub fn new(paths: Paths) -> Self {
+        Self {
+            paths,
+            version: Cell::new(PROTOCOL_VERSION),
+        }
+    }
+
+    /// Whether the client's protocol version knows structured tool output.
+    /// Versions are dates, so they compare as strings.
+    fn structured_output(&self) -> bool {
+        self.version.get() >= STRUCTURED_OUTPUT_VERSION
+    }
+
+    /// The response to one line from the client, if it needs one.
+    pub async fn handle_line(&self, line: &str) -> Option<Value> {
+        match serde_json::from_str::<Value>(line) {
+            Ok(message) => self.handle_message(message).await,
+            Err(e) => Some(error_response(
+                Value::Null,
+                &RpcError::new(PARSE_ERROR, format!("Parse error: {e}")),
+            )),
+        }
+    }
+
+    async fn handle_message(&self, message: Value) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // Not a request; replies to requests we never sent are ignored
+            return match id {
+                None if message.is_object() => None,
+                id => Some(error_response(
+                    id.unwrap_or(Value::Null),
+                    &RpcError::new(INVALID_REQUEST, "Invalid request"),
+                )),
+            };
+        };
+        // Notifications (`notifications/initialized`, cancellations) need no reply
+        let id = id?;
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": tools(self.structured_output()) })),
+            "tools/call" => self.call_tool(params).await,
+            _ => Err(RpcError::new(
+                METHOD_NOT_FOUND,
+                format!("Unknown method: {method}"),
+            )),
+        };
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(e) => error_response(id, &e),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = SUPPORTED_VERSIONS
+            .iter()
+            .find(|v| requested == Some(**v))
+            .copied()
+            .unwrap_or(PROTOCOL_VERSION);
+        self.version.set(version);
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": {} },
+            "serverInfo": { "name": "jb", "version": env!("CARGO_PKG_VERSION") },
+            "instructions": INSTRUCTIONS,
+        })
+    }
+
+    /// Run a tool. Bad arguments are a protocol error; a tool that fails
+    /// reports it in the result, so the model sees why.
+    async fn call_tool(&self, params: Value) -> Result<Value, RpcError> {
+        #[derive(Deserialize)]
+        struct Call {
+            name: String,
+            #[serde(default)]
+            arguments: Value,
+        }
+        let call: Call = parse_args(params)?;
+        let args = call.arguments;
+
+        let result = match call.name.as_str() {
+            "run_job" => to_value(self.run_job(parse_args(args)?).await),
+            "list_jobs" => to_value(self.list_jobs(parse_args(args)?)),
+            "job_status" => to_value(self.job_status(parse_args(args)?)),
+            "read_logs" => to_value(self.read_logs(parse_args(args)?)),
+            "stop_job" => to_value(self.stop_job(parse_args(args)?).await),
+            "wait_job" => to_value(self.wait_job(parse_args(args)?).await),
+            name => {
+                return Err(RpcError::new(
+                    INVALID_PARAMS,
+                    format!("Unknown tool: {name}"),
+                ));
+            }
+        };
+
+        Ok(match result {
+            Ok(value) if self.structured_output() => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "structuredContent": value,
+                "isError": false,
+            }),
+            Ok(value) => json!({
+                "content": [{ "type": "text", "text": value.to_string() }],
+                "isError": false,
+            }),
+            Err(e) => json!({
+                "content": [{ "type": "text", "text": format!("{e:#}") }],
+                "isError": true,
+            }),
+        })
+    }
+
+    async fn run_job(&self, args: RunJob) -> Result<Job> {
+        let spec = run_spec(&RunOptions {
+            command: args.command,
+            name: args.name,
+            timeout: args.timeout,
+            dir: args.cwd,
+            key: args.idempotency_key,
+            ..RunOptions::default()
+        })?;
+        let mut client = DaemonClient::connect_or_start().await?;
+        Ok(client.run(&spec).await?)
+    }
+
+    fn list_jobs(&self, args: ListJobs) -> Result<JobList> {
+        let db = Database::open(&self.paths)?;
+        db.recover_orphans();
+        let limit = args.limit.unwrap_or(DEFAULT_LIST_LIMIT);
+        let jobs = db.list(args.status, Some(limit))?;
+        Ok(JobList { jobs })
+    }
+
+    fn job_status(&self, args: JobRef) -> Result<Job> {
+        let db = Database::open(&self.paths)?;
+        db.recover_orphans();
+        db.resolve(&args.id)
+    }
+
+    fn read_logs(&self, args: ReadLogs) -> Result<LogPage> {
+        let cursor: Cursor = match &args.cursor {
+            Some(token) => token
+                .parse()
+                .map_err(|e| UserError::new(format!("Invalid cursor '{token}': {e}")))?,
+            None => Cursor::default(),
+        };
+        let db = Database::open(&self.paths)?;
+        db.recover_orphans();
+        // Status first: if the job had already finished, the output read is final
+        let job = db.resolve(&args.id)?;
+        let active = Segment::active(&self.paths, &job.id);
+        let max_bytes = args.max_bytes.unwrap_or(DEFAULT_MAX_BYTES);
+        let read = read_after_cursor(&job, &active, cursor, args.stream, Some(max_bytes))?;
+
+        Ok(LogPage {
+            complete: job.status.is_terminal() && !read.truncated,
+            id: job.id,
+            status: job.status,
+            data: String::from_utf8_lossy(&read.data).into_owned(),
+            bytes: read.data.len(),
+            cursor: read.cursor.to_string(),
+            truncated: read.truncated,
+            skipped: read.skipped,
+            reset: read.reset,
+        })
+    }
+
+    async fn stop_job(&self, args: StopJob) -> Result<Job> {
+        let db = Database::open(&self.paths)?;
+        let job = db.resolve(&args.id)?;
+        if job.status.is_terminal() {
+            return Ok(job);
+        }
+        stop::stop_job(&db, &job, args.force.unwrap_or(false)).await
+    }
+
+    async fn wait_job(&self, args: WaitJob) -> Result<WaitOutcome> {
+        let timeout_secs = args.timeout.as_deref().map(parse_duration).transpose()?;
+        let db = Database::open(&self.paths)?;
+        db.recover_orphans();
+        let job = db.resolve(&args.id)?;
+        if job.status.is_terminal() {
+            return Ok(WaitOutcome {
+                job,
+                timed_out: false,
+            });
+        }
+
+        match wait::wait_for(&db, &job, timeout_secs).await? {
+            Some(job) => Ok(WaitOutcome {
+                job,
+                timed_out: false,
+            }),
+            None => Ok(WaitOutcome {
+                job: db.resolve(&job.id)?,
+                timed_out: true,
+            }),
+        }
+    }
+}
+
+/// Arguments of `run_job`
+#[derive(Debug, Deserialize, JsonSchema)]
+struct RunJob {
+    /// Shell command to run
+    command: String,
+    /// Name to refer to the job by instead of its ID
+    name: Option<String>,
+    /// Working directory; defaults to the server's
+    cwd: Option<String>,
+    /// Stop the job after this long, e.g. "30s", "5m", "1h"
+    timeout: Option<String>,
+    /// Return the existing job instead of starting another when this key was used before
+    idempotency_key: Option<String>,
+}
+
+/// Arguments of `list_jobs`
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ListJobs {
+    /// Only jobs with this status
+    status: Option<Status>,
+    /// Most jobs to return, newest first (default 20)
+    limit: Option<usize>,
+}
+
+/// A job, by ID, ID prefix or name
+#[derive(Debug, Deserialize, JsonSchema)]
+struct JobRef {
+    /// Job ID, unique ID prefix, or name
+    id: String,
+}
+
+/// Arguments of `read_logs`
+#[derive(Debug, Deserialize, JsonSchema)]
+struct ReadLogs {
+    /// Job ID, unique ID prefix, or name
+    id: String,
+    /// Where to continue from: the cursor returned by the previous read; from the start if omitted
+    cursor: Option<String>,
+    /// Most bytes to return (default 65536)
+    max_bytes: Option<usize>,
+    /// Only this stream
+    stream: Option<Stream>,
+}
+
+/// Arguments of `stop_job`
+#[derive(Debug, Deserialize, JsonSchema)]
+struct StopJob {
+    /// Job ID, unique ID prefix, or name
+    id: String,
+    /// Kill immediately (SIGKILL) instead of asking the job to exit (SIGTERM)
+    force: Option<bool>,
+}
+
+/// Arguments of `wait_job`
+#[derive(Debug, Deserialize, JsonSchema)]
+struct WaitJob {
+    /// Job ID, unique ID prefix, or name
+    id: String,
+    /// Give up after this long, e.g. "30s", "5m"; waits until the job finishes if omitted
+    timeout: Option<String>,
+}
+
+/// Result of `list_jobs`
+#[derive(Debug, Serialize, JsonSchema)]
+struct JobList {
+    jobs: Vec<Job>,
+}
+
+/// Result of `read_logs`
+#[derive(Debug, Serialize, JsonSchema)]
+struct LogPage {
+    id: String,
+    /// Job status as of the read
+    status: Status,
+    /// Output after the cursor; invalid UTF-8 replaced
+    data: String,
+    bytes: usize,
+    /// Pass to the next read to continue after this output
+    cursor: String,
+    /// The read stopped at `max_bytes`; more output follows the cursor
+    truncated: bool,
+    /// Bytes after the old cursor that were rotated away before this read
+    skipped: u64,
+    /// The cursor no longer fit the log, so the read started over
+    reset: bool,
+    /// The job has finished and all of its output has been read
+    complete: bool,
+}
+
+/// Result of `wait_job`
+#[derive(Debug, Serialize, JsonSchema)]
+struct WaitOutcome {
+    job: Job,
+    /// The timeout passed with the job still running
+    timed_out: bool,
+}
+
+/// Every tool, with schemas derived from its argument and result types. The
+/// result schema is left out for clients that predate structured output.
+fn tools(structured: bool) -> Vec<Value> {
+    let mut tools = vec![
+        tool::<RunJob, Job>(
+            "run_job",
+            "Start a shell command as a background job. Returns the job without waiting for it.",
+        ),
+        tool::<ListJobs, JobList>("list_jobs", "List recent jobs, newest first."),
+        tool::<JobRef, Job>("job_status", "Get a job's current status and exit code."),
+        tool::<ReadLogs, LogPage>(
+            "read_logs",
+            "Read a job's output in pages. Pass the returned cursor to the next call to \
+             continue where this one stopped.",
+        ),
+        tool::<StopJob, Job>("stop_job", "Stop a running job."),
+        tool::<WaitJob, WaitOutcome>(
+            "wait_job",
+            "Wait for a job to finish, or for the timeout to pass.",
+        ),
+    ];
+    if !structured {
+        for tool in &mut tools {
+            tool.as_object_mut().unwrap().remove("outputSchema");
+        }
+    }
+    tools
+}
+
+fn tool<I: JsonSchema, O: JsonSchema>(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "description": description,
+        "inputSchema": schema_for!(I),
+        "outputSchema": schema_for!(O),
+    })
+}
+
+fn parse_args<T: DeserializeOwned>(args: Value) -> Result<T, RpcError> {
+    // Omitted arguments are the same as none
+    let args = if args.is_null() { json!({}) } else { args };
+    serde_json::from_value(args)
+        .map_err(|e| RpcError::new(INVALID_PARAMS, format!("Invalid arguments: {e}")))
+}
+
+fn to_value<T: Serialize>(result: Result<T>) -> Result<Value> {
+    Ok(serde_json::to_value(result?)?)
+}
+
+fn error_response(id: Value, error: &RpcError) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": error.code, "message": error.message },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::capture::OutputWriter;
+    use std::path::PathBuf;
+    use tempfile::TempDir;
+
+    fn setup() -> (Server, TempDir) {
+        let tmp = TempDir::new().unwrap();
+        let paths = Paths::with_root(tmp.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        (Server::new(paths), tmp)
+    }
+
+    async fn request(server: &Server, method: &str, params: Value) -> Value {
+        let message = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+        server.handle_line(&message.to_string()).await.unwrap()
+    }
+
+    async fn call(server: &Server, tool: &str, arguments: Value) -> Value {
+        let response = request(
+            server,
+            "tools/call",
+            json!({ "name": tool, "arguments": arguments }),
+        )
+        .await;
+        response["result"].clone()
+    }
+
+    fn insert_job(server: &Server, id: &str, status: Status) {
+        let mut job = Job::new(
+            id.into(),
+            "make test".into(),
+            PathBuf::from("/tmp"),
+            PathBuf::from("/tmp"),
+        );
+        job.status = status;
+        Database::open(&server.paths).unwrap().insert(&job).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_initialize_and_notifications() {
+        let (server, _tmp) = setup();
+        let response = request(
+            &server,
+            "initialize",
+            json!({ "protocolVersion": "2024-11-05", "capabilities": {} }),
+        )
+        .await;
+        assert_eq!(response["result"]["protocolVersion"], "2024-11-05");
+        assert_eq!(response["result"]["serverInfo"]["name"], "jb");
+
+        let initialized = json!({ "jsonrpc": "2.0", "method": "notifications/initialized" });
+        assert!(server.handle_line(&initialized.to_string()).await.is_none());
+
+        let unknown = request(&server, "resources/list", Value::Null).await;
+        assert_eq!(unknown["error"]["code"], METHOD_NOT_FOUND);
+        let garbage = server.handle_line("{not json").await.unwrap();
+        assert_eq!(garbage["error"]["code"], PARSE_ERROR);
+    }
+
+    #[tokio::test]
+    async fn test_tool_schemas_derived_from_types() {
+        let (server, _tmp) = setup();
+        let response = request(&server, "tools/list", Value::Null).await;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        let names: Vec<&str> = tools.iter().filter_map(|t| t["name"].as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "run_job",
+                "list_jobs",
+                "job_status",
+                "read_logs",
+                "stop_job",
+                "wait_job"
+            ]
+        );
+        for tool in tools {
+            assert_eq!(tool["inputSchema"]["type"], "object");
+            assert_eq!(tool["outputSchema"]["type"], "object");
+        }
+        let run = &tools[0]["inputSchema"];
+        assert_eq!(run["required"], json!(["command"]));
+        let job = &tools[2]["outputSchema"];
+        assert!(job["properties"]["exit_code"].is_object());
+    }
+
+    #[tokio::test]
+    async fn test_older_clients_get_no_structured_output() {
+        let (server, _tmp) = setup();
+        insert_job(&server, "abc1", Status::Completed);
+        let init = json!({ "protocolVersion": "2025-03-26", "capabilities": {} });
+        request(&server, "initialize", init).await;
+
+        let response = request(&server, "tools/list", Value::Null).await;
+        let tools = response["result"]["tools"].as_array().unwrap();
+        assert!(tools.iter().all(|t| t.get("outputSchema").is_none()));
+        assert_eq!(tools[0]["inputSchema"]["type"], "object");
+
+        let result = call(&server, "job_status", json!({ "id": "abc1" })).await;
+        assert!(result.get("structuredContent").is_none());
+        let text = result["content"][0]["text"].as_str().unwrap();
+        let job: Value = serde_json::from_str(text).unwrap();
+        assert_eq!(job["status"], "completed");
+    }
+
+    #[tokio::test]
+    async fn test_status_and_list_read_database() {
+        let (server, _tmp) = setup();
+        insert_job(&server, "abc1", Status::Completed);
+        insert_job(&server, "def2", Status::Failed);
+
+        let result = call(&server, "job_status", json!({ "id": "abc" })).await;
+        assert_eq!(result["isError"], false);
+        assert_eq!(result["structuredContent"]["status"], "completed");
+
+        let result = call(&server, "list_jobs", json!({ "status": "failed" })).await;
+        let jobs = result["structuredContent"]["jobs"].as_array().unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0]["id"], "def2");
+
+        let result = call(&server, "job_status", json!({ "id": "zzz" })).await;
+        assert_eq!(result["isError"], true);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_arguments_are_protocol_errors() {
+        let (server, _tmp) = setup();
+        let response = request(
+            &server,
+            "tools/call",
+            json!({ "name": "job_status", "arguments": {} }),
+        )
+        .await;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+        let response = request(&server, "tools/call", json!({ "name": "nope" })).await;
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[tokio::test]
+    async fn test_read_logs_pages_with_cursor() {
+        let (server, _tmp) = setup();
+        insert_job(&server, "abc1", Status::Completed);
+        let segment = Segment::active(&server.paths, "abc1");
+        let mut writer = OutputWriter::create(segment, None).unwrap();
+        writer
+            .write_chunk(Stream::Stdout, b"hello world\n")
+            .unwrap();
+        drop(writer);
+
+        let first = call(
+            &server,
+            "read_logs",
+            json!({ "id": "abc1", "max_bytes": 6 }),
+        )
+        .await;
+        let page = &first["structuredContent"];
+        assert_eq!(page["data"], "hello ");
+        assert_eq!(page["truncated"], true);
+        assert_eq!(page["complete"], false);
+
+        let next = json!({ "id": "abc1", "cursor": page["cursor"] });
+        let second = call(&server, "read_logs", next).await;
+        let page = &second["structuredContent"];
+        assert_eq!(page["data"], "world\n");
+        assert_eq!(page["complete"], true);
+    }
+}
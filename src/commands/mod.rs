@@ -1,8 +1,12 @@
 pub mod clean;
 pub mod completions;
 pub mod daemon;
+pub mod events;
+pub mod grep;
 pub mod list;
 pub mod logs;
+pub mod mcp;
+pub mod replay;
 pub mod retry;
 pub mod run;
 pub mod status;
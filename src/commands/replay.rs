@@ -0,0 +1,39 @@
+use crate::commands::logs::open_cast;
+use crate::core::output::{Segment, Stream};
+use crate::core::{Database, Paths, UserError};
+use anyhow::Result;
+use std::io::Write;
+use std::time::Duration;
+
+/// Pauses longer than this (before speeding up) are cut short, so a job that
+/// sat idle for an hour replays in seconds
+const IDLE_LIMIT_SECS: f64 = 2.0;
+
+/// Play a job's output back in the terminal with its original timing.
+pub async fn execute(id: &str, speed: f64, stream: Option<Stream>) -> Result<()> {
+    if !(speed.is_finite() && speed > 0.0) {
+        anyhow::bail!(UserError::new(format!(
+            "Invalid speed '{speed}': must be greater than 0"
+        )));
+    }
+
+    let paths = Paths::new()?;
+    let db = Database::open(&paths)?;
+    let job = db.resolve(id)?;
+    let active = Segment::active(&paths, &job.id);
+    let (_, events) = open_cast(&active, &job, stream)?;
+
+    let mut out = std::io::stdout();
+    let mut last = 0.0;
+    for event in events {
+        let event = event?;
+        let pause = (event.time - last).min(IDLE_LIMIT_SECS) / speed;
+        last = event.time;
+        if pause > 0.0 {
+            tokio::time::sleep(Duration::from_secs_f64(pause)).await;
+        }
+        out.write_all(event.data.as_bytes())?;
+        out.flush()?;
+    }
+    Ok(())
+}
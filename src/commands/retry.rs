@@ -1,7 +1,7 @@
-use crate::client::DaemonClient;
-use crate::core::ipc::{Request, Response};
+use crate::client::{DaemonClient, RunSpec};
 use crate::core::{Database, Paths};
 use anyhow::Result;
+use std::time::Duration;
 
 pub async fn execute(id: String, json: bool) -> Result<()> {
     let paths = Paths::new()?;
@@ -11,26 +11,23 @@ pub async fn execute(id: String, json: bool) -> Result<()> {
     // Send to daemon
     let mut client = DaemonClient::connect_or_start().await?;
 
-    let request = Request::Run {
-        command: job.command.clone(),
-        name: job.name.clone(),
-        cwd: job.cwd.to_string_lossy().to_string(),
-        project: job.project.to_string_lossy().to_string(),
-        timeout_secs: job.timeout_secs,
-        idempotency_key: None, // Don't reuse idempotency key
-    };
+    // The idempotency key isn't reused: it would return the old job
+    let mut spec = RunSpec::new(job.command.clone());
+    spec.name = job.name.clone();
+    spec.cwd = Some(job.cwd.clone());
+    spec.project = Some(job.project.clone());
+    spec.timeout = job.timeout_secs.map(Duration::from_secs);
+    spec.log_max_size = job.log_max_size;
+    spec.log_keep = job.log_keep;
+    spec.hooks = job.hooks.clone();
+    spec.webhooks = job.webhooks.clone();
+    spec.notify = job.notify;
+    let new_job = client.run(&spec).await?;
 
-    match client.send(request).await? {
-        Response::Job(new_job) => {
-            if json {
-                println!("{}", serde_json::to_string(&new_job)?);
-            } else {
-                println!("{}", new_job.short_id());
-            }
-            Ok(())
-        }
-        Response::UserError(e) => anyhow::bail!(crate::core::UserError::new(e)),
-        Response::Error(e) => anyhow::bail!("{e}"),
-        _ => anyhow::bail!("Unexpected response from daemon"),
+    if json {
+        println!("{}", serde_json::to_string(&new_job)?);
+    } else {
+        println!("{}", new_job.short_id());
     }
+    Ok(())
 }
@@ -1,100 +1,244 @@
-use crate::client::DaemonClient;
-use crate::core::ipc::{Request, Response};
-use crate::core::{Paths, detect_project, parse_duration};
+use crate::client::{DaemonClient, RunSpec};
+use crate::core::hooks::Hooks;
+use crate::core::webhook::Webhook;
+use crate::core::{Config, Paths, UserError, parse_duration, parse_size};
+use crate::daemon::redact::Redactor;
 use anyhow::{Context, Result};
+use serde::Deserialize;
 use std::env;
+use std::io::Read;
 use std::path::PathBuf;
+use std::time::Duration;
 
-#[allow(clippy::fn_params_excessive_bools, clippy::too_many_arguments)]
-pub async fn execute(
+/// What `jb run` should start and how to report it
+#[derive(Debug, Default)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct RunOptions {
+    pub command: String,
+    pub name: Option<String>,
+    /// Timeout duration (e.g. "5m")
+    pub timeout: Option<String>,
+    pub dir: Option<String>,
+    /// Idempotency key
+    pub key: Option<String>,
+    pub wait: bool,
+    pub follow: bool,
+    /// Log size cap (e.g. "100M"); falls back to `logs.max_size` in config
+    pub log_max_size: Option<String>,
+    /// Rotated segments to keep; falls back to `logs.keep` in config
+    pub log_keep: Option<u32>,
+    pub hooks: Hooks,
+    /// URLs to post the job to when it finishes
+    pub webhooks: Vec<String>,
+    /// `--notify` / `--no-notify`; unset follows `[notify] default` in config
+    pub notify: Option<bool>,
+}
+
+pub async fn execute(opts: RunOptions, json: bool) -> Result<()> {
+    let spec = run_spec(&opts)?;
+    let RunOptions { wait, follow, .. } = opts;
+
+    // Connect to daemon (auto-starts if not running)
+    let mut client = DaemonClient::connect_or_start().await?;
+    let job = client.run(&spec).await?;
+
+    if json && !follow {
+        println!("{}", serde_json::to_string(&job)?);
+    } else if !follow {
+        println!("{}", job.short_id());
+    }
+
+    if follow {
+        // Follow implies waiting, so use logs --follow
+        let opts = crate::commands::logs::LogOptions {
+            follow: true,
+            ..Default::default()
+        };
+        crate::commands::logs::execute(&job.id, &opts).await?;
+    } else if wait {
+        let job = client.wait(&job.id, None).await.context("Wait failed")?;
+        if json {
+            println!("{}", serde_json::to_string(&job)?);
+        } else {
+            eprintln!("Job {} finished: {}", job.short_id(), job.status);
+            if let Some(code) = job.exit_code {
+                std::process::exit(code);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// One job of a `--batch` file: the options of `jb run` by their long
+/// names, with `webhooks` a list of URLs
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchEntry {
     command: String,
     name: Option<String>,
     timeout: Option<String>,
     dir: Option<String>,
     key: Option<String>,
-    wait: bool,
-    follow: bool,
-    json: bool,
-) -> Result<()> {
+    log_max_size: Option<String>,
+    log_keep: Option<u32>,
+    on_success: Option<String>,
+    on_failure: Option<String>,
+    on_exit: Option<String>,
+    #[serde(default)]
+    webhooks: Vec<String>,
+    notify: Option<bool>,
+}
+
+impl From<BatchEntry> for RunOptions {
+    fn from(entry: BatchEntry) -> Self {
+        Self {
+            command: entry.command,
+            name: entry.name,
+            timeout: entry.timeout,
+            dir: entry.dir,
+            key: entry.key,
+            log_max_size: entry.log_max_size,
+            log_keep: entry.log_keep,
+            hooks: Hooks {
+                on_success: entry.on_success,
+                on_failure: entry.on_failure,
+                on_exit: entry.on_exit,
+            },
+            webhooks: entry.webhooks,
+            notify: entry.notify,
+            ..Self::default()
+        }
+    }
+}
+
+/// `jb run --batch`: start every job in `source` (a file, or `-` for stdin)
+/// with one request, and print their IDs in order
+pub async fn execute_batch(source: &str, json: bool) -> Result<()> {
+    let text = if source == "-" {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        std::fs::read_to_string(source).with_context(|| format!("cannot read {source}"))?
+    };
+    let entries = parse_batch(&text)?;
+    if entries.is_empty() {
+        anyhow::bail!(UserError::new("No jobs in batch"));
+    }
+
+    let config = checked_config()?;
+    let specs = entries
+        .into_iter()
+        .enumerate()
+        .map(|(n, entry)| {
+            spec_with_config(&entry.into(), &config)
+                .map_err(|e| UserError::new(format!("Batch entry {}: {e:#}", n + 1)).into())
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut client = DaemonClient::connect_or_start().await?;
+    let jobs = client.run_many(&specs).await?;
+    if json {
+        println!("{}", serde_json::to_string(&jobs)?);
+    } else {
+        for job in &jobs {
+            println!("{}", job.short_id());
+        }
+    }
+    Ok(())
+}
+
+/// Jobs from a JSON array, or from JSON lines (blank lines skipped)
+fn parse_batch(text: &str) -> Result<Vec<BatchEntry>, UserError> {
+    if text.trim_start().starts_with('[') {
+        return serde_json::from_str(text)
+            .map_err(|e| UserError::new(format!("Invalid batch: {e}")));
+    }
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(n, line)| {
+            serde_json::from_str(line)
+                .map_err(|e| UserError::new(format!("Invalid batch line {}: {e}", n + 1)))
+        })
+        .collect()
+}
+
+/// The job `opts` describes: the directory resolved, durations and sizes
+/// parsed, and log limits defaulted from config.
+pub(crate) fn run_spec(opts: &RunOptions) -> Result<RunSpec> {
+    spec_with_config(opts, &checked_config()?)
+}
+
+/// Config, with the parts the daemon would only warn about checked
+fn checked_config() -> Result<Config> {
     let paths = Paths::new()?;
     paths.ensure_dirs()?;
+    let config = Config::load(&paths)?;
+    // The daemon only logs a bad redaction config; catch it here instead
+    Redactor::from_config(&config.redact).context("invalid config")?;
+    config
+        .notify
+        .min_duration_secs()
+        .context("invalid config")?;
+    for webhook in &config.webhooks {
+        webhook.validate().context("invalid config")?;
+    }
+    Ok(config)
+}
 
-    let cwd = match dir {
-        Some(d) => PathBuf::from(&d)
+fn spec_with_config(opts: &RunOptions, config: &Config) -> Result<RunSpec> {
+    let webhooks: Vec<Webhook> = opts.webhooks.iter().map(Webhook::new).collect();
+    for webhook in &webhooks {
+        webhook.validate()?;
+    }
+
+    let cwd = match &opts.dir {
+        Some(d) => PathBuf::from(d)
             .canonicalize()
             .with_context(|| format!("directory not found: {d}"))?,
         None => env::current_dir()?,
     };
-    let project = detect_project(&cwd);
 
-    let timeout_secs = timeout.as_ref().map(|t| parse_duration(t)).transpose()?;
-
-    // Connect to daemon (auto-starts if not running)
-    let mut client = DaemonClient::connect_or_start().await?;
-
-    let request = Request::Run {
-        command,
-        name,
-        cwd: cwd.to_string_lossy().to_string(),
-        project: project.to_string_lossy().to_string(),
-        timeout_secs,
-        idempotency_key: key,
+    let timeout = opts.timeout.as_deref().map(parse_duration).transpose()?;
+    let log_max_size = match &opts.log_max_size {
+        Some(size) => Some(parse_size(size)?),
+        None => config.logs.max_size_bytes()?,
     };
 
-    match client.send(request).await? {
-        Response::Job(job) => {
-            let job_id = job.id.clone();
-
-            if json && !follow {
-                println!("{}", serde_json::to_string(&job)?);
-            } else if !follow {
-                println!("{}", job.short_id());
-            }
+    let mut spec = RunSpec::new(opts.command.clone());
+    spec.name = opts.name.clone();
+    spec.cwd = Some(cwd);
+    spec.timeout = timeout.map(Duration::from_secs);
+    spec.idempotency_key = opts.key.clone();
+    spec.log_max_size = log_max_size;
+    spec.log_keep = opts.log_keep.or(config.logs.keep);
+    spec.hooks = opts.hooks.clone();
+    spec.webhooks = webhooks;
+    spec.notify = opts.notify;
+    Ok(spec)
+}
 
-            if follow {
-                // Follow implies waiting, so use logs --follow
-                crate::commands::logs::execute(&job_id, None, true, false)?;
-            } else if wait {
-                wait_for_job(&mut client, &job_id, json).await?;
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            Ok(())
-        }
-        Response::UserError(e) => {
-            anyhow::bail!(crate::core::UserError::new(e));
-        }
-        Response::Error(e) => {
-            anyhow::bail!("{e}");
-        }
-        _ => {
-            anyhow::bail!("Unexpected response from daemon");
-        }
-    }
-}
+    #[test]
+    fn test_parse_batch_array_and_lines() {
+        let jobs =
+            parse_batch(r#"[{"command": "make", "name": "build"}, {"command": "make test"}]"#)
+                .unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].name.as_deref(), Some("build"));
 
-async fn wait_for_job(client: &mut DaemonClient, job_id: &str, json: bool) -> Result<()> {
-    let request = Request::Wait {
-        id: job_id.to_string(),
-        timeout_secs: None,
-    };
+        let jobs =
+            parse_batch("{\"command\": \"a\", \"timeout\": \"5m\"}\n\n{\"command\": \"b\"}\n")
+                .unwrap();
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].timeout.as_deref(), Some("5m"));
 
-    match client.send(request).await? {
-        Response::Job(job) => {
-            if json {
-                println!("{}", serde_json::to_string(&job)?);
-            } else {
-                eprintln!("Job {} finished: {}", job.short_id(), job.status);
-                if let Some(code) = job.exit_code {
-                    std::process::exit(code);
-                }
-            }
-            Ok(())
-        }
-        Response::Error(e) => {
-            anyhow::bail!("Wait failed: {e}");
-        }
-        _ => {
-            anyhow::bail!("Unexpected response");
-        }
+        let err = parse_batch("{\"command\": \"a\"}\n{\"cmd\": \"b\"}").unwrap_err();
+        assert!(err.message.starts_with("Invalid batch line 2:"), "{err}");
     }
 }
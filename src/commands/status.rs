@@ -1,7 +1,12 @@
-use crate::core::{Database, Paths, Status};
+use crate::core::event::HistoryEntry;
+use crate::core::hooks::HookRun;
+use crate::core::output::{self, IndexedOutput, LogSize, Segment, StreamBytes};
+use crate::core::webhook::Delivery;
+use crate::core::{Database, Paths, Status, format_size};
 use anyhow::Result;
+use chrono::SecondsFormat;
 
-pub fn execute(id: Option<String>, json: bool) -> Result<()> {
+pub fn execute(id: Option<String>, events: bool, json: bool) -> Result<()> {
     let paths = Paths::new()?;
     let db = Database::open(&paths)?;
 
@@ -9,16 +14,54 @@ pub fn execute(id: Option<String>, json: bool) -> Result<()> {
     db.recover_orphans();
 
     match id {
-        Some(id) => show_job_status(&db, &paths, &id, json),
+        Some(id) => show_job_status(&db, &paths, &id, events, json),
         None => show_system_status(&db, &paths, json),
     }
 }
 
-fn show_job_status(db: &Database, paths: &Paths, id: &str, json: bool) -> Result<()> {
+fn show_job_status(db: &Database, paths: &Paths, id: &str, events: bool, json: bool) -> Result<()> {
     let job = db.resolve(id)?;
+    let hook_runs = db.hook_runs(&job.id)?;
+    let deliveries = db.deliveries(&job.id)?;
+    let history = if events {
+        Some(db.history(&job.id)?)
+    } else {
+        None
+    };
+
+    let active = Segment::active(paths, &job.id);
+    let lines = if active.exists() {
+        use std::io::BufRead;
+        Some(
+            std::io::BufReader::new(output::open_log(&active)?)
+                .lines()
+                .count(),
+        )
+    } else {
+        None
+    };
+    let log_size = LogSize::measure(&active)?;
+    let stream_bytes =
+        IndexedOutput::load(&active)?.map(|output| StreamBytes::from_chunks(output.chunks()));
 
     if json {
-        println!("{}", serde_json::to_string_pretty(&job)?);
+        let mut value = serde_json::to_value(&job)?;
+        if let Some(lines) = lines {
+            value["output"] = serde_json::json!({
+                "lines": lines,
+                "bytes": log_size.bytes,
+                "stored_bytes": log_size.stored_bytes,
+                "compressed": log_size.compressed,
+                "stdout_bytes": stream_bytes.map(|b| b.stdout),
+                "stderr_bytes": stream_bytes.map(|b| b.stderr),
+            });
+        }
+        value["hook_runs"] = serde_json::to_value(&hook_runs)?;
+        value["webhook_deliveries"] = serde_json::to_value(&deliveries)?;
+        if let Some(history) = &history {
+            value["events"] = serde_json::to_value(history)?;
+        }
+        println!("{}", serde_json::to_string_pretty(&value)?);
         return Ok(());
     }
 
@@ -43,18 +86,88 @@ fn show_job_status(db: &Database, paths: &Paths, id: &str, json: bool) -> Result
     if let Some(code) = job.exit_code {
         println!("Exit:     {code}");
     }
-    let log_path = paths.log_file(&job.id);
-    if log_path.exists() {
-        use std::io::BufRead;
-        let lines = std::io::BufReader::new(std::fs::File::open(&log_path)?)
-            .lines()
-            .count();
+    if let Some(lines) = lines {
         println!("Output:   {lines} lines");
+        if log_size.compressed {
+            println!(
+                "Log:      {} compressed ({} original)",
+                format_size(log_size.stored_bytes),
+                format_size(log_size.bytes)
+            );
+        } else {
+            println!("Log:      {}", format_size(log_size.bytes));
+        }
+    }
+    if let Some(bytes) = stream_bytes {
+        println!("Stdout:   {}", format_size(bytes.stdout));
+        println!("Stderr:   {}", format_size(bytes.stderr));
+    }
+    if let Some(limit) = job.log_limit() {
+        println!(
+            "Log cap:  {} (keep {})",
+            format_size(limit.max_bytes),
+            limit.keep
+        );
+    }
+    for run in &hook_runs {
+        println!("{}", format_hook_run(run));
+        // Show why a hook failed; a successful one's output is in --json
+        if !run.succeeded() {
+            for line in run.output.lines() {
+                println!("          {line}");
+            }
+        }
+    }
+    for delivery in &deliveries {
+        println!("{}", format_delivery(delivery));
+    }
+    if let Some(history) = history {
+        println!();
+        if history.is_empty() {
+            println!("No events recorded");
+        }
+        for entry in history {
+            println!("{}", format_history_entry(&entry));
+        }
     }
 
     Ok(())
 }
 
+/// `2026-01-05T12:04:31.120Z  signal       SIGTERM to process group 4242`
+fn format_history_entry(entry: &HistoryEntry) -> String {
+    let at = entry.at.to_rfc3339_opts(SecondsFormat::Millis, true);
+    let line = format!(
+        "{at}  {:<12} {}",
+        entry.kind.as_str(),
+        entry.detail.as_deref().unwrap_or("")
+    );
+    line.trim_end().to_string()
+}
+
+/// `Hook:     on_failure (global) `notify-send failed` exit 1`
+fn format_hook_run(run: &HookRun) -> String {
+    let scope = if run.global { " (global)" } else { "" };
+    let outcome = run.exit_code.map_or_else(
+        || "did not finish".to_string(),
+        |code| format!("exit {code}"),
+    );
+    format!("Hook:     {}{scope} `{}` {outcome}", run.hook, run.command)
+}
+
+/// `Webhook:  https://ci.internal/jb attempt 2: delivered (HTTP 200)`
+fn format_delivery(delivery: &Delivery) -> String {
+    let outcome = match (&delivery.error, delivery.status_code) {
+        (None, Some(code)) => format!("delivered (HTTP {code})"),
+        (None, None) => "delivered".to_string(),
+        (Some(error), _) => error.clone(),
+    };
+    format!(
+        "Webhook:  {} attempt {}: {outcome}",
+        delivery.url, delivery.attempt
+    )
+}
+
 fn show_system_status(db: &Database, paths: &Paths, json: bool) -> Result<()> {
     let all_jobs = db.list(None, None)?;
     let running = all_jobs
@@ -1,6 +1,7 @@
 use crate::client::DaemonClient;
-use crate::core::ipc::{Request, Response};
-use crate::core::{Database, Paths, Status, kill_process_group};
+use crate::core::event::{HistoryKind, signal_detail};
+use crate::core::{Database, Job, Paths, Status, kill_process_group};
+use crate::error::Error;
 use anyhow::Result;
 
 pub async fn execute(id: String, force: bool, json: bool) -> Result<()> {
@@ -17,63 +18,62 @@ pub async fn execute(id: String, force: bool, json: bool) -> Result<()> {
         return Ok(());
     }
 
+    let updated = stop_job(&db, &job, force).await?;
+    if json {
+        println!("{}", serde_json::to_string(&updated)?);
+    } else {
+        println!("Stopped {}", job.short_id());
+    }
+
+    Ok(())
+}
+
+/// Stop a job that isn't terminal, via the daemon when it's running there,
+/// and return its updated record.
+pub(crate) async fn stop_job(db: &Database, job: &Job, force: bool) -> Result<Job> {
+    let mut stopped = false;
+
     // Try to stop via daemon
     if let Ok(mut client) = DaemonClient::connect_or_start().await {
-        let request = Request::Stop {
-            id: job.id.clone(),
-            force,
-        };
-
-        match client.send(request).await? {
-            Response::Ok => {
-                if json {
-                    let updated = db
-                        .get(&job.id)?
-                        .ok_or_else(|| anyhow::anyhow!("job {} disappeared", job.short_id()))?;
-                    println!("{}", serde_json::to_string(&updated)?);
-                } else {
-                    println!("Stopped {}", job.short_id());
-                }
-                return Ok(());
-            }
-            Response::UserError(_) => {
+        match client.stop(&job.id, force).await {
+            Ok(()) => stopped = true,
+            Err(Error::NotRunning(_)) => {
                 // Job not running in daemon — fall back to direct kill below
             }
-            Response::Error(e) => {
-                anyhow::bail!("{e}");
-            }
-            _ => {}
+            Err(e) => return Err(e.into()),
         }
     }
 
     // Fallback: direct kill (for jobs started before daemon)
-    stop_without_daemon(&job, &db, force)?;
-
-    if json {
-        let updated = db
-            .get(&job.id)?
-            .ok_or_else(|| anyhow::anyhow!("job {} disappeared", job.short_id()))?;
-        println!("{}", serde_json::to_string(&updated)?);
-    } else {
-        println!("Stopped {}", job.short_id());
+    if !stopped {
+        stop_without_daemon(job, db, force)?;
     }
 
-    Ok(())
+    db.get(&job.id)?
+        .ok_or_else(|| anyhow::anyhow!("job {} disappeared", job.short_id()))
 }
 
 /// Direct stop path: used when daemon is unreachable or job was pre-daemon.
 /// Pending jobs: mark Stopped in DB. Running jobs: kill process group + mark Stopped.
-fn stop_without_daemon(
-    job: &crate::core::Job,
-    db: &crate::core::Database,
-    force: bool,
-) -> Result<()> {
+fn stop_without_daemon(job: &Job, db: &Database, force: bool) -> Result<()> {
     if job.status == Status::Pending {
         db.update_status(&job.id, Status::Stopped)?;
     } else if let Some(pid) = job.pid {
         kill_process_group(pid, force);
+        db.record_event(
+            &job.id,
+            HistoryKind::Signal,
+            Some(&signal_detail(pid, force)),
+        );
         db.update_finished(&job.id, Status::Stopped, None)?;
+    } else {
+        return Ok(());
     }
+    db.record_event(
+        &job.id,
+        HistoryKind::Stopped,
+        Some("by jb stop, daemon not running"),
+    );
     Ok(())
 }
 
@@ -1,6 +1,6 @@
 use crate::client::DaemonClient;
-use crate::core::ipc::{Request, Response};
 use crate::core::{Database, Job, Paths, parse_duration};
+use crate::error::Error;
 use anyhow::Result;
 use std::time::{Duration, Instant};
 
@@ -21,26 +21,30 @@ pub async fn execute(id: String, timeout: Option<String>) -> Result<()> {
 
     let timeout_secs = timeout.map(|t| parse_duration(&t)).transpose()?;
 
+    match wait_for(&db, &job, timeout_secs).await? {
+        Some(finished) => handle_terminal(&finished),
+        None => {
+            eprintln!("Timeout - job still running");
+            std::process::exit(124);
+        }
+    }
+    Ok(())
+}
+
+/// Wait for a job to finish, via the daemon or else by polling. `None` when
+/// `timeout_secs` passed first.
+pub(crate) async fn wait_for(
+    db: &Database,
+    job: &Job,
+    timeout_secs: Option<u64>,
+) -> Result<Option<Job>> {
     // Wait via daemon
     if let Ok(mut client) = DaemonClient::connect_or_start().await {
-        let request = Request::Wait {
-            id: job.id.clone(),
-            timeout_secs,
-        };
-
-        match client.send(request).await? {
-            Response::Job(completed) => {
-                handle_terminal(&completed);
-                return Ok(());
-            }
-            Response::Error(e) => {
-                if e.contains("timed out") {
-                    eprintln!("Timeout - job still running");
-                    std::process::exit(124);
-                }
-                anyhow::bail!("{e}");
-            }
-            _ => {}
+        let timeout = timeout_secs.map(Duration::from_secs);
+        match client.wait(&job.id, timeout).await {
+            Ok(completed) => return Ok(Some(completed)),
+            Err(Error::Timeout(_)) => return Ok(None),
+            Err(e) => return Err(e.into()),
         }
     }
 
@@ -53,15 +57,13 @@ pub async fn execute(id: String, timeout: Option<String>) -> Result<()> {
             .ok_or_else(|| anyhow::anyhow!("job {} disappeared", job.id))?;
 
         if current.status.is_terminal() {
-            handle_terminal(&current);
-            return Ok(());
+            return Ok(Some(current));
         }
 
         if let Some(timeout_secs) = timeout_secs
             && start.elapsed() > Duration::from_secs(timeout_secs)
         {
-            eprintln!("Timeout - job still running");
-            std::process::exit(124);
+            return Ok(None);
         }
 
         tokio::time::sleep(Duration::from_millis(500)).await;
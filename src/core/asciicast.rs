@@ -0,0 +1,178 @@
+use crate::core::Job;
+use chrono::{DateTime, Utc};
+
+/// Terminal size recorded in the header. Output was captured through pipes,
+/// not a terminal, so there is no real size; this is the conventional default.
+pub const WIDTH: u16 = 80;
+pub const HEIGHT: u16 = 24;
+
+/// The header line of an asciicast v2 recording of `job` starting at `start`
+#[must_use]
+pub fn header(job: &Job, start: DateTime<Utc>) -> String {
+    let title = job.name.as_deref().unwrap_or(&job.command);
+    serde_json::json!({
+        "version": 2,
+        "width": WIDTH,
+        "height": HEIGHT,
+        "timestamp": start.timestamp(),
+        "title": title,
+        "command": job.command,
+    })
+    .to_string()
+}
+
+/// One output event: seconds since the start and the text written
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: f64,
+    pub data: String,
+}
+
+impl Event {
+    /// The event as an asciicast v2 line, `[time, "o", data]`
+    #[must_use]
+    pub fn to_line(&self) -> String {
+        // Microsecond precision, as asciinema records
+        let time = (self.time * 1e6).round() / 1e6;
+        serde_json::json!([time, "o", self.data]).to_string()
+    }
+}
+
+/// Turns timed chunks of captured output into terminal output events. Bytes of
+/// a character split across chunks are carried to the next event, and bare
+/// `\n` becomes `\r\n`, since a terminal would have translated it.
+pub struct Recorder {
+    start: DateTime<Utc>,
+    last: f64,
+    carry: Vec<u8>,
+    after_cr: bool,
+}
+
+impl Recorder {
+    #[must_use]
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            last: 0.0,
+            carry: Vec::new(),
+            after_cr: false,
+        }
+    }
+
+    /// The event for a chunk received at `at`, if it holds any complete text.
+    /// Chunks without a time are placed at the previous event's time.
+    pub fn push(&mut self, at: Option<DateTime<Utc>>, data: &[u8]) -> Option<Event> {
+        if let Some(at) = at {
+            #[allow(clippy::cast_precision_loss)] // microseconds fit f64 exactly for centuries
+            let secs = (at - self.start).num_microseconds().unwrap_or(0) as f64 / 1e6;
+            // Never go back in time, even if the clock did
+            self.last = self.last.max(secs);
+        }
+
+        self.carry.extend_from_slice(data);
+        let text = self.take_text();
+        if text.is_empty() {
+            return None;
+        }
+        Some(Event {
+            time: self.last,
+            data: self.translate_newlines(&text),
+        })
+    }
+
+    /// Decode as much of the carried bytes as is complete; invalid bytes become U+FFFD.
+    fn take_text(&mut self) -> String {
+        let mut text = String::new();
+        let mut rest: &[u8] = &self.carry;
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // Incomplete character at the end: wait for the rest
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.carry = rest.to_vec();
+        text
+    }
+
+    fn translate_newlines(&mut self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len() + text.len() / 16);
+        for c in text.chars() {
+            if c == '\n' && !self.after_cr {
+                out.push('\r');
+            }
+            out.push(c);
+            self.after_cr = c == '\r';
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: i64) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp_millis(1_000_000 + millis)
+    }
+
+    #[test]
+    fn test_events_timed_from_start() {
+        let mut rec = Recorder::new(at(0).unwrap());
+        let first = rec.push(at(250), b"hello\n").unwrap();
+        assert_eq!(first.time, 0.25);
+        assert_eq!(first.data, "hello\r\n");
+        // A clock going backwards doesn't reorder events
+        assert_eq!(rec.push(at(100), b"x").unwrap().time, 0.25);
+        assert_eq!(rec.push(None, b"y").unwrap().time, 0.25);
+        assert_eq!(first.to_line(), r#"[0.25,"o","hello\r\n"]"#);
+    }
+
+    #[test]
+    fn test_existing_crlf_kept() {
+        let mut rec = Recorder::new(at(0).unwrap());
+        assert_eq!(rec.push(at(0), b"a\r").unwrap().data, "a\r");
+        assert_eq!(rec.push(at(0), b"\nb\n").unwrap().data, "\nb\r\n");
+    }
+
+    #[test]
+    fn test_split_character_carried() {
+        let mut rec = Recorder::new(at(0).unwrap());
+        let bytes = "é".as_bytes();
+        assert!(rec.push(at(0), &bytes[..1]).is_none());
+        assert_eq!(rec.push(at(10), &bytes[1..]).unwrap().data, "é");
+        assert_eq!(rec.push(at(20), b"\xffok").unwrap().data, "\u{fffd}ok");
+    }
+
+    #[test]
+    fn test_header_fields() {
+        let job = Job::new(
+            "abc1".into(),
+            "make test".into(),
+            "/tmp".into(),
+            "/tmp".into(),
+        );
+        let header: serde_json::Value =
+            serde_json::from_str(&header(&job, at(0).unwrap())).unwrap();
+        assert_eq!(header["version"], 2);
+        assert_eq!(header["title"], "make test");
+        assert_eq!(header["timestamp"], 1000);
+    }
+}
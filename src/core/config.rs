@@ -0,0 +1,269 @@
+use crate::core::hooks::Hooks;
+use crate::core::webhook::Webhook;
+use crate::core::{Paths, parse_duration, parse_size};
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+/// User configuration from `~/.jb/config.toml`. Every setting is optional;
+/// a missing file is the same as an empty one.
+///
+/// ```toml
+/// [daemon]
+/// idle_timeout = "30m"
+///
+/// [logs]
+/// max_size = "100M"
+/// keep = 3
+///
+/// [redact]
+/// env = ["GITHUB_TOKEN", "DATABASE_URL"]
+/// patterns = ['password=\S+']
+///
+/// [http]
+/// listen = "127.0.0.1:7070"
+///
+/// [hooks]
+/// on_failure = 'notify-send "jb: $JB_JOB_ID failed"'
+///
+/// [notify]
+/// default = true
+/// min_duration = "1m"
+///
+/// [[webhooks]]
+/// url = "https://ci.internal/jb"
+/// secret = "..."
+/// events = ["failed"]
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub daemon: DaemonConfig,
+    pub logs: LogsConfig,
+    pub redact: RedactConfig,
+    pub http: HttpConfig,
+    /// Run for every job, after the job's own hooks
+    pub hooks: Hooks,
+    pub notify: NotifyConfig,
+    /// Posted every finished job, after the job's own webhooks
+    pub webhooks: Vec<Webhook>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DaemonConfig {
+    /// Exit after this long with no jobs and no clients (e.g. "30m"). Unset: never exit.
+    pub idle_timeout: Option<String>,
+}
+
+/// Defaults for jobs that don't set `--log-max-size` / `--log-keep`
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogsConfig {
+    /// Rotate a job's log once it grows past this size (e.g. "100M"). Unset: unlimited.
+    pub max_size: Option<String>,
+    /// Rotated segments to keep (0 discards old output)
+    pub keep: Option<u32>,
+}
+
+/// Secrets the daemon replaces in captured output before it is written
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RedactConfig {
+    /// Redact common key formats (AWS, GitHub, Slack, `sk-` API keys, JWTs, ...)
+    pub builtin: bool,
+    /// Environment variables whose values are secret
+    pub env: Vec<String>,
+    /// Additional regular expressions to redact
+    pub patterns: Vec<String>,
+}
+
+impl Default for RedactConfig {
+    fn default() -> Self {
+        Self {
+            builtin: true,
+            env: Vec::new(),
+            patterns: Vec::new(),
+        }
+    }
+}
+
+/// Desktop notifications for finished jobs
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// Notify for jobs run without `--notify` / `--no-notify`
+    pub default: bool,
+    /// Skip jobs that ran for less than this (e.g. "1m")
+    pub min_duration: Option<String>,
+}
+
+/// The daemon's optional HTTP API
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HttpConfig {
+    /// A loopback address ("127.0.0.1:7070", "localhost:7070"), "unix" for
+    /// `http.sock` in the jb directory, or "unix:<path>". Unset: no HTTP API.
+    pub listen: Option<String>,
+}
+
+/// Where the HTTP API listens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HttpListen {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl Config {
+    pub fn load(paths: &Paths) -> Result<Self> {
+        let path = paths.config();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)?;
+        Self::parse(&content).with_context(|| format!("invalid config: {}", path.display()))
+    }
+
+    pub fn parse(content: &str) -> Result<Self> {
+        Ok(toml::from_str(content)?)
+    }
+}
+
+impl DaemonConfig {
+    pub fn idle_timeout_secs(&self) -> Result<Option<u64>> {
+        self.idle_timeout
+            .as_deref()
+            .map(parse_duration)
+            .transpose()
+            .context("daemon.idle_timeout")
+    }
+}
+
+impl LogsConfig {
+    pub fn max_size_bytes(&self) -> Result<Option<u64>> {
+        self.max_size
+            .as_deref()
+            .map(parse_size)
+            .transpose()
+            .context("logs.max_size")
+    }
+}
+
+impl NotifyConfig {
+    pub fn min_duration_secs(&self) -> Result<Option<u64>> {
+        self.min_duration
+            .as_deref()
+            .map(parse_duration)
+            .transpose()
+            .context("notify.min_duration")
+    }
+}
+
+impl HttpConfig {
+    /// The address to listen on, if the API is enabled. Only loopback
+    /// addresses are accepted: the API can run commands.
+    pub fn listen_on(&self, paths: &Paths) -> Result<Option<HttpListen>> {
+        self.listen
+            .as_deref()
+            .map(|listen| parse_listen(listen.trim(), paths))
+            .transpose()
+            .context("http.listen")
+    }
+}
+
+fn parse_listen(listen: &str, paths: &Paths) -> Result<HttpListen> {
+    if listen == "unix" {
+        return Ok(HttpListen::Unix(paths.http_socket()));
+    }
+    if let Some(path) = listen.strip_prefix("unix:") {
+        return Ok(HttpListen::Unix(PathBuf::from(path)));
+    }
+    let addr = match listen.strip_prefix("localhost:") {
+        Some(port) => SocketAddr::from((Ipv4Addr::LOCALHOST, port.parse::<u16>()?)),
+        None => listen.parse::<SocketAddr>()?,
+    };
+    if !addr.ip().is_loopback() {
+        bail!("{addr} is not a loopback address");
+    }
+    Ok(HttpListen::Tcp(addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Status;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_missing_file_is_default() {
+        let tmp = TempDir::new().unwrap();
+        let paths = Paths::with_root(tmp.path().to_path_buf());
+        let config = Config::load(&paths).unwrap();
+        assert!(config.daemon.idle_timeout.is_none());
+    }
+
+    #[test]
+    fn test_idle_timeout_parsed() {
+        let config = Config::parse("[daemon]\nidle_timeout = \"15m\"\n").unwrap();
+        assert_eq!(config.daemon.idle_timeout_secs().unwrap(), Some(900));
+    }
+
+    #[test]
+    fn test_invalid_idle_timeout_errors() {
+        let config = Config::parse("[daemon]\nidle_timeout = \"soon\"\n").unwrap();
+        assert!(config.daemon.idle_timeout_secs().is_err());
+    }
+
+    #[test]
+    fn test_log_defaults_parsed() {
+        let config = Config::parse("[logs]\nmax_size = \"10M\"\nkeep = 0\n").unwrap();
+        assert_eq!(config.logs.max_size_bytes().unwrap(), Some(10 << 20));
+        assert_eq!(config.logs.keep, Some(0));
+    }
+
+    #[test]
+    fn test_redact_parsed_with_builtin_default() {
+        let config = Config::parse("[redact]\nenv = [\"API_TOKEN\"]\n").unwrap();
+        assert!(config.redact.builtin);
+        assert_eq!(config.redact.env, vec!["API_TOKEN"]);
+        assert!(Config::default().redact.builtin);
+    }
+
+    #[test]
+    fn test_unknown_key_errors() {
+        assert!(Config::parse("[daemon]\nidle_timout = \"5m\"\n").is_err());
+    }
+
+    #[test]
+    fn test_http_listen() {
+        let paths = Paths::with_root(PathBuf::from("/tmp/jb"));
+        let listen = |value: &str| {
+            let config = Config::parse(&format!("[http]\nlisten = \"{value}\"\n")).unwrap();
+            config.http.listen_on(&paths)
+        };
+        assert_eq!(
+            listen("localhost:7070").unwrap(),
+            Some(HttpListen::Tcp("127.0.0.1:7070".parse().unwrap()))
+        );
+        assert_eq!(
+            listen("unix").unwrap(),
+            Some(HttpListen::Unix(PathBuf::from("/tmp/jb/http.sock")))
+        );
+        assert!(listen("0.0.0.0:7070").is_err());
+        assert!(listen("nowhere").is_err());
+        assert_eq!(Config::default().http.listen_on(&paths).unwrap(), None);
+    }
+
+    #[test]
+    fn test_webhooks_parsed() {
+        let config = Config::parse(
+            "[[webhooks]]\nurl = \"http://localhost:9000/jb\"\nevents = [\"failed\"]\n",
+        )
+        .unwrap();
+        assert_eq!(config.webhooks.len(), 1);
+        assert!(config.webhooks[0].secret.is_none());
+        assert!(config.webhooks[0].wants(Status::Failed));
+        assert!(!config.webhooks[0].wants(Status::Completed));
+    }
+}
@@ -1,6 +1,11 @@
 use crate::core::Paths;
 use crate::core::error::UserError;
+use crate::core::event::{HistoryEntry, HistoryKind};
+use crate::core::hooks::{HookRun, Hooks};
 use crate::core::job::{Job, Status};
+use crate::core::output::{Segment, read_indexable};
+use crate::core::webhook::Delivery;
+use crate::error::Error;
 use anyhow::{Result, bail};
 use rand::Rng;
 use rusqlite::{Connection, OptionalExtension, params};
@@ -11,10 +16,29 @@ pub struct Database {
     conn: Connection,
 }
 
+/// Marks around matched terms in `search_output` snippets
+pub const SNIPPET_START: char = '\u{2}';
+pub const SNIPPET_END: char = '\u{3}';
+
+/// Columns added to `jobs` after the original schema, as (name, type).
+/// Databases created by older versions get them on open.
+const ADDED_COLUMNS: &[(&str, &str)] = &[
+    ("log_max_size", "INTEGER"),
+    ("log_keep", "INTEGER"),
+    ("on_success", "TEXT"),
+    ("on_failure", "TEXT"),
+    ("on_exit", "TEXT"),
+    // JSON array of webhooks
+    ("webhooks", "TEXT"),
+    ("notify", "INTEGER"),
+];
+
 impl Database {
     pub fn open(paths: &Paths) -> Result<Self> {
         paths.ensure_dirs()?;
         let conn = Connection::open(paths.database())?;
+        // The daemon and CLI commands share the database; wait out each other's writes
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
         let db = Self { conn };
         db.init_schema()?;
         Ok(db)
@@ -43,18 +67,80 @@ impl Database {
             CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status);
             CREATE INDEX IF NOT EXISTS idx_jobs_project ON jobs(project);
             CREATE INDEX IF NOT EXISTS idx_jobs_created_at ON jobs(created_at);
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS job_output USING fts5(id UNINDEXED, output);
+
+            CREATE TABLE IF NOT EXISTS job_events (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                job_id TEXT NOT NULL,
+                at TEXT NOT NULL,
+                kind TEXT NOT NULL,
+                detail TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_job_events_job_id ON job_events(job_id);
+
+            CREATE TABLE IF NOT EXISTS job_hooks (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                job_id TEXT NOT NULL,
+                hook TEXT NOT NULL,
+                command TEXT NOT NULL,
+                global INTEGER NOT NULL,
+                started_at TEXT NOT NULL,
+                exit_code INTEGER,
+                output TEXT NOT NULL
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_job_hooks_job_id ON job_hooks(job_id);
+
+            CREATE TABLE IF NOT EXISTS webhook_deliveries (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                job_id TEXT NOT NULL,
+                url TEXT NOT NULL,
+                attempt INTEGER NOT NULL,
+                at TEXT NOT NULL,
+                status_code INTEGER,
+                error TEXT
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_job_id ON webhook_deliveries(job_id);
             ",
         )?;
+        self.add_missing_columns()
+    }
+
+    fn add_missing_columns(&self) -> Result<()> {
+        let existing: Vec<String> = self
+            .conn
+            .prepare("SELECT name FROM pragma_table_info('jobs')")?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for (name, ty) in ADDED_COLUMNS {
+            if !existing.iter().any(|c| c == name) {
+                self.conn
+                    .execute(&format!("ALTER TABLE jobs ADD COLUMN {name} {ty}"), [])?;
+            }
+        }
         Ok(())
     }
 
     pub fn insert(&self, job: &Job) -> Result<()> {
+        let webhooks = if job.webhooks.is_empty() {
+            None
+        } else {
+            Some(serde_json::to_string(&job.webhooks)?)
+        };
         self.conn.execute(
             r"
             INSERT INTO jobs (
                 id, name, command, status, project, cwd, pid, exit_code,
-                created_at, started_at, finished_at, timeout_secs, idempotency_key
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                created_at, started_at, finished_at, timeout_secs, idempotency_key,
+                log_max_size, log_keep, on_success, on_failure, on_exit, webhooks,
+                notify
+            ) VALUES (
+                ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18,
+                ?19, ?20
+            )
             ",
             params![
                 job.id,
@@ -70,11 +156,28 @@ impl Database {
                 job.finished_at.map(|t| t.to_rfc3339()),
                 job.timeout_secs,
                 job.idempotency_key,
+                job.log_max_size,
+                job.log_keep,
+                job.hooks.on_success,
+                job.hooks.on_failure,
+                job.hooks.on_exit,
+                webhooks,
+                job.notify,
             ],
         )?;
         Ok(())
     }
 
+    /// Insert `jobs` in one transaction: all of them or, on error, none
+    pub fn insert_all(&self, jobs: &[Job]) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        for job in jobs {
+            self.insert(job)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
     pub fn get(&self, id: &str) -> Result<Option<Job>> {
         let job = self
             .conn
@@ -95,6 +198,52 @@ impl Database {
         Ok(jobs)
     }
 
+    /// The most recent job of each name matching a glob (`*`, `?`, `[...]`), by name.
+    pub fn latest_by_name_glob(&self, pattern: &str) -> Result<Vec<Job>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT * FROM jobs AS j WHERE name GLOB ?1 AND created_at = \
+             (SELECT MAX(created_at) FROM jobs WHERE name = j.name) ORDER BY name",
+        )?;
+        let jobs = stmt
+            .query_map(params![pattern], Self::row_to_job)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
+    /// Jobs matching all the given filters, oldest first: a status, a name glob,
+    /// and created at or after `since`.
+    pub fn find(
+        &self,
+        status: Option<Status>,
+        name: Option<&str>,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<Job>> {
+        let mut sql = String::from("SELECT * FROM jobs WHERE 1=1");
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(s) = status {
+            sql.push_str(" AND status = ?");
+            params_vec.push(Box::new(s.as_str().to_string()));
+        }
+        if let Some(pattern) = name {
+            sql.push_str(" AND name GLOB ?");
+            params_vec.push(Box::new(pattern.to_string()));
+        }
+        if let Some(t) = since {
+            sql.push_str(" AND created_at >= ?");
+            params_vec.push(Box::new(t.to_rfc3339()));
+        }
+        sql.push_str(" ORDER BY created_at ASC");
+
+        let mut stmt = self.conn.prepare(&sql)?;
+        let params_refs: Vec<&dyn rusqlite::ToSql> =
+            params_vec.iter().map(std::convert::AsRef::as_ref).collect();
+        let jobs = stmt
+            .query_map(params_refs.as_slice(), Self::row_to_job)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(jobs)
+    }
+
     /// Check if a name is in use by an active (pending/running) job. Returns the job if so.
     pub fn name_in_use(&self, name: &str) -> Result<Option<Job>> {
         let job = self
@@ -121,6 +270,17 @@ impl Database {
     }
 
     pub fn list(&self, status: Option<Status>, limit: Option<usize>) -> Result<Vec<Job>> {
+        self.list_output_contains(None, status, limit)
+    }
+
+    /// Like `list`, but with `text`, only jobs whose indexed output contains it
+    /// (as words, not a substring: "segfault" doesn't match "segfaulted").
+    pub fn list_output_contains(
+        &self,
+        text: Option<&str>,
+        status: Option<Status>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Job>> {
         let mut sql = String::from("SELECT * FROM jobs WHERE 1=1");
         let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
@@ -128,6 +288,11 @@ impl Database {
             sql.push_str(" AND status = ?");
             params_vec.push(Box::new(s.as_str().to_string()));
         }
+        if let Some(text) = text {
+            // Quoted, the text is one phrase rather than query syntax
+            sql.push_str(" AND id IN (SELECT id FROM job_output WHERE job_output MATCH ?)");
+            params_vec.push(Box::new(format!("\"{}\"", text.replace('"', "\"\""))));
+        }
 
         sql.push_str(" ORDER BY created_at DESC");
 
@@ -194,9 +359,253 @@ impl Database {
         let params_refs: Vec<&dyn rusqlite::ToSql> =
             params_vec.iter().map(std::convert::AsRef::as_ref).collect();
         let count = self.conn.execute(&sql, params_refs.as_slice())?;
+        self.conn.execute(
+            "DELETE FROM job_output WHERE id NOT IN (SELECT id FROM jobs)",
+            [],
+        )?;
+        self.conn.execute(
+            "DELETE FROM job_events WHERE job_id NOT IN (SELECT id FROM jobs)",
+            [],
+        )?;
+        self.conn.execute(
+            "DELETE FROM job_hooks WHERE job_id NOT IN (SELECT id FROM jobs)",
+            [],
+        )?;
+        self.conn.execute(
+            "DELETE FROM webhook_deliveries WHERE job_id NOT IN (SELECT id FROM jobs)",
+            [],
+        )?;
         Ok(count)
     }
 
+    /// Record a hook that ran for a job.
+    pub fn record_hook(&self, id: &str, run: &HookRun) -> Result<()> {
+        self.conn.execute(
+            r"
+            INSERT INTO job_hooks (job_id, hook, command, global, started_at, exit_code, output)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            ",
+            params![
+                id,
+                run.hook.as_str(),
+                run.command,
+                run.global,
+                run.started_at.to_rfc3339(),
+                run.exit_code,
+                run.output,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The hooks that ran for a job, in order
+    pub fn hook_runs(&self, id: &str) -> Result<Vec<HookRun>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT hook, command, global, started_at, exit_code, output
+            FROM job_hooks WHERE job_id = ?1 ORDER BY seq
+            ",
+        )?;
+        let rows = stmt.query_map(params![id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, Option<i32>>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+        let mut runs = Vec::new();
+        for row in rows {
+            let (hook, command, global, started_at, exit_code, output) = row?;
+            runs.push(HookRun {
+                hook: hook.parse()?,
+                command,
+                global,
+                started_at: chrono::DateTime::parse_from_rfc3339(&started_at)?
+                    .with_timezone(&chrono::Utc),
+                exit_code,
+                output,
+            });
+        }
+        Ok(runs)
+    }
+
+    /// Record an attempt to post a job to a webhook.
+    pub fn record_delivery(&self, id: &str, delivery: &Delivery) -> Result<()> {
+        self.conn.execute(
+            r"
+            INSERT INTO webhook_deliveries (job_id, url, attempt, at, status_code, error)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+            ",
+            params![
+                id,
+                delivery.url,
+                delivery.attempt,
+                delivery.at.to_rfc3339(),
+                delivery.status_code,
+                delivery.error,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Attempts to post a job to its webhooks, in order
+    pub fn deliveries(&self, id: &str) -> Result<Vec<Delivery>> {
+        let mut stmt = self.conn.prepare(
+            r"
+            SELECT url, attempt, at, status_code, error
+            FROM webhook_deliveries WHERE job_id = ?1 ORDER BY seq
+            ",
+        )?;
+        let rows = stmt.query_map(params![id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<u16>>(3)?,
+                row.get::<_, Option<String>>(4)?,
+            ))
+        })?;
+        let mut deliveries = Vec::new();
+        for row in rows {
+            let (url, attempt, at, status_code, error) = row?;
+            deliveries.push(Delivery {
+                url,
+                attempt,
+                at: chrono::DateTime::parse_from_rfc3339(&at)?.with_timezone(&chrono::Utc),
+                status_code,
+                error,
+            });
+        }
+        Ok(deliveries)
+    }
+
+    /// Append a step to a job's history. The history is informational, so a
+    /// failure to write it is logged rather than returned.
+    pub fn record_event(&self, id: &str, kind: HistoryKind, detail: Option<&str>) {
+        if let Err(e) = self.conn.execute(
+            "INSERT INTO job_events (job_id, at, kind, detail) VALUES (?1, ?2, ?3, ?4)",
+            params![id, chrono::Utc::now().to_rfc3339(), kind.as_str(), detail],
+        ) {
+            warn!("Failed to record {kind} event for job {id}: {e}");
+        }
+    }
+
+    /// A job's recorded history, oldest first
+    pub fn history(&self, id: &str) -> Result<Vec<HistoryEntry>> {
+        let mut stmt = self
+            .conn
+            .prepare("SELECT at, kind, detail FROM job_events WHERE job_id = ?1 ORDER BY seq")?;
+        let rows = stmt.query_map(params![id], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, Option<String>>(2)?,
+            ))
+        })?;
+        let mut entries = Vec::new();
+        for row in rows {
+            let (at, kind, detail) = row?;
+            entries.push(HistoryEntry {
+                at: chrono::DateTime::parse_from_rfc3339(&at)?.with_timezone(&chrono::Utc),
+                kind: kind.parse()?,
+                detail,
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Replace a job's entry in the full-text index of output.
+    pub fn index_output(&self, id: &str, output: &str) -> Result<()> {
+        let tx = self.conn.unchecked_transaction()?;
+        tx.execute("DELETE FROM job_output WHERE id = ?1", params![id])?;
+        tx.execute(
+            "INSERT INTO job_output (id, output) VALUES (?1, ?2)",
+            params![id, output],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Index the output of finished jobs that aren't indexed yet: jobs that
+    /// finished under an older jb, or before the daemon got to them.
+    pub fn index_finished(&self, paths: &Paths) -> Result<usize> {
+        let ids: Vec<String> = self
+            .conn
+            .prepare(
+                "SELECT id FROM jobs WHERE status IN ('completed', 'failed', 'stopped', 'interrupted') \
+                 AND id NOT IN (SELECT id FROM job_output)",
+            )?
+            .query_map([], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        for id in &ids {
+            let active = Segment::active(paths, id);
+            let output = if active.exists() {
+                read_indexable(&active)?
+            } else {
+                String::new()
+            };
+            self.index_output(id, &output)?;
+        }
+        Ok(ids.len())
+    }
+
+    /// Jobs whose indexed output matches an FTS5 query, best match first, each
+    /// with a snippet of the matching output. Matched terms in the snippet are
+    /// wrapped in `SNIPPET_START` and `SNIPPET_END`.
+    pub fn search_output(
+        &self,
+        query: &str,
+        status: Option<Status>,
+        name: Option<&str>,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Result<Vec<(Job, String)>> {
+        let mut sql = String::from(
+            "SELECT j.*, snippet(job_output, 1, ?, ?, '…', 16) AS snippet \
+             FROM job_output JOIN jobs AS j ON j.id = job_output.id WHERE job_output MATCH ?",
+        );
+        let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = vec![
+            Box::new(SNIPPET_START.to_string()),
+            Box::new(SNIPPET_END.to_string()),
+            Box::new(query.to_string()),
+        ];
+        if let Some(s) = status {
+            sql.push_str(" AND j.status = ?");
+            params_vec.push(Box::new(s.as_str().to_string()));
+        }
+        if let Some(pattern) = name {
+            sql.push_str(" AND j.name GLOB ?");
+            params_vec.push(Box::new(pattern.to_string()));
+        }
+        if let Some(t) = since {
+            sql.push_str(" AND j.created_at >= ?");
+            params_vec.push(Box::new(t.to_rfc3339()));
+        }
+        sql.push_str(" ORDER BY rank");
+
+        let params_refs: Vec<&dyn rusqlite::ToSql> =
+            params_vec.iter().map(std::convert::AsRef::as_ref).collect();
+        let hits = self
+            .conn
+            .prepare(&sql)
+            .and_then(|mut stmt| {
+                stmt.query_map(params_refs.as_slice(), |row| {
+                    Ok((Self::row_to_job(row)?, row.get("snippet")?))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()
+            })
+            // A plain SQL error (not busy, I/O, ...) here means a malformed query
+            .map_err(|e| match e.sqlite_error_code() {
+                Some(rusqlite::ErrorCode::Unknown) => {
+                    anyhow::Error::new(UserError::new(format!("Invalid search query: {e}")))
+                }
+                _ => e.into(),
+            })?;
+        Ok(hits)
+    }
+
     fn row_to_job(row: &rusqlite::Row) -> rusqlite::Result<Job> {
         Ok(Job {
             id: row.get("id")?,
@@ -222,6 +631,18 @@ impl Database {
                 .map(|t| t.with_timezone(&chrono::Utc)),
             timeout_secs: row.get("timeout_secs")?,
             idempotency_key: row.get("idempotency_key")?,
+            log_max_size: row.get("log_max_size")?,
+            log_keep: row.get("log_keep")?,
+            hooks: Hooks {
+                on_success: row.get("on_success")?,
+                on_failure: row.get("on_failure")?,
+                on_exit: row.get("on_exit")?,
+            },
+            webhooks: row
+                .get::<_, Option<String>>("webhooks")?
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default(),
+            notify: row.get("notify")?,
         })
     }
 
@@ -264,9 +685,7 @@ impl Database {
         // Try by name - get most recent
         let mut by_name = self.get_by_name(id)?;
         if by_name.is_empty() {
-            bail!(UserError::new(format!(
-                "No job found with ID or name '{id}'"
-            )));
+            bail!(Error::NotFound(id.to_string()));
         }
 
         // Sort by created_at desc (newest first) and return first
@@ -318,7 +737,13 @@ impl Database {
             // Process dead or no PID - mark as interrupted
             if let Err(e) = self.update_finished(&job.id, Status::Interrupted, None) {
                 warn!("Failed to mark orphaned job {} as interrupted: {e}", job.id);
+                continue;
             }
+            self.record_event(
+                &job.id,
+                HistoryKind::Interrupted,
+                Some("process gone after the daemon exited"),
+            );
         }
     }
 }
@@ -346,6 +771,7 @@ fn is_process_alive(_pid: u32) -> bool {
 mod tests {
     use super::*;
     use crate::core::job::Job;
+    use crate::core::webhook::Webhook;
     use tempfile::TempDir;
 
     fn test_db() -> (Database, TempDir) {
@@ -378,6 +804,25 @@ mod tests {
         assert_eq!(retrieved.status, Status::Pending);
     }
 
+    #[test]
+    fn test_insert_all_is_atomic() {
+        let (db, _tmp) = test_db();
+        let jobs = [
+            create_test_job("abc1", Status::Pending),
+            create_test_job("abc2", Status::Pending),
+        ];
+        db.insert_all(&jobs).unwrap();
+        assert!(db.job_exists("abc2").unwrap());
+
+        // The duplicate ID fails the batch, and the first job goes with it
+        let jobs = [
+            create_test_job("abc3", Status::Pending),
+            create_test_job("abc1", Status::Pending),
+        ];
+        assert!(db.insert_all(&jobs).is_err());
+        assert!(!db.job_exists("abc3").unwrap());
+    }
+
     #[test]
     fn test_get_nonexistent() {
         let (db, _tmp) = test_db();
@@ -398,6 +843,52 @@ mod tests {
         assert!(db.get("xy").unwrap().is_some());
     }
 
+    #[test]
+    fn test_history_recorded_in_order() {
+        let (db, _tmp) = test_db();
+        db.insert(&create_test_job("abc1", Status::Stopped))
+            .unwrap();
+        db.record_event("abc1", HistoryKind::Created, None);
+        db.record_event("abc1", HistoryKind::Timeout, Some("after 30s"));
+        db.record_event("xyz9", HistoryKind::Created, None);
+
+        let history = db.history("abc1").unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].kind, HistoryKind::Timeout);
+        assert_eq!(history[1].detail.as_deref(), Some("after 30s"));
+
+        // Removing jobs removes their history
+        db.delete_old(chrono::Utc::now() + chrono::Duration::days(1), None)
+            .unwrap();
+        assert!(db.history("abc1").unwrap().is_empty());
+        assert!(db.history("xyz9").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_webhooks_stored_with_job() {
+        let (db, _tmp) = test_db();
+        let webhook = Webhook {
+            secret: Some("s3cret".into()),
+            ..Webhook::new("http://localhost:9000/jb")
+        };
+        db.insert(&create_test_job("abc1", Status::Failed).with_webhooks(vec![webhook.clone()]))
+            .unwrap();
+        assert_eq!(db.get("abc1").unwrap().unwrap().webhooks, vec![webhook]);
+
+        let delivery = Delivery {
+            url: "http://localhost:9000/jb".into(),
+            attempt: 1,
+            at: chrono::Utc::now(),
+            status_code: Some(503),
+            error: Some("HTTP 503".into()),
+        };
+        db.record_delivery("abc1", &delivery).unwrap();
+        let deliveries = db.deliveries("abc1").unwrap();
+        assert_eq!(deliveries.len(), 1);
+        assert_eq!(deliveries[0].status_code, Some(503));
+        assert!(!deliveries[0].succeeded());
+    }
+
     #[test]
     fn test_get_by_name() {
         let (db, _tmp) = test_db();
@@ -412,6 +903,142 @@ mod tests {
         assert!(jobs.is_empty());
     }
 
+    #[test]
+    fn test_latest_by_name_glob() {
+        let (db, _tmp) = test_db();
+        let mut old = create_test_job("aaa1", Status::Failed).with_name("svc-api");
+        old.created_at -= chrono::Duration::seconds(10);
+        db.insert(&old).unwrap();
+        db.insert(&create_test_job("bbb2", Status::Running).with_name("svc-api"))
+            .unwrap();
+        db.insert(&create_test_job("ccc3", Status::Running).with_name("svc-web"))
+            .unwrap();
+        db.insert(&create_test_job("ddd4", Status::Running).with_name("worker"))
+            .unwrap();
+
+        let ids: Vec<String> = db
+            .latest_by_name_glob("svc-*")
+            .unwrap()
+            .into_iter()
+            .map(|j| j.id)
+            .collect();
+        assert_eq!(ids, vec!["bbb2", "ccc3"]);
+    }
+
+    #[test]
+    fn test_find_filters_oldest_first() {
+        let (db, _tmp) = test_db();
+        let mut old = create_test_job("aaa1", Status::Failed).with_name("build");
+        old.created_at -= chrono::Duration::days(3);
+        db.insert(&old).unwrap();
+        let mut mid = create_test_job("bbb2", Status::Failed).with_name("build");
+        mid.created_at -= chrono::Duration::hours(1);
+        db.insert(&mid).unwrap();
+        db.insert(&create_test_job("ccc3", Status::Completed).with_name("build"))
+            .unwrap();
+        db.insert(&create_test_job("ddd4", Status::Failed).with_name("test"))
+            .unwrap();
+
+        let ids = |jobs: Vec<Job>| jobs.into_iter().map(|j| j.id).collect::<Vec<_>>();
+        assert_eq!(
+            ids(db.find(Some(Status::Failed), Some("build"), None).unwrap()),
+            vec!["aaa1", "bbb2"]
+        );
+        let since = chrono::Utc::now() - chrono::Duration::days(1);
+        assert_eq!(
+            ids(db.find(Some(Status::Failed), None, Some(since)).unwrap()),
+            vec!["bbb2", "ddd4"]
+        );
+        assert_eq!(db.find(None, None, None).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_search_output_ranked_and_filtered() {
+        let (db, _tmp) = test_db();
+        db.insert(&create_test_job("aaa1", Status::Failed).with_name("build"))
+            .unwrap();
+        db.insert(&create_test_job("bbb2", Status::Completed).with_name("test"))
+            .unwrap();
+        db.index_output("aaa1", "compiling\nSegfault in worker\nsegfault again\n")
+            .unwrap();
+        db.index_output("bbb2", "one segfault among many other words here\n")
+            .unwrap();
+
+        let hits = db.search_output("segfault", None, None, None).unwrap();
+        let ids: Vec<&str> = hits.iter().map(|(j, _)| j.id.as_str()).collect();
+        assert_eq!(ids, vec!["aaa1", "bbb2"]);
+        assert!(
+            hits[0]
+                .1
+                .contains(&format!("{SNIPPET_START}Segfault{SNIPPET_END}"))
+        );
+
+        let hits = db
+            .search_output("segfault", Some(Status::Completed), None, None)
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert!(
+            db.search_output("missing", None, None, None)
+                .unwrap()
+                .is_empty()
+        );
+
+        let err = db
+            .search_output("\"unclosed", None, None, None)
+            .unwrap_err();
+        assert!(err.downcast_ref::<UserError>().is_some(), "{err:?}");
+    }
+
+    #[test]
+    fn test_index_output_replaces_entry() {
+        let (db, _tmp) = test_db();
+        db.insert(&create_test_job("aaa1", Status::Completed))
+            .unwrap();
+        db.index_output("aaa1", "partial").unwrap();
+        db.index_output("aaa1", "partial then complete").unwrap();
+
+        let hits = db.search_output("partial", None, None, None).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(
+            db.list_output_contains(Some("then complete"), None, None)
+                .unwrap()
+                .len(),
+            1
+        );
+        assert!(
+            db.list_output_contains(Some("complete then"), None, None)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_index_finished_and_delete_old_keep_index_in_sync() {
+        let tmp = TempDir::new().unwrap();
+        let paths = Paths::with_root(tmp.path().to_path_buf());
+        let db = Database::open(&paths).unwrap();
+        let mut old = create_test_job("aaa1", Status::Failed);
+        old.created_at -= chrono::Duration::days(10);
+        db.insert(&old).unwrap();
+        db.insert(&create_test_job("bbb2", Status::Running))
+            .unwrap();
+        std::fs::write(paths.log_file("aaa1"), "disk full\n").unwrap();
+        std::fs::write(paths.log_file("bbb2"), "disk full\n").unwrap();
+
+        // Only finished jobs are indexed, once
+        assert_eq!(db.index_finished(&paths).unwrap(), 1);
+        assert_eq!(db.index_finished(&paths).unwrap(), 0);
+        let found = db.list_output_contains(Some("disk"), None, None).unwrap();
+        assert_eq!(found.len(), 1);
+
+        db.delete_old(chrono::Utc::now(), None).unwrap();
+        let indexed: i64 = db
+            .conn
+            .query_row("SELECT COUNT(*) FROM job_output", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(indexed, 0);
+    }
+
     #[test]
     fn test_list_no_filter() {
         let (db, _tmp) = test_db();
@@ -690,4 +1317,36 @@ mod tests {
         let in_use = db.name_in_use("my-job").unwrap();
         assert!(in_use.is_none());
     }
+
+    #[test]
+    fn test_log_limit_roundtrip() {
+        let (db, _tmp) = test_db();
+        let limit = crate::core::output::LogLimit {
+            max_bytes: 1024,
+            keep: 2,
+        };
+        db.insert(&create_test_job("a", Status::Running).with_log_limit(limit))
+            .unwrap();
+        assert_eq!(db.get("a").unwrap().unwrap().log_limit(), Some(limit));
+    }
+
+    #[test]
+    fn test_open_adds_columns_to_old_database() {
+        let tmp = TempDir::new().unwrap();
+        let paths = Paths::with_root(tmp.path().to_path_buf());
+        paths.ensure_dirs().unwrap();
+        let conn = Connection::open(paths.database()).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE jobs (id TEXT PRIMARY KEY, name TEXT, command TEXT NOT NULL,
+             status TEXT NOT NULL, project TEXT NOT NULL, cwd TEXT NOT NULL, pid INTEGER,
+             exit_code INTEGER, created_at TEXT NOT NULL, started_at TEXT, finished_at TEXT,
+             timeout_secs INTEGER, context TEXT, idempotency_key TEXT UNIQUE);",
+        )
+        .unwrap();
+        drop(conn);
+
+        let db = Database::open(&paths).unwrap();
+        db.insert(&create_test_job("a", Status::Completed)).unwrap();
+        assert!(db.get("a").unwrap().unwrap().log_limit().is_none());
+    }
 }
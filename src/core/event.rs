@@ -0,0 +1,256 @@
+use crate::core::{Job, Status};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// What happened to a job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EventKind {
+    /// Accepted by the daemon, not yet running
+    Created,
+    /// Its process was spawned
+    Started,
+    /// Exited by itself, successfully or not
+    Finished,
+    /// Stopped by request, by its timeout or by a daemon shutdown
+    Stopped,
+}
+
+impl EventKind {
+    /// The event a job reaching `status` makes
+    #[must_use]
+    pub fn for_status(status: Status) -> Self {
+        match status {
+            Status::Pending => Self::Created,
+            Status::Running => Self::Started,
+            Status::Completed | Status::Failed => Self::Finished,
+            Status::Stopped | Status::Interrupted => Self::Stopped,
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Started => "started",
+            Self::Finished => "finished",
+            Self::Stopped => "stopped",
+        }
+    }
+}
+
+impl std::fmt::Display for EventKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// A change in a job's lifecycle, with the job as it is afterwards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobEvent {
+    pub kind: EventKind,
+    pub at: DateTime<Utc>,
+    pub job: Job,
+}
+
+impl JobEvent {
+    /// The event for `job` having just reached its current status
+    #[must_use]
+    pub fn now(job: Job) -> Self {
+        Self {
+            kind: EventKind::for_status(job.status),
+            at: Utc::now(),
+            job,
+        }
+    }
+}
+
+/// A step in a job's recorded history (the `job_events` table). Finer
+/// grained than [`EventKind`]: it also says who stopped a job and how.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    Created,
+    Started,
+    /// A signal was sent to the job's process group
+    Signal,
+    /// The job's timeout passed
+    Timeout,
+    Stopped,
+    /// The daemon stopped and left the job running
+    Detached,
+    Interrupted,
+    Finished,
+    /// A completion hook ran
+    Hook,
+    /// The job was posted to a webhook, or posting it was given up
+    Webhook,
+    /// A desktop notification was shown, or couldn't be
+    Notification,
+}
+
+impl HistoryKind {
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Started => "started",
+            Self::Signal => "signal",
+            Self::Timeout => "timeout",
+            Self::Stopped => "stopped",
+            Self::Detached => "detached",
+            Self::Interrupted => "interrupted",
+            Self::Finished => "finished",
+            Self::Hook => "hook",
+            Self::Webhook => "webhook",
+            Self::Notification => "notification",
+        }
+    }
+}
+
+impl std::fmt::Display for HistoryKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for HistoryKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "created" => Ok(Self::Created),
+            "started" => Ok(Self::Started),
+            "signal" => Ok(Self::Signal),
+            "timeout" => Ok(Self::Timeout),
+            "stopped" => Ok(Self::Stopped),
+            "detached" => Ok(Self::Detached),
+            "interrupted" => Ok(Self::Interrupted),
+            "finished" => Ok(Self::Finished),
+            "hook" => Ok(Self::Hook),
+            "webhook" => Ok(Self::Webhook),
+            "notification" => Ok(Self::Notification),
+            _ => anyhow::bail!("unknown job event: {s}"),
+        }
+    }
+}
+
+/// One recorded step of a job's history
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub at: DateTime<Utc>,
+    pub kind: HistoryKind,
+    /// What happened, e.g. `pid 4242` or `by jb stop`
+    pub detail: Option<String>,
+}
+
+/// Detail for a [`HistoryKind::Signal`] entry
+#[must_use]
+pub fn signal_detail(pid: u32, force: bool) -> String {
+    let signal = if force { "SIGKILL" } else { "SIGTERM" };
+    format!("{signal} to process group {pid}")
+}
+
+/// Which events a watcher wants; unset fields match everything
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventFilter {
+    /// Jobs in this project root
+    pub project: Option<PathBuf>,
+    /// Jobs whose name matches this glob (`*` and `?`)
+    pub name: Option<String>,
+    /// Jobs that reached this status
+    pub status: Option<Status>,
+}
+
+impl EventFilter {
+    #[must_use]
+    pub fn matches(&self, event: &JobEvent) -> bool {
+        let job = &event.job;
+        self.project.as_ref().is_none_or(|p| *p == job.project)
+            && self.status.is_none_or(|s| s == job.status)
+            && self.name.as_deref().is_none_or(|pattern| {
+                job.name
+                    .as_deref()
+                    .is_some_and(|name| glob_match(pattern, name))
+            })
+    }
+}
+
+/// Match `text` against a glob where `*` is any run of characters and `?`
+/// any one character
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Where the last `*` was, and the text position it is matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some((p, t));
+            p += 1;
+        } else if let Some((star_p, star_t)) = star {
+            // Let the last `*` take one more character
+            p = star_p + 1;
+            t = star_t + 1;
+            star = Some((star_p, t));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(name: Option<&str>, status: Status) -> JobEvent {
+        let mut job = Job::new("ab12".into(), "make".into(), "/w/app".into(), "/w".into());
+        job.name = name.map(String::from);
+        job.status = status;
+        JobEvent::now(job)
+    }
+
+    #[test]
+    fn test_kind_for_status() {
+        assert_eq!(event(None, Status::Pending).kind, EventKind::Created);
+        assert_eq!(event(None, Status::Running).kind, EventKind::Started);
+        assert_eq!(event(None, Status::Failed).kind, EventKind::Finished);
+        assert_eq!(event(None, Status::Interrupted).kind, EventKind::Stopped);
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let build = event(Some("build-web"), Status::Completed);
+        assert!(EventFilter::default().matches(&build));
+        let by_name = |name: &str| EventFilter {
+            name: Some(name.into()),
+            ..EventFilter::default()
+        };
+        assert!(by_name("build-*").matches(&build));
+        assert!(by_name("build-w?b").matches(&build));
+        assert!(!by_name("build").matches(&build));
+        assert!(!by_name("*").matches(&event(None, Status::Completed)));
+
+        let filter = EventFilter {
+            project: Some("/w".into()),
+            status: Some(Status::Completed),
+            ..EventFilter::default()
+        };
+        assert!(filter.matches(&build));
+        assert!(!filter.matches(&event(Some("build-web"), Status::Running)));
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("svc-*-api", "svc-auth-api"));
+        assert!(glob_match("a*b*c", "aXbYbc"));
+        assert!(!glob_match("a*b", "aXbc"));
+        assert!(!glob_match("?", ""));
+    }
+}
@@ -0,0 +1,268 @@
+use std::collections::{BTreeMap, VecDeque};
+
+/// Severity a log line mentions, detected by keyword
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl LineLevel {
+    /// Case-insensitive keyword match; `error` wins over `warn`, over `info`, over `debug`.
+    #[must_use]
+    pub fn detect(line: &str) -> Option<Self> {
+        let lower = line.to_lowercase();
+        if lower.contains("error") || lower.contains("fatal") || lower.contains("panic") {
+            Some(Self::Error)
+        } else if lower.contains("warn") {
+            Some(Self::Warn)
+        } else if lower.contains("info") {
+            Some(Self::Info)
+        } else if lower.contains("debug") || lower.contains("trace") {
+            Some(Self::Debug)
+        } else {
+            None
+        }
+    }
+}
+
+/// Rough size of a token, for turning a token budget into bytes
+pub const BYTES_PER_TOKEN: usize = 4;
+
+/// One line of an excerpt
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExcerptLine {
+    /// A kept line with its 1-based line number
+    Line { number: usize, text: String },
+    /// This many consecutive lines were left out
+    Skipped(usize),
+}
+
+/// Builds a bounded excerpt of output read line by line: the head, the tail, and
+/// as many error (then warning) lines from between as fit. Memory stays within
+/// a small multiple of the budget however long the output is.
+pub struct Excerpt {
+    budget: usize,
+    lines: usize,
+    /// Every line, while the whole output still fits
+    all: Option<Vec<String>>,
+    all_bytes: usize,
+    head: Vec<(usize, String)>,
+    head_bytes: usize,
+    tail: VecDeque<(usize, String)>,
+    tail_bytes: usize,
+    errors: Marked,
+    warnings: Marked,
+}
+
+/// Marker lines collected up to the marker share of the budget
+#[derive(Default)]
+struct Marked {
+    lines: Vec<(usize, String)>,
+    bytes: usize,
+}
+
+impl Excerpt {
+    /// An excerpt of about `budget_tokens` tokens
+    #[must_use]
+    pub fn new(budget_tokens: usize) -> Self {
+        Self {
+            budget: budget_tokens.saturating_mul(BYTES_PER_TOKEN),
+            lines: 0,
+            all: Some(Vec::new()),
+            all_bytes: 0,
+            head: Vec::new(),
+            head_bytes: 0,
+            tail: VecDeque::new(),
+            tail_bytes: 0,
+            errors: Marked::default(),
+            warnings: Marked::default(),
+        }
+    }
+
+    /// Head and tail each get a quarter of the budget; marker lines the rest.
+    fn head_budget(&self) -> usize {
+        self.budget / 4
+    }
+
+    fn marker_budget(&self) -> usize {
+        self.budget - 2 * self.head_budget()
+    }
+
+    pub fn push(&mut self, line: &str) {
+        self.lines += 1;
+        let number = self.lines;
+
+        if let Some(all) = self.all.as_mut() {
+            self.all_bytes += line.len() + 1;
+            if self.all_bytes <= self.budget {
+                all.push(line.to_string());
+            } else {
+                self.all = None;
+            }
+        }
+
+        // A single huge line can take at most a share
+        let text = truncate(line, self.head_budget());
+        let size = text.len() + 1;
+
+        if self.head_bytes + size <= self.head_budget() && self.head.len() + 1 == number {
+            self.head.push((number, text.clone()));
+            self.head_bytes += size;
+        }
+
+        self.tail.push_back((number, text.clone()));
+        self.tail_bytes += size;
+        while self.tail_bytes > self.head_budget() {
+            let Some((_, dropped)) = self.tail.pop_front() else {
+                break;
+            };
+            self.tail_bytes -= dropped.len() + 1;
+        }
+
+        let marker_budget = self.marker_budget();
+        let marked = match LineLevel::detect(line) {
+            Some(LineLevel::Error) => &mut self.errors,
+            Some(LineLevel::Warn) => &mut self.warnings,
+            _ => return,
+        };
+        if marked.bytes + size <= marker_budget {
+            marked.lines.push((number, text));
+            marked.bytes += size;
+        }
+    }
+
+    /// The kept lines in order, with the gaps between them marked.
+    #[must_use]
+    pub fn finish(self) -> Vec<ExcerptLine> {
+        if let Some(all) = self.all {
+            return all
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| ExcerptLine::Line {
+                    number: i + 1,
+                    text,
+                })
+                .collect();
+        }
+
+        // Errors first, then warnings in whatever room is left
+        let mut room = self.marker_budget();
+        let mut kept: BTreeMap<usize, String> = BTreeMap::new();
+        kept.extend(self.head);
+        kept.extend(self.tail);
+        for (number, text) in self.errors.lines.into_iter().chain(self.warnings.lines) {
+            let size = text.len() + 1;
+            if kept.contains_key(&number) || size > room {
+                continue;
+            }
+            room -= size;
+            kept.insert(number, text);
+        }
+
+        let mut excerpt = Vec::with_capacity(kept.len() * 2 + 1);
+        let mut last = 0;
+        for (number, text) in kept {
+            if number > last + 1 {
+                excerpt.push(ExcerptLine::Skipped(number - last - 1));
+            }
+            excerpt.push(ExcerptLine::Line { number, text });
+            last = number;
+        }
+        if self.lines > last {
+            excerpt.push(ExcerptLine::Skipped(self.lines - last));
+        }
+        excerpt
+    }
+}
+
+/// Cut `line` to at most `max` bytes (on a character boundary), marking the cut.
+fn truncate(line: &str, max: usize) -> String {
+    if line.len() <= max {
+        return line.to_string();
+    }
+    let mut end = max;
+    while !line.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}…", &line[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn excerpt(lines: &[String], budget_tokens: usize) -> Vec<ExcerptLine> {
+        let mut excerpt = Excerpt::new(budget_tokens);
+        for line in lines {
+            excerpt.push(line);
+        }
+        excerpt.finish()
+    }
+
+    fn numbers(excerpt: &[ExcerptLine]) -> Vec<usize> {
+        excerpt
+            .iter()
+            .filter_map(|l| match l {
+                ExcerptLine::Line { number, .. } => Some(*number),
+                ExcerptLine::Skipped(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_detect_level() {
+        assert_eq!(LineLevel::detect("FATAL: crash"), Some(LineLevel::Error));
+        assert_eq!(
+            LineLevel::detect("warning about info"),
+            Some(LineLevel::Warn)
+        );
+        assert_eq!(LineLevel::detect("TRACE enter"), Some(LineLevel::Debug));
+        assert_eq!(LineLevel::detect("all good"), None);
+    }
+
+    #[test]
+    fn test_small_output_kept_whole() {
+        let lines: Vec<String> = (1..=5).map(|i| format!("line {i}")).collect();
+        let result = excerpt(&lines, 100);
+        assert_eq!(numbers(&result), vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_keeps_head_tail_and_errors() {
+        // 1000 lines of 9 bytes; 100 tokens = 400 bytes, a quarter is 100 bytes
+        let mut lines: Vec<String> = (1..=1000).map(|i| format!("line {i:04}")).collect();
+        lines[499] = "error: x".into();
+        lines[699] = "warn: y".into();
+        let result = excerpt(&lines, 100);
+
+        let kept = numbers(&result);
+        assert_eq!(&kept[..3], &[1, 2, 3]);
+        assert!(kept.contains(&500) && kept.contains(&700));
+        assert_eq!(kept.last(), Some(&1000));
+
+        let shown: usize = kept.len();
+        let skipped: usize = result
+            .iter()
+            .map(|l| match l {
+                ExcerptLine::Skipped(n) => *n,
+                ExcerptLine::Line { .. } => 0,
+            })
+            .sum();
+        assert_eq!(shown + skipped, 1000);
+    }
+
+    #[test]
+    fn test_long_lines_truncated() {
+        let lines = vec!["é".repeat(1000), "x".repeat(10_000)];
+        let result = excerpt(&lines, 100);
+        for line in &result {
+            if let ExcerptLine::Line { text, .. } = line {
+                assert!(text.len() <= 100 + '…'.len_utf8());
+                assert!(text.ends_with('…'));
+            }
+        }
+    }
+}
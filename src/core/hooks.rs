@@ -0,0 +1,125 @@
+use crate::core::Status;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Shell commands the daemon runs when a job finishes. Set per job with
+/// `jb run --on-success/--on-failure/--on-exit`, and for every job in the
+/// `[hooks]` section of config.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(schemars::JsonSchema))]
+#[serde(default, deny_unknown_fields)]
+pub struct Hooks {
+    /// After the job exits 0
+    pub on_success: Option<String>,
+    /// After the job exits non-zero or fails to start
+    pub on_failure: Option<String>,
+    /// After the job ends for any reason, including a stop or timeout
+    pub on_exit: Option<String>,
+}
+
+impl Hooks {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.on_success.is_none() && self.on_failure.is_none() && self.on_exit.is_none()
+    }
+
+    #[must_use]
+    pub fn get(&self, kind: HookKind) -> Option<&str> {
+        match kind {
+            HookKind::OnSuccess => self.on_success.as_deref(),
+            HookKind::OnFailure => self.on_failure.as_deref(),
+            HookKind::OnExit => self.on_exit.as_deref(),
+        }
+    }
+}
+
+/// Which hook ran
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HookKind {
+    OnSuccess,
+    OnFailure,
+    OnExit,
+}
+
+impl HookKind {
+    /// The hooks that apply to a job that ended with `status`, in the order
+    /// they run
+    #[must_use]
+    pub fn for_status(status: Status) -> Vec<Self> {
+        match status {
+            Status::Completed => vec![Self::OnSuccess, Self::OnExit],
+            Status::Failed => vec![Self::OnFailure, Self::OnExit],
+            Status::Stopped | Status::Interrupted => vec![Self::OnExit],
+            Status::Pending | Status::Running => Vec::new(),
+        }
+    }
+
+    #[must_use]
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::OnSuccess => "on_success",
+            Self::OnFailure => "on_failure",
+            Self::OnExit => "on_exit",
+        }
+    }
+}
+
+impl std::fmt::Display for HookKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl std::str::FromStr for HookKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "on_success" => Ok(Self::OnSuccess),
+            "on_failure" => Ok(Self::OnFailure),
+            "on_exit" => Ok(Self::OnExit),
+            _ => anyhow::bail!("unknown hook: {s}"),
+        }
+    }
+}
+
+/// A hook that ran for a job, as recorded in the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HookRun {
+    pub hook: HookKind,
+    pub command: String,
+    /// Set from config rather than on the job
+    pub global: bool,
+    pub started_at: DateTime<Utc>,
+    /// `None` if it was killed or couldn't be started
+    pub exit_code: Option<i32>,
+    /// The end of its stdout then stderr, or why it didn't run to completion
+    pub output: String,
+}
+
+impl HookRun {
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        self.exit_code == Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_for_status() {
+        assert_eq!(
+            HookKind::for_status(Status::Failed),
+            vec![HookKind::OnFailure, HookKind::OnExit]
+        );
+        assert_eq!(
+            HookKind::for_status(Status::Stopped),
+            vec![HookKind::OnExit]
+        );
+        assert!(HookKind::for_status(Status::Running).is_empty());
+        assert_eq!("on_exit".parse::<HookKind>().unwrap(), HookKind::OnExit);
+    }
+}
@@ -1,8 +1,37 @@
 use crate::core::Job;
+use crate::core::event::{EventFilter, JobEvent};
+use crate::core::hooks::Hooks;
+use crate::core::output::OutputChunk;
+use crate::core::webhook::Webhook;
 use serde::{Deserialize, Serialize};
 
+/// Version of the socket protocol, exchanged in the `Hello` handshake.
+/// Raised when a change would break a peer of the previous version.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Oldest protocol version this build can still speak
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
+/// Optional capabilities this build supports, announced in the handshake
+pub const FEATURES: &[&str] = &[
+    "error_codes",
+    "events",
+    "hooks",
+    "webhooks",
+    "notify",
+    "batch",
+];
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Request {
+    /// First frame of a connection: the client's protocol version and
+    /// features. The daemon answers `Hello` with the version both will speak,
+    /// or `Failed` with `unsupported_version`. Clients that skip it get the
+    /// version 0 replies (`Error` and `UserError` instead of `Failed`).
+    Hello {
+        version: u32,
+        features: Vec<String>,
+    },
     Run {
         command: String,
         name: Option<String>,
@@ -10,6 +39,21 @@ pub enum Request {
         project: String,
         timeout_secs: Option<u64>,
         idempotency_key: Option<String>,
+        log_max_size: Option<u64>,
+        log_keep: Option<u32>,
+        #[serde(default)]
+        hooks: Box<Hooks>,
+        #[serde(default)]
+        webhooks: Vec<Webhook>,
+        #[serde(default)]
+        notify: Option<bool>,
+    },
+    /// Start several jobs at once. They are checked first and created in one
+    /// transaction, so either all start or none do. The daemon answers
+    /// `Jobs` in the same order; an entry whose idempotency key was used
+    /// before gets the earlier job.
+    RunMany {
+        jobs: Vec<NewJob>,
     },
     Stop {
         id: String,
@@ -27,7 +71,55 @@ pub enum Request {
         timeout_secs: Option<u64>,
     },
     Ping,
-    Shutdown,
+    /// Stream a job's output from `from_offset` (a position in its whole output).
+    /// The daemon answers with `Output` frames as output arrives, then one
+    /// `Finished` frame once the job is terminal.
+    Subscribe {
+        id: String,
+        from_offset: u64,
+    },
+    /// Watch job lifecycle events. The daemon answers `Ok` once watching,
+    /// then sends an `Event` frame for each matching event until the client
+    /// hangs up.
+    Events {
+        filter: EventFilter,
+    },
+    /// Stop the daemon. With `keep_jobs`, running jobs are left alive instead of interrupted.
+    Shutdown {
+        #[serde(default)]
+        keep_jobs: bool,
+    },
+}
+
+impl Request {
+    /// Parse a request frame. Version 0 clients send `Shutdown` as a unit
+    /// variant (`"Shutdown"`), which is read as `Shutdown { keep_jobs: false }`.
+    pub fn from_json(data: &[u8]) -> serde_json::Result<Self> {
+        let value: serde_json::Value = serde_json::from_slice(data)?;
+        if value == "Shutdown" {
+            return Ok(Self::Shutdown { keep_jobs: false });
+        }
+        serde_json::from_value(value)
+    }
+}
+
+/// One job of a `RunMany`, with the fields of `Run`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NewJob {
+    pub command: String,
+    pub name: Option<String>,
+    pub cwd: String,
+    pub project: String,
+    pub timeout_secs: Option<u64>,
+    pub idempotency_key: Option<String>,
+    pub log_max_size: Option<u64>,
+    pub log_keep: Option<u32>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    #[serde(default)]
+    pub notify: Option<bool>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,8 +127,24 @@ pub enum Response {
     Job(Box<Job>),
     Jobs(Vec<Job>),
     Ok,
+    /// Output pushed to a subscriber
+    Output(Vec<OutputChunk>),
+    /// Last frame of a subscription: the job in its terminal state
+    Finished(Box<Job>),
+    /// Event pushed to a watcher
+    Event(Box<JobEvent>),
+    /// A request that failed, with a code saying why
+    Failed(ErrorReply),
+    /// Version 0 form of `Failed` for an internal error, sent to clients that
+    /// didn't handshake
     Error(String),
+    /// Version 0 form of `Failed` for any other error
     UserError(String),
+    /// Answer to `Hello`: the version the connection speaks and the daemon's features
+    Hello {
+        version: u32,
+        features: Vec<String>,
+    },
     Pong {
         pid: u32,
         uptime_secs: u64,
@@ -45,6 +153,118 @@ pub enum Response {
     },
 }
 
+impl Response {
+    /// This response as a peer speaking `version` understands it
+    #[must_use]
+    pub fn for_version(self, version: u32) -> Self {
+        match self {
+            Self::Failed(reply) if version == 0 => match reply.code {
+                ErrorCode::Internal => Self::Error(reply.message),
+                _ => Self::UserError(reply.message),
+            },
+            other => other,
+        }
+    }
+}
+
+/// Why a request failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// No job has the ID or name asked for
+    NotFound,
+    /// The job isn't running, so it can't be stopped
+    NotRunning,
+    /// A running job already has the name a new job asked for
+    NameInUse,
+    /// A wait's timeout passed before the job finished
+    Timeout,
+    /// The client's protocol version is too old or too new
+    UnsupportedVersion,
+    /// The request is invalid, e.g. a bad argument
+    Rejected,
+    /// The daemon failed to carry out the request
+    Internal,
+}
+
+/// The body of a `Failed` response
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReply {
+    pub code: ErrorCode,
+    /// For people; clients match on `code`
+    pub message: String,
+    /// The job concerned: its ID, or for `not_found` the ID or name asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub job: Option<String>,
+    /// For `name_in_use`, the name
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+impl ErrorReply {
+    #[must_use]
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            job: None,
+            name: None,
+        }
+    }
+
+    #[must_use]
+    pub fn not_found(id: &str) -> Self {
+        Self {
+            job: Some(id.to_string()),
+            ..Self::new(ErrorCode::NotFound, format!("Job not found: {id}"))
+        }
+    }
+
+    #[must_use]
+    pub fn not_running(id: &str) -> Self {
+        Self {
+            job: Some(id.to_string()),
+            ..Self::new(ErrorCode::NotRunning, format!("Job {id} is not running"))
+        }
+    }
+
+    #[must_use]
+    pub fn name_in_use(name: &str, job_id: &str) -> Self {
+        Self {
+            job: Some(job_id.to_string()),
+            name: Some(name.to_string()),
+            ..Self::new(
+                ErrorCode::NameInUse,
+                format!("Name '{name}' is in use by running job {job_id}"),
+            )
+        }
+    }
+
+    #[must_use]
+    pub fn timeout(id: &str) -> Self {
+        Self {
+            job: Some(id.to_string()),
+            ..Self::new(ErrorCode::Timeout, format!("Wait for job {id} timed out"))
+        }
+    }
+
+    #[must_use]
+    pub fn rejected(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Rejected, message)
+    }
+
+    #[must_use]
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+}
+
+impl From<ErrorReply> for Response {
+    fn from(reply: ErrorReply) -> Self {
+        Self::Failed(reply)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,9 +285,30 @@ mod tests {
             project: "/project".into(),
             timeout_secs: Some(30),
             idempotency_key: Some("key1".into()),
+            log_max_size: Some(1 << 20),
+            log_keep: Some(2),
+            hooks: Box::new(Hooks {
+                on_failure: Some("notify-send failed".into()),
+                ..Hooks::default()
+            }),
+            webhooks: vec![Webhook::new("http://localhost:9000/jb")],
+            notify: Some(true),
         });
     }
 
+    #[test]
+    fn test_job_reply_leaves_out_webhook_secrets() {
+        let webhook = Webhook {
+            secret: Some("s3cret".into()),
+            ..Webhook::new("http://localhost:9000/jb")
+        };
+        let job = Job::new("abc1".into(), "make".into(), "/tmp".into(), "/tmp".into())
+            .with_webhooks(vec![webhook]);
+        let json = serde_json::to_string(&Response::Job(Box::new(job))).unwrap();
+        assert!(json.contains("http://localhost:9000/jb"));
+        assert!(!json.contains("s3cret"));
+    }
+
     #[test]
     fn test_request_run_minimal_roundtrip() {
         roundtrip(&Request::Run {
@@ -77,6 +318,11 @@ mod tests {
             project: "/tmp".into(),
             timeout_secs: None,
             idempotency_key: None,
+            log_max_size: None,
+            log_keep: None,
+            hooks: Box::default(),
+            webhooks: Vec::new(),
+            notify: None,
         });
     }
 
@@ -122,9 +368,59 @@ mod tests {
         roundtrip(&Request::Ping);
     }
 
+    #[test]
+    fn test_request_subscribe_roundtrip() {
+        roundtrip(&Request::Subscribe {
+            id: "abc1".into(),
+            from_offset: 4096,
+        });
+    }
+
+    #[test]
+    fn test_events_roundtrip() {
+        roundtrip(&Request::Events {
+            filter: EventFilter {
+                project: Some("/p".into()),
+                name: Some("svc-*".into()),
+                status: Some(crate::core::Status::Failed),
+            },
+        });
+        let job = Job::new("abc1".into(), "make".into(), "/p".into(), "/p".into());
+        roundtrip(&Response::Event(Box::new(JobEvent::now(job))));
+    }
+
+    #[test]
+    fn test_response_output_roundtrip() {
+        roundtrip(&Response::Output(vec![OutputChunk {
+            stream: Some(crate::core::output::Stream::Stderr),
+            offset: 12,
+            at: chrono::DateTime::from_timestamp_millis(1_000),
+            data: b"oops\n".to_vec(),
+        }]));
+    }
+
     #[test]
     fn test_request_shutdown_roundtrip() {
-        roundtrip(&Request::Shutdown);
+        roundtrip(&Request::Shutdown { keep_jobs: false });
+        roundtrip(&Request::Shutdown { keep_jobs: true });
+    }
+
+    #[test]
+    fn test_version_zero_requests_parse() {
+        let parse = |json: &str| Request::from_json(json.as_bytes()).unwrap();
+        assert!(matches!(
+            parse(r#""Shutdown""#),
+            Request::Shutdown { keep_jobs: false }
+        ));
+        assert!(matches!(
+            parse(r#"{"Shutdown":{}}"#),
+            Request::Shutdown { keep_jobs: false }
+        ));
+        assert!(matches!(parse(r#""Ping""#), Request::Ping));
+        let run = parse(
+            r#"{"Run":{"command":"make","name":null,"cwd":"/p","project":"/p","timeout_secs":null,"idempotency_key":null}}"#,
+        );
+        assert!(matches!(run, Request::Run { command, .. } if command == "make"));
     }
 
     #[test]
@@ -142,6 +438,61 @@ mod tests {
         roundtrip(&Response::UserError("name in use".into()));
     }
 
+    #[test]
+    fn test_request_run_many_roundtrip() {
+        let job = NewJob {
+            command: "make test".into(),
+            name: Some("test".into()),
+            cwd: "/p".into(),
+            project: "/p".into(),
+            timeout_secs: None,
+            idempotency_key: Some("ci-42".into()),
+            log_max_size: None,
+            log_keep: None,
+            hooks: Hooks::default(),
+            webhooks: Vec::new(),
+            notify: Some(true),
+        };
+        roundtrip(&Request::RunMany {
+            jobs: vec![job.clone(), job],
+        });
+    }
+
+    #[test]
+    fn test_hello_roundtrip() {
+        let features: Vec<String> = FEATURES.iter().map(ToString::to_string).collect();
+        roundtrip(&Request::Hello {
+            version: PROTOCOL_VERSION,
+            features: features.clone(),
+        });
+        roundtrip(&Response::Hello {
+            version: PROTOCOL_VERSION,
+            features,
+        });
+    }
+
+    #[test]
+    fn test_failed_roundtrip_and_downgrade() {
+        let reply = ErrorReply::name_in_use("web", "x9k2");
+        roundtrip(&Response::Failed(reply.clone()));
+        let json = serde_json::to_value(Response::Failed(ErrorReply::not_found("ab"))).unwrap();
+        assert_eq!(json["Failed"]["code"], "not_found");
+        assert_eq!(json["Failed"]["job"], "ab");
+
+        assert!(matches!(
+            Response::Failed(reply.clone()).for_version(1),
+            Response::Failed(r) if r == reply
+        ));
+        assert!(matches!(
+            Response::Failed(reply).for_version(0),
+            Response::UserError(m) if m == "Name 'web' is in use by running job x9k2"
+        ));
+        assert!(matches!(
+            Response::Failed(ErrorReply::internal("disk full")).for_version(0),
+            Response::Error(m) if m == "disk full"
+        ));
+    }
+
     #[test]
     fn test_response_pong_roundtrip() {
         roundtrip(&Response::Pong {
@@ -1,8 +1,12 @@
+use crate::core::hooks::Hooks;
+use crate::core::output::LogLimit;
+use crate::core::webhook::{self, Webhook};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum Status {
     Pending,
@@ -58,6 +62,7 @@ impl std::str::FromStr for Status {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(schemars::JsonSchema))]
 pub struct Job {
     pub id: String,
     pub name: Option<String>,
@@ -72,6 +77,22 @@ pub struct Job {
     pub finished_at: Option<DateTime<Utc>>,
     pub timeout_secs: Option<u64>,
     pub idempotency_key: Option<String>,
+    /// Rotate the log past this many bytes
+    pub log_max_size: Option<u64>,
+    /// Rotated log segments to keep
+    pub log_keep: Option<u32>,
+    /// Commands to run when the job finishes
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// Endpoints to post the job to when it finishes. Their secrets stay in
+    /// the database: a serialized job is shown to clients and posted to the
+    /// webhooks themselves.
+    #[serde(default, serialize_with = "webhook::serialize_without_secrets")]
+    pub webhooks: Vec<Webhook>,
+    /// Show a desktop notification when it finishes; unset follows
+    /// `[notify] default` in config
+    #[serde(default)]
+    pub notify: Option<bool>,
 }
 
 impl Job {
@@ -91,6 +112,11 @@ impl Job {
             finished_at: None,
             timeout_secs: None,
             idempotency_key: None,
+            log_max_size: None,
+            log_keep: None,
+            hooks: Hooks::default(),
+            webhooks: Vec::new(),
+            notify: None,
         }
     }
 
@@ -112,6 +138,36 @@ impl Job {
         self
     }
 
+    #[must_use]
+    pub fn with_log_limit(mut self, limit: LogLimit) -> Self {
+        self.log_max_size = Some(limit.max_bytes);
+        self.log_keep = Some(limit.keep);
+        self
+    }
+
+    #[must_use]
+    pub fn with_hooks(mut self, hooks: Hooks) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    #[must_use]
+    pub fn with_webhooks(mut self, webhooks: Vec<Webhook>) -> Self {
+        self.webhooks = webhooks;
+        self
+    }
+
+    #[must_use]
+    pub fn with_notify(mut self, notify: Option<bool>) -> Self {
+        self.notify = notify;
+        self
+    }
+
+    #[must_use]
+    pub fn log_limit(&self) -> Option<LogLimit> {
+        LogLimit::from_settings(self.log_max_size, self.log_keep)
+    }
+
     #[must_use]
     pub fn short_id(&self) -> &str {
         &self.id
@@ -1,10 +1,18 @@
+pub mod asciicast;
+pub mod config;
 pub mod db;
 pub mod error;
+pub mod event;
+pub mod excerpt;
+pub mod hooks;
 pub mod ipc;
 pub mod job;
+pub mod output;
 pub mod paths;
 pub mod project;
+pub mod webhook;
 
+pub use config::Config;
 pub use db::Database;
 pub use error::UserError;
 pub use job::{Job, Status};
@@ -57,6 +65,60 @@ pub fn parse_duration(s: &str) -> anyhow::Result<u64> {
     Ok(n * unit)
 }
 
+/// Parse a byte size such as "512", "64K", "100M" or "2GB" (binary units).
+pub fn parse_size(s: &str) -> anyhow::Result<u64> {
+    let s = s.trim();
+    let upper = s.to_ascii_uppercase();
+    let digits = upper.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match upper[digits.len()..].trim_end_matches('B') {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        _ => anyhow::bail!("Invalid size '{s}'. Use: 512K, 100M, 2G"),
+    };
+    let n: u64 = digits
+        .trim()
+        .parse()
+        .map_err(|_| anyhow::anyhow!("Invalid size '{s}'. Use: 512K, 100M, 2G"))?;
+    n.checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("Size '{s}' is too large"))
+}
+
+/// Parse a point in time: either a duration ago ("5m", "2h") or an RFC 3339
+/// timestamp ("2026-01-02T15:04:05Z").
+pub fn parse_timestamp(s: &str) -> anyhow::Result<chrono::DateTime<chrono::Utc>> {
+    let s = s.trim();
+    if let Ok(secs) = parse_duration(s) {
+        let ago = chrono::Duration::seconds(i64::try_from(secs)?);
+        return Ok(chrono::Utc::now() - ago);
+    }
+    chrono::DateTime::parse_from_rfc3339(s)
+        .map(|t| t.with_timezone(&chrono::Utc))
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Invalid time '{s}'. Use a duration ago (30s, 5m, 1h, 7d) or RFC 3339 (2026-01-02T15:04:05Z)"
+            )
+        })
+}
+
+/// Format a byte count for display, e.g. "512 B", "1.5 KB", "20.0 MB"
+#[must_use]
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KB", "MB", "GB", "TB"];
+    if bytes < 1024 {
+        return format!("{bytes} B");
+    }
+    #[allow(clippy::cast_precision_loss)] // display only
+    let mut value = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -102,4 +164,37 @@ mod tests {
     fn test_parse_duration_invalid_number() {
         assert!(parse_duration("abcs").is_err());
     }
+
+    #[test]
+    fn test_parse_timestamp_duration_ago() {
+        let t = parse_timestamp("5m").unwrap();
+        let ago = chrono::Utc::now() - t;
+        assert!(ago >= chrono::Duration::seconds(299) && ago <= chrono::Duration::seconds(301));
+    }
+
+    #[test]
+    fn test_parse_timestamp_rfc3339() {
+        let t = parse_timestamp("2026-01-02T16:04:05+01:00").unwrap();
+        assert_eq!(t.to_rfc3339(), "2026-01-02T15:04:05+00:00");
+        assert!(parse_timestamp("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64K").unwrap(), 64 * 1024);
+        assert_eq!(parse_size("100M").unwrap(), 100 * 1024 * 1024);
+        assert_eq!(parse_size("2gb").unwrap(), 2 * 1024 * 1024 * 1024);
+        assert!(parse_size("10X").is_err());
+        assert!(parse_size("M").is_err());
+    }
+
+    #[test]
+    fn test_format_size() {
+        assert_eq!(format_size(0), "0 B");
+        assert_eq!(format_size(512), "512 B");
+        assert_eq!(format_size(1536), "1.5 KB");
+        assert_eq!(format_size(20 * 1024 * 1024), "20.0 MB");
+        assert_eq!(format_size(3 * 1024 * 1024 * 1024), "3.0 GB");
+    }
 }
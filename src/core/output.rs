@@ -0,0 +1,1570 @@
+use crate::core::Paths;
+use crate::core::paths::rotated_path;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use flate2::read::GzDecoder;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    #[must_use]
+    pub fn tag(self) -> char {
+        match self {
+            Self::Stdout => 'o',
+            Self::Stderr => 'e',
+        }
+    }
+
+    #[must_use]
+    pub fn from_tag(tag: &str) -> Option<Self> {
+        match tag {
+            "o" => Some(Self::Stdout),
+            "e" => Some(Self::Stderr),
+            _ => None,
+        }
+    }
+}
+
+/// One chunk of output as read from a pipe.
+///
+/// The daemon appends every chunk from the job's stdout and stderr pipes, in arrival
+/// order, to the combined log `<id>.log`, and one record per chunk to the sidecar
+/// index `<id>.idx`:
+///
+/// ```text
+/// <stream> <offset> <len> <unix_millis>
+/// ```
+///
+/// `<stream>` is `o` (stdout) or `e` (stderr); `<offset>`/`<len>` locate the chunk in
+/// the combined log; `<unix_millis>` is when the daemon received it. The log alone is
+/// the interleaved view; the index recovers either stream exactly, and when each
+/// line was produced.
+///
+/// With a size limit the log is split into [`Segment`]s. Offsets are relative to the
+/// segment's own log file. Each segment's index starts with a `g <generation> <base>`
+/// record: the generation numbers the segments of the job from 0, and the base is
+/// where the segment starts in the job's whole output, so positions survive rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    pub stream: Stream,
+    pub offset: u64,
+    pub len: u64,
+    /// Receive time (absent in indexes written before timestamps were recorded)
+    pub at: Option<DateTime<Utc>>,
+}
+
+impl Chunk {
+    #[must_use]
+    pub fn end(self) -> u64 {
+        self.offset + self.len
+    }
+
+    /// Encode as an index record, including the trailing newline.
+    #[must_use]
+    pub fn to_index_line(self) -> String {
+        match self.at {
+            Some(at) => format!(
+                "{} {} {} {}\n",
+                self.stream.tag(),
+                self.offset,
+                self.len,
+                at.timestamp_millis()
+            ),
+            None => format!("{} {} {}\n", self.stream.tag(), self.offset, self.len),
+        }
+    }
+
+    /// Decode an index record. Returns `None` for malformed lines
+    /// (e.g. a record the daemon is still writing).
+    #[must_use]
+    pub fn parse_index_line(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let stream = Stream::from_tag(fields.next()?)?;
+        let offset = fields.next()?.parse().ok()?;
+        let len = fields.next()?.parse().ok()?;
+        let at = match fields.next() {
+            Some(ms) => Some(DateTime::from_timestamp_millis(ms.parse().ok()?)?),
+            None => None,
+        };
+        Some(Self {
+            stream,
+            offset,
+            len,
+            at,
+        })
+    }
+}
+
+/// Default number of rotated segments kept when only a size limit is given
+pub const DEFAULT_LOG_KEEP: u32 = 3;
+
+/// Size cap for a job's log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogLimit {
+    /// Start a new segment once the active one would grow past this
+    pub max_bytes: u64,
+    /// Rotated segments to keep; 0 discards old output on rotation
+    pub keep: u32,
+}
+
+impl LogLimit {
+    /// A limit from job settings; `keep` alone doesn't cap anything.
+    #[must_use]
+    pub fn from_settings(max_bytes: Option<u64>, keep: Option<u32>) -> Option<Self> {
+        max_bytes.map(|max_bytes| Self {
+            max_bytes,
+            keep: keep.unwrap_or(DEFAULT_LOG_KEEP),
+        })
+    }
+}
+
+/// The log and index files of one segment of a job's output.
+///
+/// The active segment is `<id>.log`/`<id>.idx`. Rotation renames it to
+/// `<id>.log.1`/`<id>.idx.1`, shifting older segments up by one. Once the job
+/// has finished, the daemon may replace a log with its gzip `<log>.gz`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub log: PathBuf,
+    pub index: PathBuf,
+}
+
+impl Segment {
+    #[must_use]
+    pub fn active(paths: &Paths, id: &str) -> Self {
+        Self {
+            log: paths.log_file(id),
+            index: paths.index_file(id),
+        }
+    }
+
+    /// The `n`th most recent rotated segment (1 = newest)
+    #[must_use]
+    pub fn rotated(&self, n: u32) -> Self {
+        Self {
+            log: rotated_path(&self.log, n as usize),
+            index: rotated_path(&self.index, n as usize),
+        }
+    }
+
+    /// Where the log lives once compressed
+    #[must_use]
+    pub fn compressed_log(&self) -> PathBuf {
+        let mut s = self.log.as_os_str().to_owned();
+        s.push(".gz");
+        PathBuf::from(s)
+    }
+
+    /// Whether the segment's log exists, plain or compressed
+    #[must_use]
+    pub fn exists(&self) -> bool {
+        self.log.exists() || self.compressed_log().exists()
+    }
+
+    /// The segments of this (active) segment's job that exist on disk, oldest first.
+    #[must_use]
+    pub fn existing(&self) -> Vec<Self> {
+        let mut segments: Vec<Self> = (1..)
+            .map(|n| self.rotated(n))
+            .take_while(Self::exists)
+            .collect();
+        segments.reverse();
+        if self.exists() {
+            segments.push(self.clone());
+        }
+        segments
+    }
+}
+
+/// A segment's log file, plain or gzip-compressed. Compressed logs are read
+/// front to back, which is how the index lists chunks.
+pub enum SegmentLog {
+    Plain(File),
+    Compressed {
+        reader: Box<GzDecoder<BufReader<File>>>,
+        /// Decompressed bytes consumed so far
+        pos: u64,
+    },
+}
+
+impl SegmentLog {
+    /// Open the plain log, or its compressed replacement.
+    pub fn open(segment: &Segment) -> std::io::Result<Self> {
+        match File::open(&segment.log) {
+            Ok(file) => Ok(Self::Plain(file)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let file = File::open(segment.compressed_log()).map_err(|_| e)?;
+                Ok(Self::Compressed {
+                    reader: Box::new(GzDecoder::new(BufReader::new(file))),
+                    pos: 0,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Move to `offset`. A compressed log can only move forward.
+    fn seek_to(&mut self, offset: u64) -> std::io::Result<()> {
+        match self {
+            Self::Plain(file) => {
+                file.seek(SeekFrom::Start(offset))?;
+            }
+            Self::Compressed { reader, pos } => {
+                if offset < *pos {
+                    return Err(std::io::Error::other(
+                        "cannot seek back in a compressed log",
+                    ));
+                }
+                *pos += std::io::copy(&mut reader.take(offset - *pos), &mut std::io::sink())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a chunk's bytes. Short if the log was cut off.
+    fn read_chunk(&mut self, chunk: Chunk) -> std::io::Result<Vec<u8>> {
+        self.seek_to(chunk.offset)?;
+        let mut data = Vec::new();
+        self.take(chunk.len).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Append everything from `offset` to the end of the log to `out`.
+    fn read_from(&mut self, offset: u64, out: &mut Vec<u8>) -> std::io::Result<usize> {
+        self.seek_to(offset)?;
+        self.read_to_end(out)
+    }
+
+    /// The file on disk (the compressed one, if compressed)
+    fn file(&self) -> &File {
+        match self {
+            Self::Plain(file) => file,
+            Self::Compressed { reader, .. } => reader.get_ref().get_ref(),
+        }
+    }
+
+    fn is_compressed(&self) -> bool {
+        matches!(self, Self::Compressed { .. })
+    }
+}
+
+impl Read for SegmentLog {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(file) => file.read(buf),
+            Self::Compressed { reader, pos } => {
+                let n = reader.read(buf)?;
+                *pos += n as u64;
+                Ok(n)
+            }
+        }
+    }
+}
+
+/// Open a job's combined log across all of its segments.
+pub fn open_log(active: &Segment) -> Result<Box<dyn Read>> {
+    let mut reader: Box<dyn Read> = Box::new(std::io::empty());
+    for segment in active.existing() {
+        reader = Box::new(reader.chain(SegmentLog::open(&segment)?));
+    }
+    Ok(reader)
+}
+
+/// Most output indexed for full-text search per job
+pub const INDEX_MAX_BYTES: usize = 8 << 20;
+
+/// A job's output as text for the search index: all of it, or for longer output
+/// the last `INDEX_MAX_BYTES`, where failures usually are.
+pub fn read_indexable(active: &Segment) -> Result<String> {
+    let mut reader = open_log(active)?;
+    let mut text = Vec::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        text.extend_from_slice(&buf[..n]);
+        if text.len() > 2 * INDEX_MAX_BYTES {
+            text.drain(..text.len() - INDEX_MAX_BYTES);
+        }
+    }
+    if text.len() > INDEX_MAX_BYTES {
+        text.drain(..text.len() - INDEX_MAX_BYTES);
+    }
+    Ok(String::from_utf8_lossy(&text).into_owned())
+}
+
+/// Size of a job's retained log: as written, and as stored on disk
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogSize {
+    pub bytes: u64,
+    pub stored_bytes: u64,
+    /// Whether any segment is compressed
+    pub compressed: bool,
+}
+
+impl LogSize {
+    pub fn measure(active: &Segment) -> Result<Self> {
+        let mut size = Self::default();
+        for segment in active.existing() {
+            if let Ok(meta) = std::fs::metadata(&segment.log) {
+                size.bytes += meta.len();
+                size.stored_bytes += meta.len();
+                continue;
+            }
+            let gz_path = segment.compressed_log();
+            size.compressed = true;
+            size.stored_bytes += std::fs::metadata(&gz_path)?.len();
+            size.bytes += match read_index(&segment.index)? {
+                Some(index) => index.chunks.last().map_or(0, |c| c.end()),
+                None => gzip_original_size(&gz_path)?,
+            };
+        }
+        Ok(size)
+    }
+}
+
+/// Uncompressed size recorded in a gzip trailer (modulo 4 GiB)
+fn gzip_original_size(path: &Path) -> Result<u64> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::End(-4))?;
+    let mut trailer = [0u8; 4];
+    file.read_exact(&mut trailer)?;
+    Ok(u64::from(u32::from_le_bytes(trailer)))
+}
+
+/// Encode the record that starts each segment's index.
+#[must_use]
+pub fn generation_line(generation: u64, base: u64) -> String {
+    format!("g {generation} {base}\n")
+}
+
+/// Decode a `g <generation> [<base>]` record into `(generation, base)`.
+fn parse_generation_line(line: &str) -> Option<(u64, u64)> {
+    let mut fields = line.strip_prefix("g ")?.split_whitespace();
+    let generation = fields.next()?.parse().ok()?;
+    let base = match fields.next() {
+        Some(base) => base.parse().ok()?,
+        None => 0,
+    };
+    Some((generation, base))
+}
+
+/// The parsed index of one segment
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SegmentIndex {
+    pub generation: u64,
+    /// Offset of the segment's first byte in the job's whole output
+    pub base: u64,
+    pub chunks: Vec<Chunk>,
+}
+
+/// Read all complete records of an index file.
+/// Returns `None` if the job has no index (captured by an older jb).
+pub fn read_index(path: &Path) -> Result<Option<SegmentIndex>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut content = String::new();
+    File::open(path)?.read_to_string(&mut content)?;
+    // A record without its newline may still be mid-write
+    let complete = content.rfind('\n').map_or(0, |i| i + 1);
+    let mut index = SegmentIndex::default();
+    for line in content[..complete].lines() {
+        if let Some(chunk) = Chunk::parse_index_line(line) {
+            index.chunks.push(chunk);
+        } else if let Some((generation, base)) = parse_generation_line(line) {
+            index.generation = generation;
+            index.base = base;
+        }
+    }
+    Ok(Some(index))
+}
+
+/// A job's indexed output across all of its segments
+pub struct IndexedOutput {
+    segments: Vec<(Segment, SegmentIndex)>,
+}
+
+impl IndexedOutput {
+    /// Load the indexes of every segment. Returns `None` if there is no output
+    /// or some segment has no index.
+    pub fn load(active: &Segment) -> Result<Option<Self>> {
+        let mut segments = Vec::new();
+        for segment in active.existing() {
+            let Some(index) = read_index(&segment.index)? else {
+                return Ok(None);
+            };
+            segments.push((segment, index));
+        }
+        Ok((!segments.is_empty()).then_some(Self { segments }))
+    }
+
+    pub fn chunks(&self) -> impl Iterator<Item = &Chunk> {
+        self.segments.iter().flat_map(|(_, index)| &index.chunks)
+    }
+
+    /// The chunks of `stream` (default: all) in order, with their bytes.
+    pub fn read_chunks(
+        self,
+        stream: Option<Stream>,
+    ) -> impl Iterator<Item = std::io::Result<(Chunk, Vec<u8>)>> {
+        let mut reader = ChunkReader::new(self, stream);
+        std::iter::from_fn(move || reader.next_chunk().transpose())
+    }
+}
+
+/// A position in a job's output that stays meaningful across rotation: an offset
+/// in the whole output plus the generation of the segment it falls in.
+/// Written as `<generation>:<offset>`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub generation: u64,
+    pub offset: u64,
+}
+
+impl std::fmt::Display for Cursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.generation, self.offset)
+    }
+}
+
+impl std::str::FromStr for Cursor {
+    type Err = String;
+
+    /// Parse `<generation>:<offset>`; a bare offset is in generation 0.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let (generation, offset) = s.split_once(':').unwrap_or(("0", s));
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u64>()
+                .map_err(|_| "expected <generation>:<offset>".to_string())
+        };
+        Ok(Self {
+            generation: parse(generation)?,
+            offset: parse(offset)?,
+        })
+    }
+}
+
+/// Output read after a cursor
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct CursorRead {
+    pub data: Vec<u8>,
+    /// Where the next read continues
+    pub cursor: Cursor,
+    /// Bytes after the old cursor that were rotated away before this read
+    pub skipped: u64,
+    /// The cursor didn't fit the log (truncated or replaced since), so the read
+    /// started over from the oldest retained output
+    pub reset: bool,
+    /// The read stopped at its byte limit; more output follows the cursor
+    pub truncated: bool,
+}
+
+impl CursorRead {
+    /// Read the indexed output after `cursor`, only `stream` if given, and at most
+    /// `max_bytes`. The cursor is checked against the segment headers, so rotation
+    /// is detected exactly.
+    pub fn from_index(
+        output: &IndexedOutput,
+        cursor: Cursor,
+        stream: Option<Stream>,
+        max_bytes: Option<usize>,
+    ) -> Result<Self> {
+        // (generation, base, end) of each segment, oldest first
+        let spans: Vec<(u64, u64, u64)> = output
+            .segments
+            .iter()
+            .map(|(_, index)| {
+                let len = index.chunks.last().map_or(0, |c| c.end());
+                (index.generation, index.base, index.base + len)
+            })
+            .collect();
+        let Some(&(oldest_generation, oldest_base, _)) = spans.first() else {
+            return Ok(Self {
+                cursor,
+                ..Self::default()
+            });
+        };
+
+        let mut read = Self::default();
+        let start = match spans.iter().find(|s| s.0 == cursor.generation) {
+            Some(&(_, base, end)) if (base..=end).contains(&cursor.offset) => cursor.offset,
+            None if cursor.generation < oldest_generation && cursor.offset <= oldest_base => {
+                read.skipped = oldest_base - cursor.offset;
+                oldest_base
+            }
+            _ => {
+                read.reset = true;
+                oldest_base
+            }
+        };
+        let generation = spans
+            .iter()
+            .rev()
+            .find(|s| s.1 <= start)
+            .map_or(oldest_generation, |s| s.0);
+        read.cursor = Cursor {
+            generation,
+            offset: start,
+        };
+
+        let max_bytes = max_bytes.unwrap_or(usize::MAX);
+        'segments: for ((segment, index), &(generation, base, end)) in
+            output.segments.iter().zip(&spans)
+        {
+            if end <= start {
+                continue;
+            }
+            let mut log = SegmentLog::open(segment)?;
+            for &chunk in &index.chunks {
+                if base + chunk.end() <= start {
+                    continue;
+                }
+                if read.data.len() >= max_bytes {
+                    read.truncated = true;
+                    break 'segments;
+                }
+                if stream.is_none_or(|s| s == chunk.stream) {
+                    let mut data = OutputChunk {
+                        stream: Some(chunk.stream),
+                        offset: base + chunk.offset,
+                        at: chunk.at,
+                        data: log.read_chunk(chunk)?,
+                    };
+                    data.skip_to(start);
+                    let room = max_bytes - read.data.len();
+                    if data.data.len() > room {
+                        read.data.extend_from_slice(&data.data[..room]);
+                        read.cursor = Cursor {
+                            generation,
+                            offset: data.offset + room as u64,
+                        };
+                        read.truncated = true;
+                        break 'segments;
+                    }
+                    read.data.extend_from_slice(&data.data);
+                }
+                read.cursor = Cursor {
+                    generation,
+                    offset: base + chunk.end(),
+                };
+            }
+        }
+        Ok(read)
+    }
+
+    /// Read a log captured without an index (a single segment) after `cursor`,
+    /// at most `max_bytes`.
+    pub fn from_log(active: &Segment, cursor: Cursor, max_bytes: Option<usize>) -> Result<Self> {
+        let mut data = Vec::new();
+        open_log(active)?.read_to_end(&mut data)?;
+        let mut read = Self::default();
+        #[allow(clippy::cast_possible_truncation)] // bounded by data.len()
+        let start = if cursor.generation == 0 && cursor.offset <= data.len() as u64 {
+            cursor.offset as usize
+        } else {
+            read.reset = true;
+            0
+        };
+        read.data = data.split_off(start);
+        if let Some(max) = max_bytes
+            && read.data.len() > max
+        {
+            read.data.truncate(max);
+            read.truncated = true;
+        }
+        read.cursor = Cursor {
+            generation: 0,
+            offset: (start + read.data.len()) as u64,
+        };
+        Ok(read)
+    }
+}
+
+/// Bytes captured per stream
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StreamBytes {
+    pub stdout: u64,
+    pub stderr: u64,
+}
+
+impl StreamBytes {
+    #[must_use]
+    pub fn from_chunks<'a>(chunks: impl IntoIterator<Item = &'a Chunk>) -> Self {
+        chunks.into_iter().fold(Self::default(), |mut acc, c| {
+            match c.stream {
+                Stream::Stdout => acc.stdout += c.len,
+                Stream::Stderr => acc.stderr += c.len,
+            }
+            acc
+        })
+    }
+}
+
+/// Reads the chunks of an indexed output in order, segment by segment.
+struct ChunkReader {
+    segments: std::vec::IntoIter<(Segment, SegmentIndex)>,
+    current: Option<(SegmentLog, std::vec::IntoIter<Chunk>)>,
+    stream: Option<Stream>,
+}
+
+impl ChunkReader {
+    fn new(output: IndexedOutput, stream: Option<Stream>) -> Self {
+        Self {
+            segments: output.segments.into_iter(),
+            current: None,
+            stream,
+        }
+    }
+
+    /// The next chunk of the selected stream with its bytes
+    fn next_chunk(&mut self) -> std::io::Result<Option<(Chunk, Vec<u8>)>> {
+        loop {
+            if let Some((log, chunks)) = self.current.as_mut() {
+                match chunks.next() {
+                    Some(chunk) if self.stream.is_some_and(|s| chunk.stream != s) => continue,
+                    Some(chunk) => return log.read_chunk(chunk).map(|data| Some((chunk, data))),
+                    None => self.current = None,
+                }
+            }
+            let Some((segment, index)) = self.segments.next() else {
+                return Ok(None);
+            };
+            self.current = Some((SegmentLog::open(&segment)?, index.chunks.into_iter()));
+        }
+    }
+}
+
+/// Reads only the chunks of one stream from the combined log, in order.
+pub struct StreamReader {
+    chunks: ChunkReader,
+    /// Current chunk and how much of it has been read
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl StreamReader {
+    #[must_use]
+    pub fn new(output: IndexedOutput, stream: Stream) -> Self {
+        Self {
+            chunks: ChunkReader::new(output, Some(stream)),
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl Read for StreamReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos == self.buf.len() {
+            let Some((_, data)) = self.chunks.next_chunk()? else {
+                return Ok(0);
+            };
+            self.buf = data;
+            self.pos = 0;
+        }
+        let n = buf.len().min(self.buf.len() - self.pos);
+        buf[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A line of output with the receive time of the chunk it started in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimedLine {
+    pub at: Option<DateTime<Utc>>,
+    pub text: String,
+}
+
+/// Splits chunked output into lines, remembering when each line started.
+#[derive(Default)]
+pub struct LineSplitter {
+    line: Vec<u8>,
+    line_at: Option<DateTime<Utc>>,
+    started: bool,
+}
+
+impl LineSplitter {
+    /// Feed a chunk received at `at`; completed lines are appended to `out`.
+    pub fn push(&mut self, data: &[u8], at: Option<DateTime<Utc>>, out: &mut Vec<TimedLine>) {
+        for part in data.split_inclusive(|&b| b == b'\n') {
+            if !self.started {
+                self.line_at = at;
+                self.started = true;
+            }
+            match part.strip_suffix(b"\n") {
+                Some(rest) => {
+                    self.line.extend_from_slice(rest);
+                    out.push(self.take());
+                }
+                None => self.line.extend_from_slice(part),
+            }
+        }
+    }
+
+    /// The trailing line without a newline, if any.
+    pub fn finish(&mut self) -> Option<TimedLine> {
+        self.started.then(|| self.take())
+    }
+
+    fn take(&mut self) -> TimedLine {
+        self.started = false;
+        TimedLine {
+            at: self.line_at,
+            text: String::from_utf8_lossy(&std::mem::take(&mut self.line)).into_owned(),
+        }
+    }
+}
+
+/// Iterates the lines of a job's output with their timestamps,
+/// optionally restricted to one stream.
+pub struct TimedLines {
+    chunks: ChunkReader,
+    splitter: LineSplitter,
+    ready: VecDeque<TimedLine>,
+    done: bool,
+}
+
+impl TimedLines {
+    #[must_use]
+    pub fn new(output: IndexedOutput, stream: Option<Stream>) -> Self {
+        Self {
+            chunks: ChunkReader::new(output, stream),
+            splitter: LineSplitter::default(),
+            ready: VecDeque::new(),
+            done: false,
+        }
+    }
+}
+
+impl Iterator for TimedLines {
+    type Item = std::io::Result<TimedLine>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.ready.pop_front() {
+                return Some(Ok(line));
+            }
+            if self.done {
+                return None;
+            }
+            match self.chunks.next_chunk() {
+                Ok(Some((chunk, data))) => {
+                    let mut lines = Vec::new();
+                    self.splitter.push(&data, chunk.at, &mut lines);
+                    self.ready.extend(lines);
+                }
+                Ok(None) => {
+                    self.done = true;
+                    return self.splitter.finish().map(Ok);
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+/// Output read back from a job's log, positioned in the job's whole output.
+/// This is also what the daemon pushes to log subscribers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutputChunk {
+    /// Stream the bytes came from; `None` when read without the index
+    pub stream: Option<Stream>,
+    /// Offset from the start of the job's output, across rotations
+    pub offset: u64,
+    /// Receive time, if the index recorded one
+    pub at: Option<DateTime<Utc>>,
+    pub data: Vec<u8>,
+}
+
+impl OutputChunk {
+    #[must_use]
+    pub fn end(&self) -> u64 {
+        self.offset + self.data.len() as u64
+    }
+
+    /// Drop any bytes before `offset`. Returns `false` if nothing is left.
+    pub fn skip_to(&mut self, offset: u64) -> bool {
+        if offset >= self.end() {
+            return false;
+        }
+        if offset > self.offset {
+            #[allow(clippy::cast_possible_truncation)] // less than data.len()
+            self.data.drain(..(offset - self.offset) as usize);
+            self.offset = offset;
+        }
+        true
+    }
+}
+
+/// Drops output that was delivered before a resume point. Only the first chunks
+/// are filtered: offsets start over if the log is truncated in place.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResumeFilter {
+    from: Option<u64>,
+}
+
+impl ResumeFilter {
+    #[must_use]
+    pub fn new(from_offset: u64) -> Self {
+        Self {
+            from: (from_offset > 0).then_some(from_offset),
+        }
+    }
+
+    /// The part of `chunk` not yet delivered, if any.
+    pub fn apply(&mut self, mut chunk: OutputChunk) -> Option<OutputChunk> {
+        if let Some(from) = self.from {
+            if !chunk.skip_to(from) {
+                return None;
+            }
+            self.from = None;
+        }
+        Some(chunk)
+    }
+}
+
+/// Incrementally reads new output of a running job, across log rotation.
+pub struct OutputTail {
+    active: Segment,
+    stream: Option<Stream>,
+    indexed: bool,
+    /// Open segments still to be read, oldest first; the last one is the
+    /// active segment as of the last check
+    segments: VecDeque<TailSegment>,
+    /// Bytes read so far by a raw tail, counted from the oldest segment it saw
+    raw_offset: u64,
+}
+
+struct TailSegment {
+    log: SegmentLog,
+    log_pos: u64,
+    index: Option<IndexTail>,
+}
+
+struct IndexTail {
+    file: File,
+    pos: u64,
+    partial: String,
+    /// From the segment's generation record
+    base: u64,
+}
+
+impl OutputTail {
+    /// Tail the combined log, or only `stream` (read through the index).
+    /// Starts at the oldest retained segment. Fails if the files don't exist yet.
+    pub fn open(active: Segment, stream: Option<Stream>) -> Result<Self> {
+        Self::open_with(active, stream, stream.is_some())
+    }
+
+    /// Tail through the index so chunk metadata (stream, timestamp) is available.
+    pub fn open_indexed(active: Segment, stream: Option<Stream>) -> Result<Self> {
+        Self::open_with(active, stream, true)
+    }
+
+    fn open_with(active: Segment, stream: Option<Stream>, indexed: bool) -> Result<Self> {
+        let mut segments = VecDeque::new();
+        for segment in active.existing() {
+            segments.push_back(TailSegment::open(&segment, indexed)?);
+        }
+        if segments.is_empty() {
+            // Nothing on disk yet: surface the usual not-found error
+            segments.push_back(TailSegment::open(&active, indexed)?);
+        }
+        Ok(Self {
+            active,
+            stream,
+            indexed,
+            segments,
+            raw_offset: 0,
+        })
+    }
+
+    /// Append whatever output arrived since the last call to `out`.
+    /// Returns the number of bytes appended.
+    pub fn read_new(&mut self, out: &mut Vec<u8>) -> Result<usize> {
+        let start = out.len();
+        if self.indexed {
+            for chunk in self.read_new_chunks()? {
+                out.extend_from_slice(&chunk.data);
+            }
+            return Ok(out.len() - start);
+        }
+        loop {
+            let segment = self.segments.front_mut().expect("tail has a segment");
+            let n = segment.log.read_from(segment.log_pos, out)? as u64;
+            segment.log_pos += n;
+            self.raw_offset += n;
+            if !self.advance()? {
+                return Ok(out.len() - start);
+            }
+        }
+    }
+
+    /// New chunks (of the selected stream) since the last call, with their bytes.
+    /// Only available on an indexed tail; returns nothing otherwise.
+    pub fn read_new_chunks(&mut self) -> Result<Vec<OutputChunk>> {
+        let mut chunks = Vec::new();
+        if !self.indexed {
+            return Ok(chunks);
+        }
+        loop {
+            let segment = self.segments.front_mut().expect("tail has a segment");
+            let index = segment.index.as_mut().expect("indexed tail");
+            for chunk in index.read_new_chunks()? {
+                if self.stream.is_some_and(|s| chunk.stream != s) {
+                    continue;
+                }
+                chunks.push(OutputChunk {
+                    stream: Some(chunk.stream),
+                    offset: index.base + chunk.offset,
+                    at: chunk.at,
+                    data: segment.log.read_chunk(chunk)?,
+                });
+            }
+            if !self.advance()? {
+                return Ok(chunks);
+            }
+        }
+    }
+
+    /// New output since the last call: one entry per chunk through the index,
+    /// or everything new as a single unattributed chunk from a raw tail.
+    pub fn read_output(&mut self) -> Result<Vec<OutputChunk>> {
+        if self.indexed {
+            return self.read_new_chunks();
+        }
+        let offset = self.raw_offset;
+        let mut data = Vec::new();
+        self.read_new(&mut data)?;
+        if data.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(vec![OutputChunk {
+            stream: None,
+            offset,
+            at: None,
+            data,
+        }])
+    }
+
+    /// Move on after the front segment has been drained. Returns `true` if
+    /// there may be more to read right away.
+    fn advance(&mut self) -> Result<bool> {
+        if self.segments.len() > 1 {
+            self.segments.pop_front();
+            return Ok(true);
+        }
+        let segment = self.segments.front_mut().expect("tail has a segment");
+
+        // Truncated in place: start over from the beginning of the file
+        if segment.truncated()? {
+            segment.log_pos = 0;
+            self.raw_offset = 0;
+            if let Some(index) = segment.index.as_mut() {
+                index.pos = 0;
+                index.partial.clear();
+                index.base = 0;
+            }
+            return Ok(true);
+        }
+
+        // Rotated: queue the new active segment. The old one is drained once more
+        // first, in case the daemon wrote to it after our last read.
+        let watched = if self.indexed {
+            &self.active.index
+        } else {
+            &self.active.log
+        };
+        if segment
+            .watched_file(self.indexed)
+            .is_some_and(|f| replaced(f, watched))
+            && let Ok(next) = TailSegment::open(&self.active, self.indexed)
+        {
+            self.segments.push_back(next);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+impl TailSegment {
+    fn open(segment: &Segment, indexed: bool) -> Result<Self> {
+        // The daemon creates the log before the index, so opening the index last
+        // guarantees the log belongs to the same (or a newer) segment
+        let index = if indexed {
+            Some(IndexTail {
+                file: File::open(&segment.index)?,
+                pos: 0,
+                partial: String::new(),
+                base: 0,
+            })
+        } else {
+            None
+        };
+        Ok(Self {
+            log: SegmentLog::open(segment)?,
+            log_pos: 0,
+            index,
+        })
+    }
+
+    fn watched_file(&self, indexed: bool) -> Option<&File> {
+        if indexed {
+            self.index.as_ref().map(|i| &i.file)
+        } else {
+            Some(self.log.file())
+        }
+    }
+
+    fn truncated(&self) -> Result<bool> {
+        Ok(match &self.index {
+            Some(index) => index.file.metadata()?.len() < index.pos,
+            None if self.log.is_compressed() => false,
+            None => self.log.file().metadata()?.len() < self.log_pos,
+        })
+    }
+}
+
+/// Whether `path` now names a different file than the open `file`
+/// (it was rotated away). A missing path isn't a replacement yet.
+fn replaced(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(open), Ok(current)) => open.ino() != current.ino() || open.dev() != current.dev(),
+        _ => false,
+    }
+}
+
+impl IndexTail {
+    fn read_new_chunks(&mut self) -> Result<Vec<Chunk>> {
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        let mut buf = String::new();
+        let n = self.file.read_to_string(&mut buf)?;
+        self.pos += n as u64;
+        self.partial.push_str(&buf);
+
+        // Only consume complete records; keep a trailing partial one for next time
+        let complete = match self.partial.rfind('\n') {
+            Some(i) => i + 1,
+            None => return Ok(Vec::new()),
+        };
+        let mut chunks = Vec::new();
+        for line in self.partial[..complete].lines() {
+            if let Some(chunk) = Chunk::parse_index_line(line) {
+                chunks.push(chunk);
+            } else if let Some((_, base)) = parse_generation_line(line) {
+                self.base = base;
+            }
+        }
+        self.partial.drain(..complete);
+        Ok(chunks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    /// Write a combined log and its index from (stream, bytes) pairs
+    fn write_output(dir: &Path, parts: &[(Stream, &str)]) -> (PathBuf, PathBuf) {
+        let log_path = dir.join("job.log");
+        let index_path = dir.join("job.idx");
+        let mut log = File::create(&log_path).unwrap();
+        let mut index = File::create(&index_path).unwrap();
+        let mut offset = 0;
+        for (stream, data) in parts {
+            log.write_all(data.as_bytes()).unwrap();
+            let chunk = Chunk {
+                stream: *stream,
+                offset,
+                len: data.len() as u64,
+                at: DateTime::from_timestamp_millis(1_000 + offset as i64),
+            };
+            index.write_all(chunk.to_index_line().as_bytes()).unwrap();
+            offset += data.len() as u64;
+        }
+        (log_path, index_path)
+    }
+
+    #[test]
+    fn test_index_line_roundtrip() {
+        let chunk = Chunk {
+            stream: Stream::Stderr,
+            offset: 42,
+            len: 7,
+            at: DateTime::from_timestamp_millis(1_760_000_000_123),
+        };
+        assert_eq!(chunk.to_index_line(), "e 42 7 1760000000123\n");
+        assert_eq!(Chunk::parse_index_line("e 42 7 1760000000123"), Some(chunk));
+    }
+
+    #[test]
+    fn test_index_line_without_timestamp() {
+        let chunk = Chunk::parse_index_line("o 0 5").unwrap();
+        assert_eq!(chunk.len, 5);
+        assert!(chunk.at.is_none());
+    }
+
+    #[test]
+    fn test_parse_index_line_rejects_malformed() {
+        assert_eq!(Chunk::parse_index_line(""), None);
+        assert_eq!(Chunk::parse_index_line("x 1 2"), None);
+        assert_eq!(Chunk::parse_index_line("o 1"), None);
+        assert_eq!(Chunk::parse_index_line("o one 2"), None);
+        assert_eq!(Chunk::parse_index_line("o 1 2 later"), None);
+    }
+
+    #[test]
+    fn test_read_index_missing_is_none() {
+        let tmp = TempDir::new().unwrap();
+        assert!(read_index(&tmp.path().join("none.idx")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_stream_bytes() {
+        let tmp = TempDir::new().unwrap();
+        let (_, index_path) = write_output(
+            tmp.path(),
+            &[
+                (Stream::Stdout, "abc"),
+                (Stream::Stderr, "de"),
+                (Stream::Stdout, "f"),
+            ],
+        );
+        let chunks = read_index(&index_path).unwrap().unwrap().chunks;
+        assert_eq!(
+            StreamBytes::from_chunks(&chunks),
+            StreamBytes {
+                stdout: 4,
+                stderr: 2
+            }
+        );
+    }
+
+    #[test]
+    fn test_stream_reader_separates_streams() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = write_output(
+            tmp.path(),
+            &[
+                (Stream::Stdout, "out 1\n"),
+                (Stream::Stderr, "err 1\n"),
+                (Stream::Stdout, "out 2\n"),
+            ],
+        );
+        let segment = Segment {
+            log: log_path,
+            index: index_path,
+        };
+        let load = || IndexedOutput::load(&segment).unwrap().unwrap();
+
+        let mut out = String::new();
+        StreamReader::new(load(), Stream::Stdout)
+            .read_to_string(&mut out)
+            .unwrap();
+        assert_eq!(out, "out 1\nout 2\n");
+
+        let mut err = String::new();
+        StreamReader::new(load(), Stream::Stderr)
+            .read_to_string(&mut err)
+            .unwrap();
+        assert_eq!(err, "err 1\n");
+    }
+
+    #[test]
+    fn test_output_tail_reads_incrementally() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = write_output(
+            tmp.path(),
+            &[(Stream::Stdout, "a\n"), (Stream::Stderr, "b\n")],
+        );
+
+        let segment = Segment {
+            log: log_path.clone(),
+            index: index_path.clone(),
+        };
+        let mut tail = OutputTail::open(segment, Some(Stream::Stderr)).unwrap();
+        let mut out = Vec::new();
+        tail.read_new(&mut out).unwrap();
+        assert_eq!(out, b"b\n");
+
+        // Append another stderr chunk and a half-written index record
+        let mut log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&log_path)
+            .unwrap();
+        let mut index = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&index_path)
+            .unwrap();
+        log.write_all(b"c\n").unwrap();
+        index.write_all(b"e 4 2 1004\ne 6").unwrap();
+
+        out.clear();
+        tail.read_new(&mut out).unwrap();
+        assert_eq!(out, b"c\n");
+    }
+
+    #[test]
+    fn test_output_tail_unfiltered_reads_combined() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = write_output(
+            tmp.path(),
+            &[(Stream::Stdout, "a\n"), (Stream::Stderr, "b\n")],
+        );
+
+        let segment = Segment {
+            log: log_path,
+            index: index_path,
+        };
+        let mut tail = OutputTail::open(segment, None).unwrap();
+        let mut out = Vec::new();
+        tail.read_new(&mut out).unwrap();
+        assert_eq!(out, b"a\nb\n");
+        out.clear();
+        assert_eq!(tail.read_new(&mut out).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_line_splitter_uses_start_time_of_line() {
+        let t = |ms| DateTime::from_timestamp_millis(ms);
+        let mut splitter = LineSplitter::default();
+        let mut lines = Vec::new();
+
+        splitter.push(b"one\ntw", t(1), &mut lines);
+        splitter.push(b"o\nthree", t(2), &mut lines);
+
+        assert_eq!(
+            lines,
+            vec![
+                TimedLine {
+                    at: t(1),
+                    text: "one".into()
+                },
+                TimedLine {
+                    at: t(1),
+                    text: "two".into()
+                },
+            ]
+        );
+        assert_eq!(
+            splitter.finish(),
+            Some(TimedLine {
+                at: t(2),
+                text: "three".into()
+            })
+        );
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn test_timed_lines_filters_stream() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = write_output(
+            tmp.path(),
+            &[
+                (Stream::Stdout, "out\n"),
+                (Stream::Stderr, "err\n"),
+                (Stream::Stdout, "tail"),
+            ],
+        );
+        let segment = Segment {
+            log: log_path,
+            index: index_path,
+        };
+        let load = || IndexedOutput::load(&segment).unwrap().unwrap();
+
+        let all: Vec<String> = TimedLines::new(load(), None)
+            .map(|l| l.unwrap().text)
+            .collect();
+        assert_eq!(all, vec!["out", "err", "tail"]);
+
+        let err: Vec<TimedLine> = TimedLines::new(load(), Some(Stream::Stderr))
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(err.len(), 1);
+        assert_eq!(err[0].at, DateTime::from_timestamp_millis(1_004));
+    }
+
+    #[test]
+    fn test_read_index_generation_header() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("job.idx");
+        std::fs::write(&path, "g 4 1024\no 0 3 1000\n").unwrap();
+        let index = read_index(&path).unwrap().unwrap();
+        assert_eq!((index.generation, index.base), (4, 1024));
+        assert_eq!(index.chunks.len(), 1);
+    }
+
+    /// Writes the active segment plus `<log>.1` holding `rotated`
+    fn write_rotated(dir: &Path, rotated: &str, active: &str) -> Segment {
+        let (log, index) = write_output(dir, &[(Stream::Stdout, rotated)]);
+        let segment = Segment { log, index };
+        let old = segment.rotated(1);
+        std::fs::rename(&segment.log, &old.log).unwrap();
+        std::fs::rename(&segment.index, &old.index).unwrap();
+        write_output(dir, &[(Stream::Stderr, active)]);
+        segment
+    }
+
+    #[test]
+    fn test_segments_read_oldest_first() {
+        let tmp = TempDir::new().unwrap();
+        let active = write_rotated(tmp.path(), "one\n", "two\n");
+        assert_eq!(active.existing(), vec![active.rotated(1), active.clone()]);
+
+        let mut combined = String::new();
+        open_log(&active)
+            .unwrap()
+            .read_to_string(&mut combined)
+            .unwrap();
+        assert_eq!(combined, "one\ntwo\n");
+
+        let output = IndexedOutput::load(&active).unwrap().unwrap();
+        assert_eq!(
+            StreamBytes::from_chunks(output.chunks()),
+            StreamBytes {
+                stdout: 4,
+                stderr: 4
+            }
+        );
+        let lines: Vec<String> = TimedLines::new(output, None)
+            .map(|l| l.unwrap().text)
+            .collect();
+        assert_eq!(lines, vec!["one", "two"]);
+    }
+
+    #[test]
+    fn test_output_tail_follows_rotation() {
+        let tmp = TempDir::new().unwrap();
+        let (log, index) = write_output(tmp.path(), &[(Stream::Stdout, "a\n")]);
+        let active = Segment { log, index };
+
+        let mut raw = OutputTail::open(active.clone(), None).unwrap();
+        let mut indexed = OutputTail::open_indexed(active.clone(), None).unwrap();
+        let mut out = Vec::new();
+        raw.read_new(&mut out).unwrap();
+        assert_eq!(out, b"a\n");
+        assert_eq!(indexed.read_new_chunks().unwrap().len(), 1);
+
+        // Rotate: the old segment gets a late chunk, then a new segment starts
+        let mut old_log = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&active.log)
+            .unwrap();
+        let mut old_index = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&active.index)
+            .unwrap();
+        old_log.write_all(b"b\n").unwrap();
+        old_index.write_all(b"o 2 2 1002\n").unwrap();
+        std::fs::rename(&active.index, &active.rotated(1).index).unwrap();
+        std::fs::rename(&active.log, &active.rotated(1).log).unwrap();
+        write_output(tmp.path(), &[(Stream::Stderr, "c\n")]);
+
+        out.clear();
+        raw.read_new(&mut out).unwrap();
+        assert_eq!(out, b"b\nc\n");
+        let data: Vec<Vec<u8>> = indexed
+            .read_new_chunks()
+            .unwrap()
+            .into_iter()
+            .map(|c| c.data)
+            .collect();
+        assert_eq!(data, vec![b"b\n".to_vec(), b"c\n".to_vec()]);
+    }
+
+    #[test]
+    fn test_tail_positions_chunks_in_whole_output() {
+        let tmp = TempDir::new().unwrap();
+        let (log, index) = write_output(tmp.path(), &[]);
+        std::fs::write(&log, "abcdef").unwrap();
+        std::fs::write(&index, "g 2 100\no 0 3 1000\ne 3 3 1001\n").unwrap();
+
+        let mut tail = OutputTail::open_indexed(Segment { log, index }, None).unwrap();
+        let chunks = tail.read_new_chunks().unwrap();
+        let offsets: Vec<u64> = chunks.iter().map(|c| c.offset).collect();
+        assert_eq!(offsets, vec![100, 103]);
+
+        // Resuming mid-chunk keeps only the unseen bytes
+        let mut chunk = chunks[1].clone();
+        assert!(chunk.skip_to(104));
+        assert_eq!((chunk.offset, chunk.data.as_slice()), (104, &b"ef"[..]));
+        assert!(!chunk.skip_to(106));
+    }
+
+    #[test]
+    fn test_cursor_parse_and_display() {
+        let cursor: Cursor = "2:4096".parse().unwrap();
+        assert_eq!(
+            cursor,
+            Cursor {
+                generation: 2,
+                offset: 4096
+            }
+        );
+        assert_eq!(cursor.to_string(), "2:4096");
+        assert_eq!("0".parse::<Cursor>().unwrap(), Cursor::default());
+        assert!("x:1".parse::<Cursor>().is_err());
+    }
+
+    /// Segment 1 (`abc`, base 0) rotated away, segment 2 (`def`, base 3) as `.1`,
+    /// and active segment 3 (`gh`, base 6)
+    fn write_generations(dir: &Path) -> Segment {
+        let (log, index) = write_output(dir, &[]);
+        let active = Segment { log, index };
+        let old = active.rotated(1);
+        std::fs::write(&old.log, "def").unwrap();
+        std::fs::write(&old.index, "g 2 3\no 0 3 1000\n").unwrap();
+        std::fs::write(&active.log, "gh").unwrap();
+        std::fs::write(&active.index, "g 3 6\ne 0 2 1001\n").unwrap();
+        active
+    }
+
+    #[test]
+    fn test_cursor_read_across_segments() {
+        let tmp = TempDir::new().unwrap();
+        let active = write_generations(tmp.path());
+        let output = IndexedOutput::load(&active).unwrap().unwrap();
+        let at = |generation, offset| Cursor { generation, offset };
+
+        let read = CursorRead::from_index(&output, at(2, 4), None, None).unwrap();
+        assert_eq!(read.data, b"efgh");
+        assert_eq!(
+            (read.cursor, read.skipped, read.reset),
+            (at(3, 8), 0, false)
+        );
+
+        // Nothing new: the cursor stays put
+        let read = CursorRead::from_index(&output, at(3, 8), None, None).unwrap();
+        assert!(read.data.is_empty());
+        assert_eq!(read.cursor, at(3, 8));
+
+        // Generation 1 was rotated away: its unread tail is reported as skipped
+        let read = CursorRead::from_index(&output, at(1, 1), Some(Stream::Stdout), None).unwrap();
+        assert_eq!(read.data, b"def");
+        assert_eq!((read.cursor, read.skipped), (at(3, 8), 2));
+    }
+
+    #[test]
+    fn test_cursor_read_stops_at_limit() {
+        let tmp = TempDir::new().unwrap();
+        let active = write_generations(tmp.path());
+        let output = IndexedOutput::load(&active).unwrap().unwrap();
+        let at = |generation, offset| Cursor { generation, offset };
+
+        let read = CursorRead::from_index(&output, at(2, 3), None, Some(4)).unwrap();
+        assert_eq!(read.data, b"defg");
+        assert_eq!((read.cursor, read.truncated), (at(3, 7), true));
+        let read = CursorRead::from_index(&output, read.cursor, None, Some(4)).unwrap();
+        assert_eq!(read.data, b"h");
+        assert!(!read.truncated);
+
+        // A limit at a chunk boundary resumes with the next chunk
+        let read = CursorRead::from_index(&output, at(2, 3), None, Some(3)).unwrap();
+        assert_eq!((read.data.as_slice(), read.truncated), (&b"def"[..], true));
+        let read = CursorRead::from_index(&output, read.cursor, None, None).unwrap();
+        assert_eq!(read.data, b"gh");
+
+        let read = CursorRead::from_log(&active, Cursor::default(), Some(1)).unwrap();
+        assert_eq!((read.data.as_slice(), read.cursor), (&b"d"[..], at(0, 1)));
+    }
+
+    #[test]
+    fn test_cursor_read_resets_on_mismatch() {
+        let tmp = TempDir::new().unwrap();
+        let active = write_generations(tmp.path());
+        let output = IndexedOutput::load(&active).unwrap().unwrap();
+
+        // Beyond the end of its generation (the log was truncated or replaced)
+        let cursor = Cursor {
+            generation: 3,
+            offset: 50,
+        };
+        let read = CursorRead::from_index(&output, cursor, None, None).unwrap();
+        assert!(read.reset);
+        assert_eq!(read.data, b"defgh");
+
+        // Unindexed log shorter than the cursor
+        let read = CursorRead::from_log(&active, cursor, None).unwrap();
+        assert!(read.reset);
+    }
+
+    #[test]
+    fn test_output_tail_restarts_after_truncation() {
+        let tmp = TempDir::new().unwrap();
+        let (log, index) = write_output(tmp.path(), &[(Stream::Stdout, "long line\n")]);
+        let mut tail = OutputTail::open(
+            Segment {
+                log: log.clone(),
+                index,
+            },
+            None,
+        )
+        .unwrap();
+        let mut out = Vec::new();
+        tail.read_new(&mut out).unwrap();
+
+        std::fs::write(&log, "new\n").unwrap();
+        out.clear();
+        tail.read_new(&mut out).unwrap();
+        assert_eq!(out, b"new\n");
+    }
+
+    #[test]
+    fn test_reads_compressed_segment() {
+        let tmp = TempDir::new().unwrap();
+        let (log, index) = write_output(
+            tmp.path(),
+            &[(Stream::Stdout, "out\n"), (Stream::Stderr, "err\n")],
+        );
+        let segment = Segment { log, index };
+        let mut encoder = flate2::write::GzEncoder::new(
+            File::create(segment.compressed_log()).unwrap(),
+            flate2::Compression::default(),
+        );
+        encoder
+            .write_all(&std::fs::read(&segment.log).unwrap())
+            .unwrap();
+        encoder.finish().unwrap();
+        std::fs::remove_file(&segment.log).unwrap();
+
+        let mut err = String::new();
+        StreamReader::new(
+            IndexedOutput::load(&segment).unwrap().unwrap(),
+            Stream::Stderr,
+        )
+        .read_to_string(&mut err)
+        .unwrap();
+        assert_eq!(err, "err\n");
+
+        let mut out = Vec::new();
+        OutputTail::open(segment.clone(), None)
+            .unwrap()
+            .read_new(&mut out)
+            .unwrap();
+        assert_eq!(out, b"out\nerr\n");
+        assert_eq!(LogSize::measure(&segment).unwrap().bytes, 8);
+    }
+}
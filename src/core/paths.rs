@@ -1,4 +1,12 @@
-use std::path::PathBuf;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Environment variable that relocates all state (database, logs, socket)
+pub const HOME_ENV: &str = "JB_HOME";
+
+/// Root set by the global `--home` flag; takes precedence over `JB_HOME`
+static HOME_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
 
 #[derive(Clone)]
 pub struct Paths {
@@ -6,20 +14,49 @@ pub struct Paths {
 }
 
 impl Paths {
+    /// Resolve the state root: `--home`, then `$JB_HOME`, then `~/.jb`.
     pub fn new() -> anyhow::Result<Self> {
+        Self::resolve(HOME_OVERRIDE.get().cloned(), std::env::var_os(HOME_ENV))
+    }
+
+    /// The root from the `--home` flag or the value of `JB_HOME`, in that order
+    fn resolve(flag: Option<PathBuf>, env: Option<OsString>) -> anyhow::Result<Self> {
+        if let Some(root) = flag {
+            return Ok(Self::with_root(root));
+        }
+        if let Some(root) = env.filter(|v| !v.is_empty()) {
+            return Ok(Self::with_root(std::path::absolute(root)?));
+        }
         let root = dirs::home_dir()
             .ok_or_else(|| anyhow::anyhow!("could not determine home directory"))?
             .join(".jb");
-        Ok(Self { root })
+        Ok(Self::with_root(root))
     }
 
-    /// Create Paths with a custom root directory (useful for testing)
-    #[cfg(test)]
+    /// Override the state root for the rest of this process (the `--home` flag).
+    pub fn set_home(root: &Path) -> anyhow::Result<()> {
+        let root = std::path::absolute(root)?;
+        HOME_OVERRIDE
+            .set(root)
+            .map_err(|_| anyhow::anyhow!("home directory already set"))
+    }
+
+    /// Create Paths with a custom root directory
     #[must_use]
     pub fn with_root(root: PathBuf) -> Self {
         Self { root }
     }
 
+    #[must_use]
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    #[must_use]
+    pub fn config(&self) -> PathBuf {
+        self.root.join("config.toml")
+    }
+
     #[must_use]
     pub fn database(&self) -> PathBuf {
         self.root.join("job.db")
@@ -35,19 +72,68 @@ impl Paths {
         self.logs_dir().join(format!("{job_id}.log"))
     }
 
+    /// Per-chunk stream index for `log_file` (see `output::Chunk`)
+    #[must_use]
+    pub fn index_file(&self, job_id: &str) -> PathBuf {
+        self.logs_dir().join(format!("{job_id}.idx"))
+    }
+
     #[must_use]
     pub fn socket(&self) -> PathBuf {
         self.root.join("daemon.sock")
     }
 
+    /// Socket of the optional HTTP API, when it is configured as `unix`
+    #[must_use]
+    pub fn http_socket(&self) -> PathBuf {
+        self.root.join("http.sock")
+    }
+
+    /// Bearer token the HTTP API requires, readable only by the user
+    #[must_use]
+    pub fn http_token(&self) -> PathBuf {
+        self.root.join("http.token")
+    }
+
     #[must_use]
     pub fn pid_file(&self) -> PathBuf {
         self.root.join("daemon.pid")
     }
 
+    /// Daemon's own log file. Rotated copies live alongside as `daemon.log.1`, `daemon.log.2`, ...
+    #[must_use]
+    pub fn daemon_log(&self) -> PathBuf {
+        self.root.join("daemon.log")
+    }
+
     pub fn ensure_dirs(&self) -> anyhow::Result<()> {
         std::fs::create_dir_all(&self.root)?;
         std::fs::create_dir_all(self.logs_dir())?;
         Ok(())
     }
 }
+
+/// Path of the `n`th rotated copy of a file: `<path>.<n>`
+#[must_use]
+pub fn rotated_path(path: &Path, n: usize) -> PathBuf {
+    let mut s = path.as_os_str().to_owned();
+    s.push(format!(".{n}"));
+    PathBuf::from(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_home_flag_takes_precedence() {
+        let paths = Paths::resolve(Some("/flag".into()), Some("/env".into())).unwrap();
+        assert_eq!(paths.root(), Path::new("/flag"));
+        let paths = Paths::resolve(None, Some("/env".into())).unwrap();
+        assert_eq!(paths.root(), Path::new("/env"));
+        assert_eq!(paths.socket(), Path::new("/env/daemon.sock"));
+        // An empty JB_HOME is ignored
+        let paths = Paths::resolve(None, Some(OsString::new())).unwrap();
+        assert_eq!(paths.root().file_name(), Some(".jb".as_ref()));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::core::Status;
+use anyhow::{Result, bail};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize, Serializer};
+use sha2::Sha256;
+
+/// Header carrying the payload's HMAC-SHA256 when the webhook has a secret
+pub const SIGNATURE_HEADER: &str = "X-Jb-Signature-256";
+
+/// An endpoint the daemon posts finished jobs to. Set per job with
+/// `jb run --webhook URL`, and for every job with `[[webhooks]]` in config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "cli", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    /// An `http://` or `https://` URL
+    pub url: String,
+    /// Sign each payload with this key, in the `X-Jb-Signature-256` header
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    /// Only post jobs that ended with one of these statuses; empty for all
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub events: Vec<Status>,
+}
+
+impl Webhook {
+    #[must_use]
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            secret: None,
+            events: Vec::new(),
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        let url = url::Url::parse(&self.url)
+            .map_err(|e| anyhow::anyhow!("invalid webhook URL '{}': {e}", self.url))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            bail!("webhook URL must be http or https: {}", self.url);
+        }
+        if let Some(status) = self.events.iter().find(|s| !s.is_terminal()) {
+            bail!("webhook events must be statuses a job ends with, not {status}");
+        }
+        Ok(())
+    }
+
+    /// Whether a job that ended with `status` is posted
+    #[must_use]
+    pub fn wants(&self, status: Status) -> bool {
+        status.is_terminal() && (self.events.is_empty() || self.events.contains(&status))
+    }
+}
+
+/// `sha256=<hex>` of `body` keyed with `secret`, the value of
+/// [`SIGNATURE_HEADER`]. Receivers recompute it over the raw body.
+#[must_use]
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(body);
+    let hex: String = mac
+        .finalize()
+        .into_bytes()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect();
+    format!("sha256={hex}")
+}
+
+/// Serialize `webhooks` without their secrets; see [`Job::webhooks`](crate::core::Job::webhooks)
+pub(crate) fn serialize_without_secrets<S: Serializer>(
+    webhooks: &[Webhook],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(webhooks.iter().map(|w| Webhook {
+        secret: None,
+        ..w.clone()
+    }))
+}
+
+/// One attempt at posting a job to a webhook, as recorded in the database
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub url: String,
+    /// 1 for the first try
+    pub attempt: u32,
+    pub at: DateTime<Utc>,
+    /// The HTTP status the endpoint answered with, if it answered
+    pub status_code: Option<u16>,
+    /// Why the attempt failed, if it did
+    pub error: Option<String>,
+}
+
+impl Delivery {
+    #[must_use]
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wants_filters_by_status() {
+        let all = Webhook::new("http://localhost/hook");
+        assert!(all.wants(Status::Stopped));
+        assert!(!all.wants(Status::Running));
+
+        let failures = Webhook {
+            events: vec![Status::Failed],
+            ..all
+        };
+        assert!(failures.wants(Status::Failed));
+        assert!(!failures.wants(Status::Completed));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Webhook::new("https://ci.internal/jb").validate().is_ok());
+        assert!(Webhook::new("ftp://ci.internal/jb").validate().is_err());
+        assert!(Webhook::new("not a url").validate().is_err());
+        let running = Webhook {
+            events: vec![Status::Running],
+            ..Webhook::new("http://localhost/")
+        };
+        assert!(running.validate().is_err());
+    }
+
+    #[test]
+    fn test_signature() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            signature("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
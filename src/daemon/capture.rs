@@ -0,0 +1,422 @@
+use crate::core::output::{Chunk, LogLimit, Segment, Stream, generation_line, read_index};
+use crate::daemon::redact::{Redactor, StreamRedactor};
+use crate::daemon::state::OutputProgress;
+use chrono::Utc;
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::Write;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::time::Instant;
+
+/// Writes captured output to the combined log and its chunk index,
+/// rotating both when the log reaches its size limit.
+pub struct OutputWriter {
+    segment: Segment,
+    limit: Option<LogLimit>,
+    log: File,
+    index: File,
+    /// Write position in the active segment's log
+    offset: u64,
+    generation: u64,
+    /// Bytes written to earlier segments
+    base: u64,
+    progress: Option<OutputProgress>,
+}
+
+impl OutputWriter {
+    pub fn create(segment: Segment, limit: Option<LogLimit>) -> std::io::Result<Self> {
+        let (log, index) = create_segment(&segment, 0, 0)?;
+        Ok(Self {
+            segment,
+            limit,
+            log,
+            index,
+            offset: 0,
+            generation: 0,
+            base: 0,
+            progress: None,
+        })
+    }
+
+    /// Continue writing the active segment where an earlier writer stopped,
+    /// e.g. in the helper that drains a job the daemon left running.
+    pub fn resume(segment: Segment, limit: Option<LogLimit>) -> std::io::Result<Self> {
+        let header = read_index(&segment.index)
+            .map_err(std::io::Error::other)?
+            .ok_or_else(|| std::io::Error::other("the job's output has no index"))?;
+        let append = |path| OpenOptions::new().append(true).open(path);
+        let log = append(&segment.log)?;
+        let index = append(&segment.index)?;
+        Ok(Self {
+            offset: log.metadata()?.len(),
+            segment,
+            limit,
+            log,
+            index,
+            generation: header.generation,
+            base: header.base,
+            progress: None,
+        })
+    }
+
+    /// Report every write to `progress` (for log subscribers).
+    #[must_use]
+    pub fn with_progress(mut self, progress: OutputProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    pub fn write_chunk(&mut self, stream: Stream, data: &[u8]) -> std::io::Result<()> {
+        if let Some(limit) = self.limit
+            && self.offset > 0
+            && self.offset + data.len() as u64 > limit.max_bytes
+        {
+            self.rotate(limit.keep)?;
+        }
+
+        // Log first: a reader that sees the index record can rely on the bytes being there
+        self.log.write_all(data)?;
+        let chunk = Chunk {
+            stream,
+            offset: self.offset,
+            len: data.len() as u64,
+            at: Some(Utc::now()),
+        };
+        self.index.write_all(chunk.to_index_line().as_bytes())?;
+        self.offset = chunk.end();
+        if let Some(progress) = &self.progress {
+            progress.send_replace(self.base + self.offset);
+        }
+        Ok(())
+    }
+
+    /// Retire the active segment and start a new one. Files are renamed (or
+    /// removed when nothing is kept) rather than truncated, so a reader holding
+    /// the old files open can finish them.
+    fn rotate(&mut self, keep: u32) -> std::io::Result<()> {
+        if keep == 0 {
+            std::fs::remove_file(&self.segment.index)?;
+            std::fs::remove_file(&self.segment.log)?;
+        } else {
+            let oldest = self.segment.rotated(keep);
+            let _ = std::fs::remove_file(&oldest.index);
+            let _ = std::fs::remove_file(&oldest.log);
+            for n in (1..keep).rev() {
+                let (from, to) = (self.segment.rotated(n), self.segment.rotated(n + 1));
+                if from.log.exists() {
+                    std::fs::rename(&from.index, &to.index)?;
+                    std::fs::rename(&from.log, &to.log)?;
+                }
+            }
+            let newest = self.segment.rotated(1);
+            std::fs::rename(&self.segment.index, &newest.index)?;
+            std::fs::rename(&self.segment.log, &newest.log)?;
+        }
+
+        self.generation += 1;
+        self.base += self.offset;
+        (self.log, self.index) = create_segment(&self.segment, self.generation, self.base)?;
+        self.offset = 0;
+        Ok(())
+    }
+}
+
+/// Create a segment's log, then its index (readers rely on this order).
+fn create_segment(segment: &Segment, generation: u64, base: u64) -> std::io::Result<(File, File)> {
+    let log = File::create(&segment.log)?;
+    let mut index = File::create(&segment.index)?;
+    index.write_all(generation_line(generation, base).as_bytes())?;
+    Ok((log, index))
+}
+
+/// How long a partial line waits for its end before it is redacted and written
+const REDACT_HOLD_MILLIS: u64 = 100;
+
+/// Copy a job's stdout and stderr into `writer` until both pipes are closed,
+/// redacting secrets on the way when there is a `redactor`.
+pub async fn capture(
+    stdout: impl AsyncRead + Unpin,
+    stderr: impl AsyncRead + Unpin,
+    writer: OutputWriter,
+    redactor: Option<Redactor>,
+) -> std::io::Result<()> {
+    capture_until(
+        stdout,
+        stderr,
+        writer,
+        redactor,
+        std::future::pending::<()>(),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Like [`capture`], but stop early when `handoff` resolves: held-back output
+/// is written and the pipes are returned, still open, with its value.
+pub async fn capture_until<O, E, T>(
+    mut stdout: O,
+    mut stderr: E,
+    mut writer: OutputWriter,
+    redactor: Option<Redactor>,
+    handoff: impl Future<Output = T>,
+) -> std::io::Result<Option<(O, E, T)>>
+where
+    O: AsyncRead + Unpin,
+    E: AsyncRead + Unpin,
+{
+    tokio::pin!(handoff);
+    let mut out_buf = vec![0u8; 8192];
+    let mut err_buf = vec![0u8; 8192];
+    let mut out_open = true;
+    let mut err_open = true;
+    let mut out_redact = redactor.clone().map(StreamRedactor::new);
+    let mut err_redact = redactor.map(StreamRedactor::new);
+
+    // When the oldest held-back partial line must be written
+    let mut hold_until: Option<Instant> = None;
+
+    while out_open || err_open {
+        let pending = [&out_redact, &err_redact]
+            .iter()
+            .any(|r| r.as_ref().is_some_and(StreamRedactor::has_pending));
+        if !pending {
+            hold_until = None;
+        }
+        let deadline = *hold_until
+            .get_or_insert_with(|| Instant::now() + Duration::from_millis(REDACT_HOLD_MILLIS));
+
+        tokio::select! {
+            r = stdout.read(&mut out_buf), if out_open => match r {
+                Ok(0) | Err(_) => {
+                    out_open = false;
+                    write_flushed(&mut writer, Stream::Stdout, out_redact.as_mut())?;
+                }
+                Ok(n) => {
+                    let data = &out_buf[..n];
+                    write_redacted(&mut writer, Stream::Stdout, data, out_redact.as_mut())?;
+                }
+            },
+            r = stderr.read(&mut err_buf), if err_open => match r {
+                Ok(0) | Err(_) => {
+                    err_open = false;
+                    write_flushed(&mut writer, Stream::Stderr, err_redact.as_mut())?;
+                }
+                Ok(n) => {
+                    let data = &err_buf[..n];
+                    write_redacted(&mut writer, Stream::Stderr, data, err_redact.as_mut())?;
+                }
+            },
+            // Don't hold a prompt or progress line back for long
+            () = tokio::time::sleep_until(deadline), if pending => {
+                write_flushed(&mut writer, Stream::Stdout, out_redact.as_mut())?;
+                write_flushed(&mut writer, Stream::Stderr, err_redact.as_mut())?;
+            }
+            value = &mut handoff => {
+                write_flushed(&mut writer, Stream::Stdout, out_redact.as_mut())?;
+                write_flushed(&mut writer, Stream::Stderr, err_redact.as_mut())?;
+                return Ok(Some((stdout, stderr, value)));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+fn write_redacted(
+    writer: &mut OutputWriter,
+    stream: Stream,
+    data: &[u8],
+    redact: Option<&mut StreamRedactor>,
+) -> std::io::Result<()> {
+    let Some(redact) = redact else {
+        return writer.write_chunk(stream, data);
+    };
+    let ready = redact.push(data);
+    if ready.is_empty() {
+        return Ok(());
+    }
+    writer.write_chunk(stream, &ready)
+}
+
+fn write_flushed(
+    writer: &mut OutputWriter,
+    stream: Stream,
+    redact: Option<&mut StreamRedactor>,
+) -> std::io::Result<()> {
+    match redact {
+        Some(redact) if redact.has_pending() => writer.write_chunk(stream, &redact.flush()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::output::StreamBytes;
+    use tempfile::TempDir;
+
+    fn segment(tmp: &TempDir) -> Segment {
+        Segment {
+            log: tmp.path().join("a.log"),
+            index: tmp.path().join("a.idx"),
+        }
+    }
+
+    #[test]
+    fn test_writer_records_offsets() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = (tmp.path().join("a.log"), tmp.path().join("a.idx"));
+        let mut writer = OutputWriter::create(segment(&tmp), None).unwrap();
+
+        writer.write_chunk(Stream::Stdout, b"hello\n").unwrap();
+        writer.write_chunk(Stream::Stderr, b"oops\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&log_path).unwrap(), "hello\noops\n");
+        let chunks = read_index(&index_path).unwrap().unwrap().chunks;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(
+            (chunks[0].stream, chunks[0].offset, chunks[0].len),
+            (Stream::Stdout, 0, 6)
+        );
+        assert_eq!(
+            (chunks[1].stream, chunks[1].offset, chunks[1].len),
+            (Stream::Stderr, 6, 5)
+        );
+        assert!(chunks.iter().all(|c| c.at.is_some()));
+    }
+
+    #[tokio::test]
+    async fn test_capture_keeps_streams_apart() {
+        let tmp = TempDir::new().unwrap();
+        let (log_path, index_path) = (tmp.path().join("a.log"), tmp.path().join("a.idx"));
+        let writer = OutputWriter::create(segment(&tmp), None).unwrap();
+
+        capture(&b"out line\n"[..], &b"err line\n"[..], writer, None)
+            .await
+            .unwrap();
+
+        let chunks = read_index(&index_path).unwrap().unwrap().chunks;
+        assert_eq!(
+            StreamBytes::from_chunks(&chunks),
+            StreamBytes {
+                stdout: 9,
+                stderr: 9
+            }
+        );
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(log.contains("out line\n") && log.contains("err line\n"));
+    }
+
+    #[tokio::test]
+    async fn test_capture_redacts_before_writing() {
+        let tmp = TempDir::new().unwrap();
+        let log_path = tmp.path().join("a.log");
+        let writer = OutputWriter::create(segment(&tmp), None).unwrap();
+        let redactor = Redactor::new(false, &[], vec!["hunter22".to_string()]).unwrap();
+
+        capture(
+            &b"pw hunter22\nno newline hunter22"[..],
+            &b"err hunter22\n"[..],
+            writer,
+            redactor,
+        )
+        .await
+        .unwrap();
+
+        let log = std::fs::read_to_string(&log_path).unwrap();
+        assert!(!log.contains("hunter22"), "{log}");
+        assert!(log.contains("pw [REDACTED]\n") && log.contains("no newline [REDACTED]"));
+        assert!(log.contains("err [REDACTED]\n"));
+    }
+
+    #[test]
+    fn test_rotates_and_keeps_n_segments() {
+        let tmp = TempDir::new().unwrap();
+        let active = segment(&tmp);
+        let limit = LogLimit {
+            max_bytes: 4,
+            keep: 2,
+        };
+        let mut writer = OutputWriter::create(active.clone(), Some(limit)).unwrap();
+
+        for data in ["aaa\n", "bbb\n", "ccc\n", "ddd\n"] {
+            writer.write_chunk(Stream::Stdout, data.as_bytes()).unwrap();
+        }
+
+        let read = |s: &Segment| std::fs::read_to_string(&s.log).unwrap();
+        assert_eq!(read(&active), "ddd\n");
+        assert_eq!(read(&active.rotated(1)), "ccc\n");
+        assert_eq!(read(&active.rotated(2)), "bbb\n");
+        assert!(!active.rotated(3).log.exists());
+
+        // Offsets restart in each segment, which is numbered by its generation
+        let index = read_index(&active.index).unwrap().unwrap();
+        assert_eq!((index.generation, index.base), (3, 12));
+        assert_eq!(index.chunks[0].offset, 0);
+        assert_eq!(
+            read_index(&active.rotated(2).index)
+                .unwrap()
+                .unwrap()
+                .generation,
+            1
+        );
+    }
+
+    #[test]
+    fn test_keep_zero_discards_old_output() {
+        let tmp = TempDir::new().unwrap();
+        let active = segment(&tmp);
+        let limit = LogLimit {
+            max_bytes: 4,
+            keep: 0,
+        };
+        let mut writer = OutputWriter::create(active.clone(), Some(limit)).unwrap();
+
+        writer.write_chunk(Stream::Stdout, b"old\n").unwrap();
+        writer.write_chunk(Stream::Stderr, b"new\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&active.log).unwrap(), "new\n");
+        assert!(!active.rotated(1).log.exists());
+        let index = read_index(&active.index).unwrap().unwrap();
+        assert_eq!((index.generation, index.chunks.len()), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_hand_off_then_resume() {
+        let tmp = TempDir::new().unwrap();
+        let active = segment(&tmp);
+        let writer = OutputWriter::create(active.clone(), None).unwrap();
+        let redactor = Redactor::new(false, &[], vec!["hunter22".to_string()]).unwrap();
+        let (mut out_tx, out_rx) = tokio::io::duplex(64);
+        let (_err_tx, err_rx) = tokio::io::duplex(64);
+        let (handoff_tx, handoff_rx) = tokio::sync::oneshot::channel();
+
+        let task = tokio::spawn(capture_until(out_rx, err_rx, writer, redactor, handoff_rx));
+        tokio::io::AsyncWriteExt::write_all(&mut out_tx, b"pw hunter22")
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        handoff_tx.send(7).unwrap();
+        let (out_rx, err_rx, value) = task.await.unwrap().unwrap().unwrap();
+        assert_eq!(value.unwrap(), 7);
+
+        // The pipes are still open, and a resumed writer carries on from the end
+        tokio::io::AsyncWriteExt::write_all(&mut out_tx, b"\nmore\n")
+            .await
+            .unwrap();
+        drop((out_tx, _err_tx));
+        let writer = OutputWriter::resume(active.clone(), None).unwrap();
+        capture(out_rx, err_rx, writer, None).await.unwrap();
+
+        assert_eq!(
+            std::fs::read_to_string(&active.log).unwrap(),
+            "pw [REDACTED]\nmore\n"
+        );
+        let chunks = read_index(&active.index).unwrap().unwrap().chunks;
+        assert_eq!(
+            chunks.iter().map(|c| (c.offset, c.len)).collect::<Vec<_>>(),
+            [(0, 13), (13, 6)]
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use crate::core::output::Segment;
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Logs smaller than this stay uncompressed: gzip wouldn't save anything worthwhile
+const MIN_COMPRESS_BYTES: u64 = 4096;
+
+/// Gzip every segment log of a finished job, replacing `<log>` with `<log>.gz`.
+/// Returns the number of logs compressed.
+pub fn compress_logs(active: &Segment) -> std::io::Result<usize> {
+    let mut compressed = 0;
+    for segment in active.existing() {
+        let Ok(meta) = std::fs::metadata(&segment.log) else {
+            continue; // already compressed
+        };
+        if meta.len() < MIN_COMPRESS_BYTES {
+            continue;
+        }
+        compress_file(&segment.log, &segment.compressed_log())?;
+        compressed += 1;
+    }
+    Ok(compressed)
+}
+
+/// Write `gz_path` via a temporary file, then remove the original. A reader
+/// that still has the original open keeps reading it; new readers find one
+/// of the two complete files at every point.
+fn compress_file(path: &Path, gz_path: &Path) -> std::io::Result<()> {
+    let tmp_path = gz_path.with_extension("gz.tmp");
+    let mut encoder = GzEncoder::new(File::create(&tmp_path)?, Compression::default());
+    std::io::copy(&mut BufReader::new(File::open(path)?), &mut encoder)?;
+    encoder.finish()?.sync_all()?;
+    std::fs::rename(&tmp_path, gz_path)?;
+    std::fs::remove_file(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::output::{LogSize, open_log};
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_compresses_large_logs_only() {
+        let tmp = TempDir::new().unwrap();
+        let active = Segment {
+            log: tmp.path().join("a.log"),
+            index: tmp.path().join("a.idx"),
+        };
+        let big = "some repetitive output\n".repeat(1000);
+        std::fs::write(&active.rotated(1).log, &big).unwrap();
+        std::fs::write(&active.log, "short tail\n").unwrap();
+
+        assert_eq!(compress_logs(&active).unwrap(), 1);
+        assert!(!active.rotated(1).log.exists());
+        assert!(active.rotated(1).compressed_log().exists());
+        assert!(active.log.exists(), "small log stays plain");
+
+        let mut content = String::new();
+        open_log(&active)
+            .unwrap()
+            .read_to_string(&mut content)
+            .unwrap();
+        assert_eq!(content, format!("{big}short tail\n"));
+
+        let size = LogSize::measure(&active).unwrap();
+        assert!(size.compressed);
+        assert_eq!(size.bytes, (big.len() + "short tail\n".len()) as u64);
+        assert!(size.stored_bytes < size.bytes);
+    }
+}
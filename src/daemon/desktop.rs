@@ -0,0 +1,246 @@
+use crate::core::event::HistoryKind;
+use crate::core::{Job, Status};
+use crate::daemon::load_config;
+use crate::daemon::state::DaemonState;
+use chrono::Utc;
+use sender::send;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// Show a desktop notification for a finished job, in the background, if it
+/// asked for one with `--notify` or `[notify] default` in config says so.
+pub fn notify(state: &Arc<DaemonState>, job_id: &str) {
+    let state = Arc::clone(state);
+    let job_id = job_id.to_string();
+    tokio::spawn(async move { notify_finished(&state, &job_id).await });
+}
+
+/// Notify about the jobs a daemon shutdown interrupted. Unlike [`notify`] this is
+/// awaited, so the notifications go out before the daemon exits.
+pub async fn notify_interrupted(state: &Arc<DaemonState>, job_ids: Vec<String>) {
+    let mut sends = JoinSet::new();
+    for job_id in job_ids {
+        let state = Arc::clone(state);
+        sends.spawn(async move {
+            if let Some(job) = load_job(&state, &job_id) {
+                notify_job(&state, &job).await;
+            }
+        });
+    }
+    sends.join_all().await;
+}
+
+async fn notify_finished(state: &DaemonState, job_id: &str) {
+    let Some(job) = load_job(state, job_id) else {
+        return;
+    };
+    // The daemon is exiting: a task spawned now would not get to run, so
+    // `notify_interrupted` covers these on the shutdown path
+    if job.status == Status::Interrupted {
+        return;
+    }
+    notify_job(state, &job).await;
+}
+
+fn load_job(state: &DaemonState, job_id: &str) -> Option<Job> {
+    match state.get_job(job_id) {
+        Ok(job) => job,
+        Err(e) => {
+            warn!("Failed to load job {job_id} for notification: {e}");
+            None
+        }
+    }
+}
+
+async fn notify_job(state: &DaemonState, job: &Job) {
+    let config = load_config(&state.paths, "notifications").notify;
+    let min_secs = config.min_duration_secs().unwrap_or_else(|e| {
+        warn!("Ignoring config for notifications: {e:#}");
+        None
+    });
+    let default = config.default;
+    if !job.notify.unwrap_or(default) {
+        return;
+    }
+    let ran_for = run_time(job);
+    if min_secs.is_some_and(|min| ran_for.as_secs() < min) {
+        return;
+    }
+
+    let (summary, body) = message(job, ran_for);
+    let urgent = job.status == Status::Failed;
+    let detail = match send(&summary, &body, urgent).await {
+        Ok(via) => {
+            info!("Notified job {} finishing via {via}", job.id);
+            format!("sent via {via}")
+        }
+        Err(e) => {
+            warn!("Failed to notify job {} finishing: {e:#}", job.id);
+            format!("not sent: {e:#}")
+        }
+    };
+    let db = state.db.lock().unwrap();
+    db.record_event(&job.id, HistoryKind::Notification, Some(&detail));
+}
+
+/// How long the job ran, from start (or creation, if it never started) to finish
+fn run_time(job: &Job) -> Duration {
+    let start = job.started_at.unwrap_or(job.created_at);
+    let end = job.finished_at.unwrap_or_else(Utc::now);
+    (end - start).to_std().unwrap_or_default()
+}
+
+/// Summary and body: `jb: build failed` / `exit 2 after 3m 12s` and the command
+fn message(job: &Job, ran_for: Duration) -> (String, String) {
+    let label = job
+        .name
+        .clone()
+        .unwrap_or_else(|| job.short_id().to_string());
+    let summary = format!("jb: {label} {}", job.status);
+    let outcome = match job.exit_code {
+        Some(code) => format!("exit {code} after {}", format_run_time(ran_for)),
+        None => format!("{} after {}", job.status, format_run_time(ran_for)),
+    };
+    (summary, format!("{outcome}\n{}", job.command))
+}
+
+fn format_run_time(duration: Duration) -> String {
+    // Rounded, so a job of 0.99s doesn't read as 0s
+    let secs = (duration + Duration::from_millis(500)).as_secs();
+    let (hours, mins, secs) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if hours > 0 {
+        format!("{hours}h {mins}m")
+    } else if mins > 0 {
+        format!("{mins}m {secs}s")
+    } else {
+        format!("{secs}s")
+    }
+}
+
+/// How notifications reach the desktop. Tests get a stand-in instead, so
+/// `cargo test` doesn't pop up notifications or wait on a missing notifier.
+#[cfg(not(test))]
+mod sender {
+    use anyhow::{Result, bail};
+    use std::time::Duration;
+    use tokio::process::Command;
+
+    /// Longest to wait for the notification service or `notify-send`
+    const SEND_TIMEOUT: Duration = Duration::from_secs(5);
+
+    /// Send over D-Bus, falling back to `notify-send`; returns which one worked
+    pub async fn send(summary: &str, body: &str, urgent: bool) -> Result<&'static str> {
+        let dbus_error =
+            match tokio::time::timeout(SEND_TIMEOUT, send_dbus(summary, body, urgent)).await {
+                Ok(Ok(())) => return Ok("D-Bus"),
+                Ok(Err(e)) => e,
+                Err(_) => anyhow::anyhow!("timed out"),
+            };
+        match tokio::time::timeout(SEND_TIMEOUT, send_notify_send(summary, body, urgent)).await {
+            Ok(Ok(())) => Ok("notify-send"),
+            Ok(Err(e)) => bail!("D-Bus: {dbus_error:#}; notify-send: {e:#}"),
+            Err(_) => bail!("D-Bus: {dbus_error:#}; notify-send: timed out"),
+        }
+    }
+
+    /// `org.freedesktop.Notifications.Notify` on the session bus
+    #[cfg(target_os = "linux")]
+    async fn send_dbus(summary: &str, body: &str, urgent: bool) -> Result<()> {
+        use std::collections::HashMap;
+        use zbus::zvariant::Value;
+
+        let connection = zbus::Connection::session().await?;
+        // Urgency: 1 normal, 2 critical
+        let hints = HashMap::from([("urgency", Value::U8(if urgent { 2 } else { 1 }))]);
+        let actions: Vec<&str> = Vec::new();
+        connection
+            .call_method(
+                Some("org.freedesktop.Notifications"),
+                "/org/freedesktop/Notifications",
+                Some("org.freedesktop.Notifications"),
+                "Notify",
+                // app name, replaces id, icon, summary, body, actions, hints, expiry (-1: server default)
+                &("jb", 0u32, "", summary, body, actions, hints, -1i32),
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    async fn send_dbus(_summary: &str, _body: &str, _urgent: bool) -> Result<()> {
+        bail!("no D-Bus on this platform")
+    }
+
+    async fn send_notify_send(summary: &str, body: &str, urgent: bool) -> Result<()> {
+        let status = Command::new("notify-send")
+            .args(["--app-name", "jb", "--urgency"])
+            .arg(if urgent { "critical" } else { "normal" })
+            .arg(summary)
+            .arg(body)
+            .kill_on_drop(true)
+            .status()
+            .await?;
+        if !status.success() {
+            bail!("exited with {status}");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod sender {
+    use anyhow::Result;
+
+    pub async fn send(_summary: &str, _body: &str, _urgent: bool) -> Result<&'static str> {
+        Ok("a test stand-in")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::test_support::test_state;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_message() {
+        let mut job = Job::new(
+            "ab12".into(),
+            "cargo build".into(),
+            "/p".into(),
+            "/p".into(),
+        );
+        job.name = Some("build".into());
+        job.status = Status::Failed;
+        job.exit_code = Some(2);
+        let (summary, body) = message(&job, Duration::from_secs(192));
+        assert_eq!(summary, "jb: build failed");
+        assert_eq!(body, "exit 2 after 3m 12s\ncargo build");
+
+        job.name = None;
+        job.status = Status::Stopped;
+        job.exit_code = None;
+        let (summary, body) = message(&job, Duration::from_secs(4000));
+        assert_eq!(summary, "jb: ab12 stopped");
+        assert_eq!(body, "stopped after 1h 6m\ncargo build");
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_job_notified_on_shutdown() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let mut job = Job::new("ab12".into(), "sleep 60".into(), "/p".into(), "/p".into());
+        job.status = Status::Interrupted;
+        job.notify = Some(true);
+        state.db.lock().unwrap().insert(&job).unwrap();
+        notify_interrupted(&state, vec!["ab12".into()]).await;
+        let history = state.db.lock().unwrap().history("ab12").unwrap();
+        let entry = history
+            .iter()
+            .find(|entry| entry.kind == HistoryKind::Notification)
+            .expect("notification recorded");
+        assert_eq!(entry.detail.as_deref(), Some("sent via a test stand-in"));
+    }
+}
@@ -0,0 +1,190 @@
+use crate::core::event::HistoryKind;
+use crate::core::hooks::{HookKind, HookRun};
+use crate::core::{Job, Paths, Status, kill_process_group};
+use crate::daemon::load_config;
+use crate::daemon::state::DaemonState;
+use chrono::Utc;
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+/// Longest a hook may run before its process group is killed
+const HOOK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Bytes of output kept from each hook run (the end of it)
+const HOOK_OUTPUT_BYTES: usize = 4096;
+
+/// Run the job's hooks and the global ones from config for the status the
+/// job ended with, one after another, and record each run on the job.
+pub async fn run_hooks(state: &Arc<DaemonState>, job_id: &str) {
+    // Counted as a client so an idle timeout doesn't kill a running hook
+    let _connection = state.track_connection();
+    let job = match state.get_job(job_id) {
+        Ok(Some(job)) => job,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to load job {job_id} for hooks: {e}");
+            return;
+        }
+    };
+    // Interrupted by a daemon shutdown: the daemon is about to exit
+    if job.status == Status::Interrupted {
+        return;
+    }
+    // Read per job so edits apply without a restart
+    let global = load_config(&state.paths, "hooks").hooks;
+
+    for kind in HookKind::for_status(job.status) {
+        let commands = [(job.hooks.get(kind), false), (global.get(kind), true)];
+        for (command, is_global) in commands {
+            let Some(command) = command else { continue };
+            let run = run_hook(&state.paths, &job, kind, command, is_global).await;
+            let db = state.db.lock().unwrap();
+            if let Err(e) = db.record_hook(&job.id, &run) {
+                warn!("Failed to record {kind} hook of job {}: {e}", job.id);
+            }
+            let outcome = match run.exit_code {
+                Some(code) => format!("{kind} exited {code}"),
+                None => format!("{kind} did not finish"),
+            };
+            db.record_event(&job.id, HistoryKind::Hook, Some(&outcome));
+        }
+    }
+}
+
+async fn run_hook(
+    paths: &Paths,
+    job: &Job,
+    kind: HookKind,
+    command: &str,
+    global: bool,
+) -> HookRun {
+    let started_at = Utc::now();
+    let finish = |exit_code, output| HookRun {
+        hook: kind,
+        command: command.to_string(),
+        global,
+        started_at,
+        exit_code,
+        output,
+    };
+    // A job that failed to start may have no usable directory
+    let cwd = if job.cwd.is_dir() {
+        job.cwd.clone()
+    } else {
+        paths.root().to_path_buf()
+    };
+
+    let child = Command::new("sh")
+        .arg("-c")
+        .arg(command)
+        .current_dir(cwd)
+        .envs(hook_env(paths, job, kind))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn();
+    let child = match child {
+        Ok(child) => child,
+        Err(e) => return finish(None, format!("failed to start: {e}")),
+    };
+    let pid = child.id();
+
+    info!("Running {kind} hook of job {}", job.id);
+    match tokio::time::timeout(HOOK_TIMEOUT, child.wait_with_output()).await {
+        Ok(Ok(output)) => {
+            let mut text = output.stdout;
+            text.extend_from_slice(&output.stderr);
+            finish(output.status.code(), tail(&text))
+        }
+        Ok(Err(e)) => finish(None, format!("failed: {e}")),
+        Err(_) => {
+            if let Some(pid) = pid {
+                kill_process_group(pid, true);
+            }
+            warn!("{kind} hook of job {} timed out", job.id);
+            let secs = HOOK_TIMEOUT.as_secs();
+            finish(None, format!("killed after {secs}s"))
+        }
+    }
+}
+
+/// What a hook learns about the job from its environment
+fn hook_env(paths: &Paths, job: &Job, kind: HookKind) -> Vec<(&'static str, String)> {
+    let exit_code = job.exit_code.map(|c| c.to_string()).unwrap_or_default();
+    vec![
+        ("JB_JOB_ID", job.id.clone()),
+        ("JB_JOB_NAME", job.name.clone().unwrap_or_default()),
+        ("JB_COMMAND", job.command.clone()),
+        ("JB_STATUS", job.status.to_string()),
+        ("JB_EXIT_CODE", exit_code),
+        (
+            "JB_LOG_PATH",
+            paths.log_file(&job.id).to_string_lossy().to_string(),
+        ),
+        ("JB_HOOK", kind.to_string()),
+    ]
+}
+
+/// The last `HOOK_OUTPUT_BYTES` of `output` as text
+fn tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(HOOK_OUTPUT_BYTES);
+    String::from_utf8_lossy(&output[start..]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::hooks::Hooks;
+    use crate::daemon::test_support::test_state;
+    use tempfile::TempDir;
+
+    fn setup(hooks: Hooks, status: Status, exit_code: Option<i32>) -> (Arc<DaemonState>, TempDir) {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let mut job = Job::new(
+            "ab12".into(),
+            "make".into(),
+            tmp.path().to_path_buf(),
+            tmp.path().to_path_buf(),
+        )
+        .with_hooks(hooks);
+        job.status = status;
+        job.exit_code = exit_code;
+        state.db.lock().unwrap().insert(&job).unwrap();
+        (state, tmp)
+    }
+
+    #[tokio::test]
+    async fn test_hooks_run_with_job_env() {
+        let hooks = Hooks {
+            on_success: Some("echo never".into()),
+            on_failure: Some("echo \"$JB_JOB_ID $JB_STATUS $JB_EXIT_CODE\"; exit 7".into()),
+            on_exit: Some("echo \"$JB_HOOK\" >&2".into()),
+        };
+        let (state, _tmp) = setup(hooks, Status::Failed, Some(2));
+        run_hooks(&state, "ab12").await;
+
+        let runs = state.db.lock().unwrap().hook_runs("ab12").unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].hook, HookKind::OnFailure);
+        assert_eq!(runs[0].output, "ab12 failed 2\n");
+        assert_eq!(runs[0].exit_code, Some(7));
+        assert!(!runs[0].succeeded());
+        assert_eq!(runs[1].hook, HookKind::OnExit);
+        assert_eq!(runs[1].output, "on_exit\n");
+        assert!(runs[1].succeeded());
+
+        let history = state.db.lock().unwrap().history("ab12").unwrap();
+        assert_eq!(history[0].detail.as_deref(), Some("on_failure exited 7"));
+    }
+
+    #[test]
+    fn test_output_tail_kept() {
+        let output = vec![b'x'; HOOK_OUTPUT_BYTES + 10];
+        assert_eq!(tail(&output).len(), HOOK_OUTPUT_BYTES);
+    }
+}
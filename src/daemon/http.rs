@@ -0,0 +1,793 @@
+use crate::core::config::HttpListen;
+use crate::core::event::EventFilter;
+use crate::core::hooks::Hooks;
+use crate::core::ipc::{ErrorCode, ErrorReply, Request, Response};
+use crate::core::output::{self, LogSize, Segment};
+use crate::core::webhook::Webhook;
+use crate::core::{Job, Status, detect_project};
+use crate::daemon::load_config;
+use crate::daemon::server::{ShutdownTx, handle_request};
+use crate::daemon::state::DaemonState;
+use crate::error::Error;
+use axum::Router;
+use axum::body::{Body, Bytes};
+use axum::extract::{Json, Path, Query, Request as HttpRequest, State};
+use axum::http::{HeaderMap, StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::{IntoResponse, Response as HttpResponse};
+use axum::routing::{get, post};
+use rand::Rng;
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::fs::{OpenOptions, Permissions};
+use std::io::{Read, Write};
+use std::os::unix::fs::{FileTypeExt, OpenOptionsExt, PermissionsExt};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::{TcpListener, UnixListener};
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::{BroadcastStream, ReceiverStream};
+use tracing::{info, warn};
+
+/// Size of the pieces a log is streamed in
+const LOG_CHUNK: usize = 64 * 1024;
+
+/// Shared by every handler
+#[derive(Clone)]
+struct Api {
+    state: Arc<DaemonState>,
+    shutdown_tx: ShutdownTx,
+    /// Every request must carry it as `Authorization: Bearer <token>`
+    token: Arc<str>,
+}
+
+/// Serve the HTTP API on `listen` until the task is dropped. Requests go
+/// through `handle_request`, as on the daemon socket.
+pub(super) async fn serve(
+    listen: HttpListen,
+    state: Arc<DaemonState>,
+    shutdown_tx: ShutdownTx,
+) -> anyhow::Result<()> {
+    let token = load_token(&state.paths.http_token())?;
+    let app = router(state, shutdown_tx, token);
+    match listen {
+        HttpListen::Tcp(addr) => {
+            let listener = TcpListener::bind(addr).await?;
+            info!("HTTP API on http://{addr}");
+            axum::serve(listener, app).await?;
+        }
+        HttpListen::Unix(path) => {
+            remove_stale_socket(&path)?;
+            let listener = UnixListener::bind(&path)?;
+            info!("HTTP API on {}", path.display());
+            axum::serve(listener, app).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove a socket left at `path` by a daemon that didn't shut down cleanly.
+/// Anything else there is likely a typo in `[http] listen`, so it is kept.
+fn remove_stale_socket(path: &std::path::Path) -> anyhow::Result<()> {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => Ok(std::fs::remove_file(path)?),
+        Ok(_) => anyhow::bail!(
+            "[http] listen: {} exists and is not a socket",
+            path.display()
+        ),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn router(state: Arc<DaemonState>, shutdown_tx: ShutdownTx, token: String) -> Router {
+    let api = Api {
+        state,
+        shutdown_tx,
+        token: token.into(),
+    };
+    Router::new()
+        .route("/daemon", get(daemon_info))
+        .route("/jobs", get(list_jobs).post(run_job))
+        .route("/jobs/{id}", get(get_job))
+        .route("/jobs/{id}/stop", post(stop_job))
+        .route("/jobs/{id}/wait", get(wait_job))
+        .route("/jobs/{id}/logs", get(job_logs))
+        .route("/events", get(events))
+        .layer(middleware::from_fn_with_state(api.clone(), authorize))
+        .with_state(api)
+}
+
+/// The API's token, created on first use. A loopback port is open to every
+/// local user and to web pages, so the file is kept private to the user.
+fn load_token(path: &std::path::Path) -> std::io::Result<String> {
+    if let Ok(token) = std::fs::read_to_string(path)
+        && !token.trim().is_empty()
+    {
+        std::fs::set_permissions(path, Permissions::from_mode(0o600))?;
+        return Ok(token.trim().to_string());
+    }
+    let bytes: [u8; 32] = rand::rng().random();
+    let token: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(Permissions::from_mode(0o600))?;
+    writeln!(file, "{token}")?;
+    info!("Created HTTP API token in {}", path.display());
+    Ok(token)
+}
+
+/// Let a request through only with the token and a local `Host`. Checking the
+/// host stops DNS rebinding: a page whose name resolves to 127.0.0.1 still
+/// sends its own name.
+async fn authorize(
+    State(api): State<Api>,
+    request: HttpRequest,
+    next: Next,
+) -> Result<HttpResponse, ApiError> {
+    api.check_access(request.headers())?;
+    Ok(next.run(request).await)
+}
+
+/// `localhost`, `127.0.0.1` or `[::1]`, with or without a port
+fn is_local_host(host: &str) -> bool {
+    let name = match host.rsplit_once(':') {
+        Some((name, port)) if port.parse::<u16>().is_ok() => name,
+        _ => host,
+    };
+    matches!(name, "localhost" | "127.0.0.1" | "[::1]")
+}
+
+/// Compare without returning early, so timing doesn't reveal the token
+fn same_secret(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn daemon_info(State(api): State<Api>) -> HttpResponse {
+    match api.handle(Request::Ping).await {
+        Response::Pong {
+            pid,
+            uptime_secs,
+            running_jobs,
+            total_jobs,
+        } => Json(serde_json::json!({
+            "pid": pid,
+            "uptime_secs": uptime_secs,
+            "running_jobs": running_jobs,
+            "total_jobs": total_jobs,
+        }))
+        .into_response(),
+        other => reply(other, StatusCode::OK),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    status: Option<String>,
+    limit: Option<usize>,
+}
+
+async fn list_jobs(
+    State(api): State<Api>,
+    Query(query): Query<ListQuery>,
+) -> Result<HttpResponse, ApiError> {
+    if let Some(status) = &query.status {
+        status
+            .parse::<Status>()
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+    }
+    let request = Request::List {
+        status: query.status,
+        limit: query.limit,
+    };
+    Ok(reply(api.handle(request).await, StatusCode::OK))
+}
+
+/// Body of `POST /jobs`; the same fields as `Request::Run`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RunBody {
+    command: String,
+    name: Option<String>,
+    /// Absolute working directory
+    cwd: PathBuf,
+    /// Defaults to the project containing `cwd`
+    project: Option<PathBuf>,
+    timeout_secs: Option<u64>,
+    idempotency_key: Option<String>,
+    /// Log limits default to `[logs]` in config, as for `jb run`
+    log_max_size: Option<u64>,
+    log_keep: Option<u32>,
+    on_success: Option<String>,
+    on_failure: Option<String>,
+    on_exit: Option<String>,
+    #[serde(default)]
+    webhooks: Vec<Webhook>,
+    /// Unset follows `[notify] default` in config
+    notify: Option<bool>,
+}
+
+async fn run_job(
+    State(api): State<Api>,
+    Json(body): Json<RunBody>,
+) -> Result<HttpResponse, ApiError> {
+    if !body.cwd.is_absolute() {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            "cwd must be an absolute path",
+        ));
+    }
+    let (log_max_size, log_keep) = log_defaults(&api.state, body.log_max_size, body.log_keep);
+    let project = body.project.unwrap_or_else(|| detect_project(&body.cwd));
+    let request = Request::Run {
+        command: body.command,
+        name: body.name,
+        cwd: body.cwd.to_string_lossy().to_string(),
+        project: project.to_string_lossy().to_string(),
+        timeout_secs: body.timeout_secs,
+        idempotency_key: body.idempotency_key,
+        log_max_size,
+        log_keep,
+        hooks: Box::new(Hooks {
+            on_success: body.on_success,
+            on_failure: body.on_failure,
+            on_exit: body.on_exit,
+        }),
+        webhooks: body.webhooks,
+        notify: body.notify,
+    };
+    Ok(reply(api.handle(request).await, StatusCode::CREATED))
+}
+
+/// Log limits from the request, or from config where it doesn't set them
+fn log_defaults(
+    state: &DaemonState,
+    max_size: Option<u64>,
+    keep: Option<u32>,
+) -> (Option<u64>, Option<u32>) {
+    if max_size.is_some() && keep.is_some() {
+        return (max_size, keep);
+    }
+    let logs = load_config(&state.paths, "log limits").logs;
+    match logs.max_size_bytes() {
+        Ok(config_max) => (max_size.or(config_max), keep.or(logs.keep)),
+        Err(e) => {
+            warn!("Ignoring config for log limits: {e:#}");
+            (max_size, keep)
+        }
+    }
+}
+
+async fn get_job(State(api): State<Api>, Path(id): Path<String>) -> Result<HttpResponse, ApiError> {
+    let job = api.resolve(&id)?;
+    let response = api.handle(Request::Status { id: job.id }).await;
+    Ok(reply(response, StatusCode::OK))
+}
+
+#[derive(Debug, Deserialize)]
+struct StopQuery {
+    #[serde(default)]
+    force: bool,
+}
+
+async fn stop_job(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<StopQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let job = api.resolve(&id)?;
+    let request = Request::Stop {
+        id: job.id.clone(),
+        force: query.force,
+    };
+    let response = match api.handle(request).await {
+        // Answer with the stopped job rather than an empty body
+        Response::Ok => api.handle(Request::Status { id: job.id }).await,
+        other => other,
+    };
+    Ok(reply(response, StatusCode::OK))
+}
+
+#[derive(Debug, Deserialize)]
+struct WaitQuery {
+    /// Seconds to wait before giving up
+    timeout: Option<u64>,
+}
+
+async fn wait_job(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    Query(query): Query<WaitQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let job = api.resolve(&id)?;
+    let request = Request::Wait {
+        id: job.id,
+        timeout_secs: query.timeout,
+    };
+    Ok(reply(api.handle(request).await, StatusCode::OK))
+}
+
+/// A job's retained output as plain text. A single `Range: bytes=...` is
+/// honoured, over the same bytes as the whole response.
+async fn job_logs(
+    State(api): State<Api>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<HttpResponse, ApiError> {
+    let job = api.resolve(&id)?;
+    let active = Segment::active(&api.state.paths, &job.id);
+    let total = if active.exists() {
+        LogSize::measure(&active).map_err(ApiError::internal)?.bytes
+    } else {
+        0
+    };
+
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map_or(ByteRange::Full, |v| ByteRange::parse(v, total));
+    let (status, start, len) = match range {
+        ByteRange::Full => (StatusCode::OK, 0, total),
+        ByteRange::Partial { start, end } => (StatusCode::PARTIAL_CONTENT, start, end - start + 1),
+        ByteRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{total}"))],
+            )
+                .into_response());
+        }
+    };
+
+    let mut response = HttpResponse::builder()
+        .status(status)
+        .header(header::CONTENT_TYPE, "text/plain; charset=utf-8")
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::CONTENT_LENGTH, len);
+    if status == StatusCode::PARTIAL_CONTENT {
+        let end = start + len - 1;
+        response = response.header(
+            header::CONTENT_RANGE,
+            format!("bytes {start}-{end}/{total}"),
+        );
+    }
+    let body = if len == 0 {
+        Body::empty()
+    } else {
+        Body::from_stream(stream_log(active, start, len))
+    };
+    response.body(body).map_err(ApiError::internal)
+}
+
+/// `len` bytes of a job's log from `start`, read on a blocking thread.
+fn stream_log(active: Segment, start: u64, len: u64) -> ReceiverStream<std::io::Result<Bytes>> {
+    let (tx, rx) = tokio::sync::mpsc::channel(4);
+    tokio::task::spawn_blocking(move || {
+        let read = || -> std::io::Result<()> {
+            let mut log = output::open_log(&active).map_err(std::io::Error::other)?;
+            std::io::copy(&mut (&mut log).take(start), &mut std::io::sink())?;
+            let mut log = log.take(len);
+            let mut buf = vec![0u8; LOG_CHUNK];
+            loop {
+                let n = log.read(&mut buf)?;
+                if n == 0 {
+                    return Ok(());
+                }
+                if tx
+                    .blocking_send(Ok(Bytes::copy_from_slice(&buf[..n])))
+                    .is_err()
+                {
+                    // The client went away
+                    return Ok(());
+                }
+            }
+        };
+        if let Err(e) = read() {
+            let _ = tx.blocking_send(Err(e));
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+#[derive(Debug, Deserialize)]
+struct EventsQuery {
+    /// Only events of this job
+    id: Option<String>,
+    project: Option<PathBuf>,
+    /// Glob on the job name
+    name: Option<String>,
+    status: Option<Status>,
+}
+
+/// Server-sent lifecycle events (`created`, `started`, `finished`,
+/// `stopped`), each with the event as JSON.
+async fn events(
+    State(api): State<Api>,
+    Query(query): Query<EventsQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let id = match query.id {
+        Some(id) => Some(api.resolve(&id)?.id),
+        None => None,
+    };
+    let filter = EventFilter {
+        project: query.project,
+        name: query.name,
+        status: query.status,
+    };
+    // An open stream keeps the daemon from exiting idle, like a socket client
+    let guard = api.state.track_connection();
+    let stream = BroadcastStream::new(api.state.watch_events()).filter_map(move |event| {
+        let _ = &guard;
+        // A watcher that fell behind misses the oldest events
+        let event = event.ok()?;
+        if !filter.matches(&event) || id.as_ref().is_some_and(|id| *id != event.job.id) {
+            return None;
+        }
+        let sse = Event::default()
+            .event(event.kind.as_str())
+            .json_data(&event)
+            .ok()?;
+        Some(Ok::<_, Infallible>(sse))
+    });
+    Ok(Sse::new(stream)
+        .keep_alive(KeepAlive::default())
+        .into_response())
+}
+
+impl Api {
+    fn check_access(&self, headers: &HeaderMap) -> Result<(), ApiError> {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        if !header(header::HOST).is_some_and(is_local_host) {
+            return Err(ApiError::new(
+                StatusCode::FORBIDDEN,
+                "Host must be localhost or 127.0.0.1",
+            ));
+        }
+        let token = header(header::AUTHORIZATION).and_then(|value| value.strip_prefix("Bearer "));
+        if !token.is_some_and(|token| same_secret(token.trim(), &self.token)) {
+            let path = self.state.paths.http_token();
+            let message = format!("missing or wrong bearer token (see {})", path.display());
+            return Err(ApiError::new(StatusCode::UNAUTHORIZED, message));
+        }
+        Ok(())
+    }
+
+    async fn handle(&self, request: Request) -> Response {
+        let _guard = self.state.track_connection();
+        handle_request(request, &self.state, &self.shutdown_tx).await
+    }
+
+    /// The job `id` names (an ID or a name)
+    fn resolve(&self, id: &str) -> Result<Job, ApiError> {
+        let resolved = self.state.db.lock().unwrap().resolve(id);
+        resolved.map_err(|e| match e.downcast_ref::<Error>() {
+            Some(Error::NotFound(_)) => ApiError::new(StatusCode::NOT_FOUND, e),
+            _ => ApiError::internal(e),
+        })
+    }
+}
+
+/// The HTTP response for a daemon response: `success` with the job or jobs as
+/// JSON, or an error status for an error reply.
+fn reply(response: Response, success: StatusCode) -> HttpResponse {
+    match response {
+        Response::Job(job) | Response::Finished(job) => (success, Json(job)).into_response(),
+        Response::Jobs(jobs) => (success, Json(jobs)).into_response(),
+        Response::Ok => StatusCode::NO_CONTENT.into_response(),
+        Response::Failed(error) => ApiError::from_reply(error).into_response(),
+        // Only sent to socket clients that skipped the handshake
+        Response::Error(message) => ApiError::internal(message).into_response(),
+        Response::UserError(message) => {
+            ApiError::new(StatusCode::BAD_REQUEST, message).into_response()
+        }
+        Response::Output(_)
+        | Response::Event(_)
+        | Response::Pong { .. }
+        | Response::Hello { .. } => ApiError::internal("unexpected response").into_response(),
+    }
+}
+
+/// A failed request, sent as `{"error": message}`, with `"code"` when the
+/// daemon gave one
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+    code: Option<ErrorCode>,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        Self {
+            status,
+            message: message.to_string(),
+            code: None,
+        }
+    }
+
+    fn internal(message: impl ToString) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, message)
+    }
+
+    /// The status for an error reply from the daemon
+    fn from_reply(error: ErrorReply) -> Self {
+        let status = match error.code {
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::NameInUse | ErrorCode::NotRunning => StatusCode::CONFLICT,
+            ErrorCode::Timeout => StatusCode::REQUEST_TIMEOUT,
+            ErrorCode::Rejected | ErrorCode::UnsupportedVersion => StatusCode::BAD_REQUEST,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self {
+            status,
+            message: error.message,
+            code: Some(error.code),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> HttpResponse {
+        let mut body = serde_json::json!({ "error": self.message });
+        if let Some(code) = self.code {
+            body["code"] = serde_json::json!(code);
+        }
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// What part of a `total`-byte body a `Range` header asks for
+#[derive(Debug, PartialEq, Eq)]
+enum ByteRange {
+    /// No usable range: send everything
+    Full,
+    /// Bytes `start..=end`
+    Partial { start: u64, end: u64 },
+    /// The range starts past the end
+    Unsatisfiable,
+}
+
+impl ByteRange {
+    /// Parse a single `bytes=` range; anything else (other units, several
+    /// ranges, bad syntax) is ignored, as HTTP allows.
+    fn parse(value: &str, total: u64) -> Self {
+        let Some(spec) = value.trim().strip_prefix("bytes=") else {
+            return Self::Full;
+        };
+        if spec.contains(',') {
+            return Self::Full;
+        }
+        let Some((first, last)) = spec.trim().split_once('-') else {
+            return Self::Full;
+        };
+        let (first, last) = (first.trim(), last.trim());
+
+        // Suffix range: the last N bytes
+        if first.is_empty() {
+            return match last.parse::<u64>() {
+                Ok(0) => Self::Unsatisfiable,
+                Ok(_) if total == 0 => Self::Unsatisfiable,
+                Ok(n) => Self::Partial {
+                    start: total.saturating_sub(n),
+                    end: total - 1,
+                },
+                Err(_) => Self::Full,
+            };
+        }
+        let Ok(start) = first.parse::<u64>() else {
+            return Self::Full;
+        };
+        let end = if last.is_empty() {
+            total.saturating_sub(1)
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => end.min(total.saturating_sub(1)),
+                _ => return Self::Full,
+            }
+        };
+        if start >= total {
+            return Self::Unsatisfiable;
+        }
+        Self::Partial { start, end }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::output::Stream;
+    use crate::daemon::capture::OutputWriter;
+    use crate::daemon::test_support::test_state;
+    use tempfile::TempDir;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    #[test]
+    fn test_byte_range_parse() {
+        let partial = |start, end| ByteRange::Partial { start, end };
+        assert_eq!(ByteRange::parse("bytes=0-9", 100), partial(0, 9));
+        assert_eq!(ByteRange::parse("bytes=90-", 100), partial(90, 99));
+        assert_eq!(ByteRange::parse("bytes=-10", 100), partial(90, 99));
+        assert_eq!(ByteRange::parse("bytes=-500", 100), partial(0, 99));
+        assert_eq!(ByteRange::parse("bytes=50-500", 100), partial(50, 99));
+        assert_eq!(
+            ByteRange::parse("bytes=100-", 100),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(ByteRange::parse("bytes=-5", 0), ByteRange::Unsatisfiable);
+        assert_eq!(ByteRange::parse("bytes=0-1,5-6", 100), ByteRange::Full);
+        assert_eq!(ByteRange::parse("bytes=9-2", 100), ByteRange::Full);
+        assert_eq!(ByteRange::parse("lines=1-2", 100), ByteRange::Full);
+    }
+
+    const TOKEN: &str = "s3cret";
+
+    /// Send a raw HTTP/1.1 request to the API with the token and return the
+    /// status line, headers and body as text.
+    async fn http(state: &Arc<DaemonState>, request: &str) -> String {
+        let (line, rest) = request.split_once("\r\n").unwrap();
+        let request = format!("{line}\r\nAuthorization: Bearer {TOKEN}\r\n{rest}");
+        unauthorized(state, &request).await
+    }
+
+    /// Send a raw HTTP/1.1 request to the API as it is
+    async fn unauthorized(state: &Arc<DaemonState>, request: &str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = router(
+            Arc::clone(state),
+            tokio::sync::watch::channel(None).0,
+            TOKEN.into(),
+        );
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    fn setup() -> (Arc<DaemonState>, TempDir) {
+        let tmp = TempDir::new().unwrap();
+        (test_state(&tmp), tmp)
+    }
+
+    fn insert_finished(state: &DaemonState, id: &str, output: &[u8]) {
+        let mut job = Job::new(id.into(), "make".into(), "/tmp".into(), "/tmp".into());
+        job.status = Status::Completed;
+        state.db.lock().unwrap().insert(&job).unwrap();
+        let segment = Segment::active(&state.paths, id);
+        let mut writer = OutputWriter::create(segment, None).unwrap();
+        writer.write_chunk(Stream::Stdout, output).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_jobs_and_not_found() {
+        let (state, _tmp) = setup();
+        insert_finished(&state, "abc1", b"hi\n");
+
+        let response = http(
+            &state,
+            "GET /jobs HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+        assert!(response.contains(r#""id":"abc1""#));
+
+        let response = http(
+            &state,
+            "GET /jobs/nope HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 404"), "{response}");
+
+        let response = http(
+            &state,
+            "POST /jobs/abc1/stop HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 409"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_logs_range() {
+        let (state, _tmp) = setup();
+        insert_finished(&state, "abc1", b"0123456789");
+
+        let response = http(
+            &state,
+            "GET /jobs/abc1/logs HTTP/1.1\r\nHost: localhost\r\nRange: bytes=2-4\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 206"), "{response}");
+        assert!(
+            response.contains("content-range: bytes 2-4/10"),
+            "{response}"
+        );
+        assert!(response.ends_with("\r\n\r\n234"), "{response}");
+
+        let response = http(
+            &state,
+            "GET /jobs/abc1/logs HTTP/1.1\r\nHost: localhost\r\nRange: bytes=20-\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 416"), "{response}");
+
+        let response = http(
+            &state,
+            "GET /jobs/abc1/logs HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.ends_with("\r\n\r\n0123456789"), "{response}");
+    }
+
+    #[tokio::test]
+    async fn test_requires_token_and_local_host() {
+        let (state, _tmp) = setup();
+
+        let response = unauthorized(
+            &state,
+            "GET /jobs HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+        let response = unauthorized(
+            &state,
+            "GET /jobs HTTP/1.1\r\nHost: localhost\r\nAuthorization: Bearer s3creT\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 401"), "{response}");
+
+        // DNS rebinding: the right address, a foreign name
+        let response = http(
+            &state,
+            "POST /jobs HTTP/1.1\r\nHost: evil.example:7878\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+        assert!(response.starts_with("HTTP/1.1 403"), "{response}");
+
+        for host in ["localhost:7878", "127.0.0.1", "[::1]:7878"] {
+            let request =
+                format!("GET /jobs HTTP/1.1\r\nHost: {host}\r\nConnection: close\r\n\r\n");
+            let response = http(&state, &request).await;
+            assert!(response.starts_with("HTTP/1.1 200"), "{host}: {response}");
+        }
+    }
+
+    #[test]
+    fn test_token_created_private_and_reused() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("http.token");
+        let token = load_token(&path).unwrap();
+        assert_eq!(token.len(), 64);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(load_token(&path).unwrap(), token);
+    }
+
+    #[test]
+    fn test_only_stale_sockets_removed() {
+        let tmp = TempDir::new().unwrap();
+        let file = tmp.path().join("config.toml");
+        std::fs::write(&file, "keep me").unwrap();
+        let err = remove_stale_socket(&file).unwrap_err().to_string();
+        assert!(err.contains("config.toml"), "{err}");
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep me");
+
+        let socket = tmp.path().join("http.sock");
+        drop(std::os::unix::net::UnixListener::bind(&socket).unwrap());
+        remove_stale_socket(&socket).unwrap();
+        assert!(!socket.exists());
+        remove_stale_socket(&socket).unwrap();
+    }
+}
@@ -0,0 +1,144 @@
+use crate::core::Paths;
+use crate::core::paths::rotated_path;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Rotate the daemon log once it grows past this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+
+/// Number of rotated copies to keep (`daemon.log.1` .. `daemon.log.N`)
+const KEEP_ROTATED: usize = 3;
+
+/// Route the daemon's `tracing` output to `~/.jb/daemon.log`.
+/// `RUST_LOG` overrides the default `info` level.
+pub fn init(paths: &Paths) -> anyhow::Result<()> {
+    let writer = RotatingFile::open(paths.daemon_log(), MAX_LOG_BYTES, KEEP_ROTATED)?;
+
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .with_ansi(false)
+        .with_writer(Mutex::new(writer))
+        .init();
+
+    Ok(())
+}
+
+/// Append-only log file that rotates itself when it exceeds `max_bytes`.
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: PathBuf, max_bytes: u64, keep: usize) -> std::io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let written = file.metadata()?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            max_bytes,
+            keep,
+        })
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        // Shift daemon.log.(N-1) -> daemon.log.N, ..., daemon.log -> daemon.log.1
+        for i in (1..self.keep).rev() {
+            let from = rotated_path(&self.path, i);
+            if from.exists() {
+                std::fs::rename(&from, rotated_path(&self.path, i + 1))?;
+            }
+        }
+        if self.keep > 0 {
+            std::fs::rename(&self.path, rotated_path(&self.path, 1))?;
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&self.path)?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.written > 0 && self.written + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        let n = self.file.write(buf)?;
+        self.written += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_rotates_when_size_exceeded() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("daemon.log");
+        let mut log = RotatingFile::open(path.clone(), 10, 2).unwrap();
+
+        log.write_all(b"first-line\n").unwrap();
+        log.write_all(b"second\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(
+            std::fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "first-line\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_at_most_n_rotated_copies() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("daemon.log");
+        let mut log = RotatingFile::open(path.clone(), 4, 2).unwrap();
+
+        for line in ["aaaa\n", "bbbb\n", "cccc\n", "dddd\n"] {
+            log.write_all(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "dddd\n");
+        assert_eq!(
+            std::fs::read_to_string(rotated_path(&path, 1)).unwrap(),
+            "cccc\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(rotated_path(&path, 2)).unwrap(),
+            "bbbb\n"
+        );
+        assert!(!rotated_path(&path, 3).exists());
+    }
+
+    #[test]
+    fn test_appends_to_existing_file() {
+        let tmp = TempDir::new().unwrap();
+        let path = tmp.path().join("daemon.log");
+        std::fs::write(&path, "old\n").unwrap();
+
+        let mut log = RotatingFile::open(path.clone(), 1024, 2).unwrap();
+        log.write_all(b"new\n").unwrap();
+
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old\nnew\n");
+    }
+}
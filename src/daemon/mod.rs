@@ -1,11 +1,24 @@
+pub mod capture;
+pub mod compress;
+pub mod desktop;
+pub mod hooks;
+pub mod http;
+pub mod logging;
+pub mod redact;
 pub mod server;
 pub mod spawner;
 pub mod state;
+pub mod subscribe;
+#[cfg(test)]
+mod test_support;
+pub mod webhook;
 
-use crate::core::Paths;
+use crate::core::config::HttpListen;
+use crate::core::{Config, Database, Paths};
 use anyhow::{Result, bail};
 use std::sync::Arc;
-use tracing::info;
+use std::time::Duration;
+use tracing::{info, warn};
 
 pub async fn run() -> Result<()> {
     let paths = Paths::new()?;
@@ -28,18 +41,62 @@ pub async fn run() -> Result<()> {
         std::fs::remove_file(paths.socket())?;
     }
 
+    let config = load_config(&paths, "the daemon");
+    let idle_timeout = match config.daemon.idle_timeout_secs() {
+        Ok(secs) => secs.map(Duration::from_secs),
+        Err(e) => {
+            warn!("Ignoring config: {e:#}");
+            None
+        }
+    };
+    let http = config.http.listen_on(&paths).unwrap_or_else(|e| {
+        warn!("Ignoring config: {e:#}");
+        None
+    });
+    if let Some(t) = idle_timeout {
+        info!("Idle timeout: {}s", t.as_secs());
+    }
+
     let state = Arc::new(state::DaemonState::new(&paths)?);
+    index_finished_jobs(&paths);
 
     // Run the server
-    let result = server::run(paths.clone(), state.clone()).await;
+    let result = server::run(paths.clone(), state.clone(), idle_timeout, http.clone()).await;
 
     // Cleanup
     let _ = std::fs::remove_file(paths.pid_file());
     let _ = std::fs::remove_file(paths.socket());
+    if let Some(HttpListen::Unix(path)) = http {
+        let _ = std::fs::remove_file(path);
+    }
 
     result
 }
 
+/// The config file, or the defaults if it can't be loaded. The daemon has no
+/// terminal, so a bad config is logged rather than fatal; `jb run` reports it
+/// before a job gets this far. `purpose` names the user in the warning.
+pub fn load_config(paths: &Paths, purpose: &str) -> Config {
+    Config::load(paths).unwrap_or_else(|e| {
+        warn!("Ignoring config for {purpose}: {e:#}");
+        Config::default()
+    })
+}
+
+/// Index the output of finished jobs the daemon didn't index when they ended
+/// (under an older jb, or when no daemon was running), in the background so
+/// startup isn't held up. Read-only commands leave the index to the daemon.
+fn index_finished_jobs(paths: &Paths) {
+    let paths = paths.clone();
+    tokio::task::spawn_blocking(move || {
+        match Database::open(&paths).and_then(|db| db.index_finished(&paths)) {
+            Ok(0) => {}
+            Ok(n) => info!("Indexed the output of {n} finished job(s)"),
+            Err(e) => warn!("Failed to index finished jobs: {e:#}"),
+        }
+    });
+}
+
 /// Check if an existing daemon is running. Returns the PID if so.
 fn check_existing_daemon(paths: &Paths) -> Option<u32> {
     let pid_file = paths.pid_file();
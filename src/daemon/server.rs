@@ -1,26 +1,65 @@
-use crate::core::ipc::{Request, Response};
+use crate::core::config::HttpListen;
+use crate::core::ipc::{
+    ErrorCode, ErrorReply, FEATURES, MIN_PROTOCOL_VERSION, NewJob, PROTOCOL_VERSION, Request,
+    Response,
+};
 use crate::core::{Paths, Status};
-use crate::daemon::spawner;
 use crate::daemon::state::DaemonState;
+use crate::daemon::{desktop, http, spawner, subscribe};
 use anyhow::Result;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{UnixListener, UnixStream};
 use tokio::sync::watch;
 use tracing::{error, info, warn};
 
-pub async fn run(paths: Paths, state: Arc<DaemonState>) -> Result<()> {
+/// How running jobs are treated when the daemon shuts down
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownMode {
+    /// Signal running jobs and mark them interrupted
+    InterruptJobs,
+    /// Leave running jobs alive; they are no longer tracked by this daemon
+    KeepJobs,
+}
+
+pub(super) type ShutdownTx = watch::Sender<Option<ShutdownMode>>;
+
+/// Run the accept loop until a shutdown is requested or, with `idle_timeout`,
+/// until the daemon has had nothing to do for that long. With `http`, the
+/// HTTP API is served alongside until then.
+pub async fn run(
+    paths: Paths,
+    state: Arc<DaemonState>,
+    idle_timeout: Option<Duration>,
+    http: Option<HttpListen>,
+) -> Result<()> {
     let listener = UnixListener::bind(paths.socket())?;
     info!("Listening on {}", paths.socket().display());
 
     // Shutdown signal channel
-    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (shutdown_tx, shutdown_rx) = watch::channel(None);
 
     // Spawn signal handler
     let shutdown_tx_clone = shutdown_tx.clone();
     tokio::spawn(async move {
         wait_for_shutdown_signal().await;
-        let _ = shutdown_tx_clone.send(true);
+        let _ = shutdown_tx_clone.send(Some(ShutdownMode::InterruptJobs));
+    });
+
+    // A failure here leaves the socket API running
+    let http_server = http.map(|listen| {
+        let state = state.clone();
+        let shutdown_tx = shutdown_tx.clone();
+        tokio::spawn(async move {
+            if let Err(e) = http::serve(listen, state, shutdown_tx).await {
+                error!("HTTP API failed: {e:#}");
+            }
+        })
+    });
+
+    let idle_check_interval = idle_timeout.map_or(Duration::MAX, |t| {
+        (t / 4).clamp(Duration::from_millis(10), Duration::from_secs(5))
     });
 
     loop {
@@ -30,7 +69,9 @@ pub async fn run(paths: Paths, state: Arc<DaemonState>) -> Result<()> {
                     Ok((stream, _addr)) => {
                         let state = state.clone();
                         let shutdown_tx = shutdown_tx.clone();
+                        let guard = state.track_connection();
                         tokio::spawn(async move {
+                            let _guard = guard;
                             if let Err(e) = handle_connection(stream, state, shutdown_tx).await {
                                 error!("Connection error: {}", e);
                             }
@@ -45,11 +86,29 @@ pub async fn run(paths: Paths, state: Arc<DaemonState>) -> Result<()> {
                 info!("Shutdown signal received, stopping daemon");
                 break;
             }
+            () = tokio::time::sleep(idle_check_interval), if idle_timeout.is_some() => {
+                if let Some(timeout) = idle_timeout
+                    && state.is_idle(timeout)
+                {
+                    info!("Idle for {}s, stopping daemon", timeout.as_secs());
+                    break;
+                }
+            }
         }
     }
 
-    // Mark running jobs as interrupted
-    state.interrupt_running_jobs();
+    let mode = shutdown_rx.borrow().unwrap_or(ShutdownMode::InterruptJobs);
+    match mode {
+        // Mark running jobs as interrupted
+        ShutdownMode::InterruptJobs => {
+            let interrupted = state.interrupt_running_jobs();
+            desktop::notify_interrupted(&state, interrupted).await;
+        }
+        ShutdownMode::KeepJobs => state.detach_running_jobs().await,
+    }
+    if let Some(http_server) = http_server {
+        http_server.abort();
+    }
 
     info!("Daemon shutdown complete");
     Ok(())
@@ -76,9 +135,9 @@ async fn wait_for_shutdown_signal() {
     }
 }
 
-async fn shutdown_signal(rx: &watch::Receiver<bool>) {
+async fn shutdown_signal(rx: &watch::Receiver<Option<ShutdownMode>>) {
     let mut rx = rx.clone();
-    while !*rx.borrow() {
+    while rx.borrow().is_none() {
         if rx.changed().await.is_err() {
             break;
         }
@@ -88,8 +147,10 @@ async fn shutdown_signal(rx: &watch::Receiver<bool>) {
 async fn handle_connection(
     mut stream: UnixStream,
     state: Arc<DaemonState>,
-    shutdown_tx: watch::Sender<bool>,
+    shutdown_tx: ShutdownTx,
 ) -> Result<()> {
+    // Until a `Hello`, the client is taken to predate the handshake
+    let mut version = 0;
     loop {
         let request = match read_message(&mut stream).await {
             Ok(Some(req)) => req,
@@ -100,7 +161,46 @@ async fn handle_connection(
             }
         };
 
-        let response = handle_request(request, &state, &shutdown_tx).await;
+        if let Request::Hello {
+            version: theirs, ..
+        } = request
+        {
+            let response = match hello(theirs) {
+                Ok(agreed) => {
+                    version = agreed;
+                    Response::Hello {
+                        version,
+                        features: FEATURES.iter().map(ToString::to_string).collect(),
+                    }
+                }
+                Err(reply) => Response::Failed(reply),
+            };
+            if let Err(e) = write_message(&mut stream, &response).await {
+                warn!("Write error: {}", e);
+                break;
+            }
+            continue;
+        }
+
+        // Subscriptions stream frames until they end or the client leaves
+        if let Request::Subscribe { id, from_offset } = request {
+            if let Err(e) =
+                subscribe::stream_output(&mut stream, &state, &id, from_offset, version).await
+            {
+                warn!("Subscription to {id} ended: {e}");
+            }
+            break;
+        }
+        if let Request::Events { filter } = request {
+            if let Err(e) = subscribe::stream_events(&mut stream, &state, &filter).await {
+                warn!("Event stream ended: {e}");
+            }
+            break;
+        }
+
+        let response = handle_request(request, &state, &shutdown_tx)
+            .await
+            .for_version(version);
 
         if let Err(e) = write_message(&mut stream, &response).await {
             warn!("Write error: {}", e);
@@ -111,6 +211,21 @@ async fn handle_connection(
     Ok(())
 }
 
+/// The protocol version to speak with a client that offers `theirs`: the
+/// older of the two, as long as we still support it
+fn hello(theirs: u32) -> Result<u32, ErrorReply> {
+    let agreed = theirs.min(PROTOCOL_VERSION);
+    if agreed < MIN_PROTOCOL_VERSION {
+        return Err(ErrorReply::new(
+            ErrorCode::UnsupportedVersion,
+            format!(
+                "Protocol version {theirs} is not supported (daemon speaks {MIN_PROTOCOL_VERSION} to {PROTOCOL_VERSION})"
+            ),
+        ));
+    }
+    Ok(agreed)
+}
+
 async fn read_message(stream: &mut UnixStream) -> Result<Option<Request>> {
     let mut len_buf = [0u8; 4];
     match stream.read_exact(&mut len_buf).await {
@@ -127,11 +242,10 @@ async fn read_message(stream: &mut UnixStream) -> Result<Option<Request>> {
     let mut buf = vec![0u8; len];
     stream.read_exact(&mut buf).await?;
 
-    let request: Request = serde_json::from_slice(&buf)?;
-    Ok(Some(request))
+    Ok(Some(Request::from_json(&buf)?))
 }
 
-async fn write_message(stream: &mut UnixStream, response: &Response) -> Result<()> {
+pub(super) async fn write_message(stream: &mut UnixStream, response: &Response) -> Result<()> {
     let data = serde_json::to_vec(response)?;
     #[allow(clippy::cast_possible_truncation)] // messages are always < 4GB
     let len = (data.len() as u32).to_be_bytes();
@@ -143,10 +257,10 @@ async fn write_message(stream: &mut UnixStream, response: &Response) -> Result<(
     Ok(())
 }
 
-async fn handle_request(
+pub(super) async fn handle_request(
     request: Request,
     state: &Arc<DaemonState>,
-    shutdown_tx: &watch::Sender<bool>,
+    shutdown_tx: &ShutdownTx,
 ) -> Response {
     match request {
         Request::Ping => Response::Pong {
@@ -156,9 +270,14 @@ async fn handle_request(
             total_jobs: state.total_jobs(),
         },
 
-        Request::Shutdown => {
-            info!("Shutdown requested via IPC");
-            let _ = shutdown_tx.send(true);
+        Request::Shutdown { keep_jobs } => {
+            info!("Shutdown requested via IPC (keep_jobs: {keep_jobs})");
+            let mode = if keep_jobs {
+                ShutdownMode::KeepJobs
+            } else {
+                ShutdownMode::InterruptJobs
+            };
+            let _ = shutdown_tx.send(Some(mode));
             Response::Ok
         }
 
@@ -169,31 +288,45 @@ async fn handle_request(
             project,
             timeout_secs,
             idempotency_key,
-        } => spawner::spawn_job(
-            state,
-            command,
-            name,
-            cwd,
-            project,
-            timeout_secs,
-            idempotency_key,
-        ),
+            log_max_size,
+            log_keep,
+            hooks,
+            webhooks,
+            notify,
+        } => {
+            let new = NewJob {
+                command,
+                name,
+                cwd,
+                project,
+                timeout_secs,
+                idempotency_key,
+                log_max_size,
+                log_keep,
+                hooks: *hooks,
+                webhooks,
+                notify,
+            };
+            spawner::spawn_job(state, new)
+        }
+
+        Request::RunMany { jobs } => spawner::spawn_jobs(state, jobs),
 
         Request::Stop { id, force } => match state.get_job(&id) {
             Ok(Some(job)) => {
                 if job.status != Status::Running {
-                    return Response::UserError(format!("Job {} is not running", job.short_id()));
+                    return ErrorReply::not_running(job.short_id()).into();
                 }
                 spawner::stop_job(state, &job.id, force)
             }
-            Ok(None) => Response::Error(format!("Job not found: {id}")),
-            Err(e) => Response::Error(e.to_string()),
+            Ok(None) => ErrorReply::not_found(&id).into(),
+            Err(e) => ErrorReply::internal(e.to_string()).into(),
         },
 
         Request::Status { id } => match state.get_job(&id) {
             Ok(Some(job)) => Response::Job(Box::new(job)),
-            Ok(None) => Response::Error(format!("Job not found: {id}")),
-            Err(e) => Response::Error(e.to_string()),
+            Ok(None) => ErrorReply::not_found(&id).into(),
+            Err(e) => ErrorReply::internal(e.to_string()).into(),
         },
 
         Request::List { status, limit } => {
@@ -201,10 +334,119 @@ async fn handle_request(
 
             match state.list_jobs(status_filter, limit) {
                 Ok(jobs) => Response::Jobs(jobs),
-                Err(e) => Response::Error(e.to_string()),
+                Err(e) => ErrorReply::internal(e.to_string()).into(),
             }
         }
 
         Request::Wait { id, timeout_secs } => spawner::wait_for_job(state, &id, timeout_secs).await,
+
+        // Handled by the connection loop
+        Request::Hello { .. } | Request::Subscribe { .. } | Request::Events { .. } => {
+            ErrorReply::internal("unexpected handshake or subscription").into()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::test_support::{new_job, test_state};
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_idle_daemon_exits() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let paths = state.paths.clone();
+
+        let result = tokio::time::timeout(
+            Duration::from_secs(5),
+            run(paths, state, Some(Duration::from_millis(200)), None),
+        )
+        .await;
+        assert!(result.is_ok(), "idle daemon should have exited");
+    }
+
+    #[tokio::test]
+    async fn test_running_job_keeps_daemon_alive() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let paths = state.paths.clone();
+        let resp = spawner::spawn_job(&state, new_job(&tmp, "sleep 2"));
+        assert!(matches!(resp, Response::Job(_)));
+
+        let server = tokio::spawn(run(paths, state, Some(Duration::from_millis(100)), None));
+        tokio::time::sleep(Duration::from_millis(800)).await;
+        assert!(
+            !server.is_finished(),
+            "daemon exited while a job was running"
+        );
+
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+        assert!(result.is_ok(), "daemon should exit once the job finishes");
+    }
+
+    #[tokio::test]
+    async fn test_open_connection_keeps_daemon_alive() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let paths = state.paths.clone();
+        let socket = paths.socket();
+
+        let server = tokio::spawn(run(paths, state, Some(Duration::from_millis(100)), None));
+        let mut conn = None;
+        for _ in 0..50 {
+            if let Ok(s) = UnixStream::connect(&socket).await {
+                conn = Some(s);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let conn = conn.expect("daemon socket never came up");
+
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(
+            !server.is_finished(),
+            "daemon exited with a client connected"
+        );
+
+        drop(conn);
+        let result = tokio::time::timeout(Duration::from_secs(5), server).await;
+        assert!(result.is_ok(), "daemon should exit after the client leaves");
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_legacy_replies() {
+        use crate::client::DaemonClient;
+        use crate::error::Error;
+
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let paths = state.paths.clone();
+        let server = tokio::spawn(run(paths.clone(), state, None, None));
+        let mut client = None;
+        for _ in 0..50 {
+            if let Ok(c) = DaemonClient::connect_to(&paths).await {
+                client = Some(c);
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let mut client = client.expect("daemon socket never came up");
+        assert_eq!(client.protocol_version(), PROTOCOL_VERSION);
+        assert!(client.supports("error_codes"));
+        let err = client.status("zzzz").await.unwrap_err();
+        assert!(matches!(err, Error::NotFound(id) if id == "zzzz"));
+
+        // Clients from before the handshake still get message-only replies
+        let mut legacy = DaemonClient::connect_without_handshake_to(&paths)
+            .await
+            .unwrap();
+        let err = legacy.status("zzzz").await.unwrap_err();
+        assert!(matches!(err, Error::Rejected(m) if m == "Job not found: zzzz"));
+
+        assert!(hello(0).is_err());
+        assert_eq!(hello(PROTOCOL_VERSION + 1).unwrap(), PROTOCOL_VERSION);
+        server.abort();
     }
 }
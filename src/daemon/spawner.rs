@@ -1,103 +1,238 @@
-use crate::core::ipc::Response;
-use crate::core::{Job, Status, kill_process_group};
-use crate::daemon::state::{DaemonState, RunningJob};
+use crate::core::config::RedactConfig;
+use crate::core::event::{HistoryKind, signal_detail};
+use crate::core::ipc::{ErrorReply, NewJob, Response};
+use crate::core::output::{self, LogLimit, Segment};
+use crate::core::{Database, Job, Paths, Status, kill_process_group};
+use crate::daemon::capture::{self, OutputWriter};
+use crate::daemon::load_config;
+use crate::daemon::redact::Redactor;
+use crate::daemon::state::{DaemonState, DetachRequest, OutputProgress, RunningJob};
+use crate::daemon::{compress, desktop, hooks, webhook};
+use std::collections::HashSet;
+use std::os::fd::AsFd;
+use std::os::unix::process::CommandExt;
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::fs::File;
-use tokio::process::Command;
-use tokio::sync::watch;
+use tokio::net::unix::pipe;
+use tokio::process::{ChildStderr, ChildStdout, Command};
+use tokio::sync::{oneshot, watch};
+use tokio::task::JoinHandle;
 use tracing::{error, info, warn};
 
-pub fn spawn_job(
-    state: &Arc<DaemonState>,
-    command: String,
-    name: Option<String>,
-    cwd: String,
-    project: String,
-    timeout_secs: Option<u64>,
-    idempotency_key: Option<String>,
-) -> Response {
-    // Check idempotency key and name uniqueness, generate ID
-    let id = {
-        let db = state.db.lock().unwrap();
+/// Create the job `new` describes and start it, answering `Job`. A job with an
+/// idempotency key that was used before gets the earlier job instead.
+pub fn spawn_job(state: &Arc<DaemonState>, new: NewJob) -> Response {
+    match create_jobs(state, vec![new]) {
+        Ok(mut jobs) => Response::Job(Box::new(jobs.remove(0))),
+        Err(refused) => refused.reply.into(),
+    }
+}
 
-        // Idempotency check
-        if let Some(ref key) = idempotency_key
-            && let Ok(Some(existing)) = db.get_by_idempotency_key(key)
-        {
-            return Response::Job(Box::new(existing));
+/// Create `jobs` together and start them, answering `Jobs` in the same order.
+/// Everything that could refuse one (a bad webhook, a name in use, a key or
+/// name given twice) is checked before any is created, and the new jobs are
+/// inserted in one transaction. Entries with a known idempotency key get the
+/// earlier job, as with `spawn_job`.
+pub fn spawn_jobs(state: &Arc<DaemonState>, jobs: Vec<NewJob>) -> Response {
+    match create_jobs(state, jobs) {
+        Ok(jobs) => Response::Jobs(jobs),
+        Err(Refused {
+            entry: Some(n),
+            reply,
+        }) => in_batch(n, reply),
+        Err(refused) => refused.reply.into(),
+    }
+}
+
+/// Why `create_jobs` created nothing
+struct Refused {
+    /// The entry at fault (counting from 0), unless the daemon itself failed
+    entry: Option<usize>,
+    reply: ErrorReply,
+}
+
+impl Refused {
+    fn entry(n: usize, reply: ErrorReply) -> Self {
+        Self {
+            entry: Some(n),
+            reply,
         }
+    }
 
-        // Name uniqueness check: can't have two running jobs with same name
-        if let Some(ref n) = name
-            && let Ok(Some(running)) = db.name_in_use(n)
-        {
-            return Response::UserError(format!(
-                "Name '{}' is in use by running job {}",
-                n,
-                running.short_id()
-            ));
+    fn internal(message: String) -> Self {
+        Self {
+            entry: None,
+            reply: ErrorReply::internal(message),
+        }
+    }
+}
+
+/// Check, create and start `jobs` for `spawn_job` and `spawn_jobs`. Returns
+/// every job in order, the earlier one for a known idempotency key.
+fn create_jobs(state: &Arc<DaemonState>, jobs: Vec<NewJob>) -> Result<Vec<Job>, Refused> {
+    for (n, new) in jobs.iter().enumerate() {
+        if let Some(e) = new.webhooks.iter().find_map(|w| w.validate().err()) {
+            return Err(Refused::entry(n, ErrorReply::rejected(e.to_string())));
         }
+    }
 
-        match db.generate_id() {
-            Ok(id) => id,
-            Err(e) => return Response::Error(e.to_string()),
+    // Each job, and whether it is new rather than found by its key
+    let mut records: Vec<(Job, bool)> = Vec::with_capacity(jobs.len());
+    let created = {
+        let db = state.db.lock().unwrap();
+        let mut keys = HashSet::new();
+        let mut names = HashSet::new();
+        let mut ids = HashSet::new();
+
+        for (n, new) in jobs.into_iter().enumerate() {
+            if let Some(key) = &new.idempotency_key {
+                if !keys.insert(key.clone()) {
+                    let message = format!("Idempotency key '{key}' is given twice");
+                    return Err(Refused::entry(n, ErrorReply::rejected(message)));
+                }
+                if let Ok(Some(existing)) = db.get_by_idempotency_key(key) {
+                    records.push((existing, false));
+                    continue;
+                }
+            }
+            if let Some(name) = &new.name {
+                if !names.insert(name.clone()) {
+                    let message = format!("Name '{name}' is given twice");
+                    return Err(Refused::entry(n, ErrorReply::rejected(message)));
+                }
+                if let Ok(Some(running)) = db.name_in_use(name) {
+                    let reply = ErrorReply::name_in_use(name, running.short_id());
+                    return Err(Refused::entry(n, reply));
+                }
+            }
+            // Unused in the database and not taken earlier in this batch
+            let id = loop {
+                match db.generate_id() {
+                    Ok(id) if ids.insert(id.clone()) => break id,
+                    Ok(_) => {}
+                    Err(e) => return Err(Refused::internal(e.to_string())),
+                }
+            };
+            records.push((job_record(id, new), true));
+        }
+
+        let created: Vec<Job> = records
+            .iter()
+            .filter(|(_, is_new)| *is_new)
+            .map(|(job, _)| job.clone())
+            .collect();
+        if let Err(e) = db.insert_all(&created) {
+            return Err(Refused::internal(format!("Failed to create jobs: {e}")));
         }
+        for job in &created {
+            db.record_event(&job.id, HistoryKind::Created, None);
+            state.status_changed(&db, &job.id);
+        }
+        created
     };
 
-    // Create job record
+    // Still pending; each updates to running shortly
+    for job in &created {
+        start_job(state, job);
+    }
+    Ok(records.into_iter().map(|(job, _)| job).collect())
+}
+
+/// `reply`, saying which entry of a batch (counting from 0) it is about
+fn in_batch(n: usize, mut reply: ErrorReply) -> Response {
+    reply.message = format!("Batch entry {}: {}", n + 1, reply.message);
+    reply.into()
+}
+
+/// The pending job `new` describes, under `id`
+fn job_record(id: String, new: NewJob) -> Job {
     let mut job = Job::new(
         id,
-        command.clone(),
-        PathBuf::from(&cwd),
-        PathBuf::from(&project),
+        new.command,
+        PathBuf::from(new.cwd),
+        PathBuf::from(new.project),
     );
-
-    if let Some(n) = name {
+    if let Some(n) = new.name {
         job = job.with_name(n);
     }
-    if let Some(t) = timeout_secs {
+    if let Some(t) = new.timeout_secs {
         job = job.with_timeout(t);
     }
-    if let Some(k) = idempotency_key {
+    if let Some(k) = new.idempotency_key {
         job = job.with_idempotency_key(k);
     }
-
-    // Insert into DB
-    {
-        let db = state.db.lock().unwrap();
-        if let Err(e) = db.insert(&job) {
-            return Response::Error(format!("Failed to create job: {e}"));
-        }
+    if let Some(limit) = LogLimit::from_settings(new.log_max_size, new.log_keep) {
+        job = job.with_log_limit(limit);
     }
+    job.with_hooks(new.hooks)
+        .with_webhooks(new.webhooks)
+        .with_notify(new.notify)
+}
 
+/// Run a job that is in the database, in the background
+fn start_job(state: &Arc<DaemonState>, job: &Job) {
     let job_id = job.id.clone();
+    let command = job.command.clone();
+    let cwd = job.cwd.to_string_lossy().to_string();
+    let (timeout_secs, log_limit) = (job.timeout_secs, job.log_limit());
     let state_clone = state.clone();
+    // Held until the job's status is final, so subscribers see it when the channel closes
+    let progress = state.publish_output(&job_id);
 
     // Spawn the process; on failure mark the job as failed in the DB
     tokio::spawn(async move {
-        if let Err(e) = run_job(&state_clone, job_id.clone(), command, cwd, timeout_secs).await {
+        if let Err(e) = run_job(
+            &state_clone,
+            job_id.clone(),
+            command,
+            cwd,
+            timeout_secs,
+            log_limit,
+            Arc::clone(&progress),
+        )
+        .await
+        {
             error!("Job {} failed: {}", job_id, e);
-            let db = state_clone.db.lock().unwrap();
-            // Only overwrite if no concurrent stop/interrupt already set a terminal status
-            if let Ok(Some(job)) = db.get(&job_id)
-                && !job.status.is_terminal()
-                && let Err(db_err) = db.update_finished(&job_id, Status::Failed, None)
-            {
-                error!("Failed to mark job {} as failed: {}", job_id, db_err);
+            let marked_failed = {
+                let db = state_clone.db.lock().unwrap();
+                // Only overwrite if no concurrent stop/interrupt already set a terminal status
+                if let Ok(Some(job)) = db.get(&job_id)
+                    && !job.status.is_terminal()
+                {
+                    match db.update_finished(&job_id, Status::Failed, None) {
+                        Ok(()) => {
+                            let detail = format!("failed to start: {e}");
+                            db.record_event(&job_id, HistoryKind::Finished, Some(&detail));
+                            state_clone.status_changed(&db, &job_id);
+                            true
+                        }
+                        Err(db_err) => {
+                            error!("Failed to mark job {} as failed: {}", job_id, db_err);
+                            false
+                        }
+                    }
+                } else {
+                    false
+                }
+            };
+            state_clone.forget_output(&job_id);
+            if marked_failed {
+                webhook::notify(&state_clone, &job_id);
+                desktop::notify(&state_clone, &job_id);
+                hooks::run_hooks(&state_clone, &job_id).await;
             }
         }
     });
-
-    // Return the job (still pending, will update to running shortly)
-    Response::Job(Box::new(job))
 }
 
 /// Time to wait for graceful shutdown before SIGKILL
 const GRACEFUL_SHUTDOWN_SECS: u64 = 2;
 
+/// Time to let the capture task drain the pipes after the process exits
+const CAPTURE_DRAIN_MILLIS: u64 = 500;
+
 #[allow(clippy::too_many_lines)]
 async fn run_job(
     state: &Arc<DaemonState>,
@@ -105,20 +240,21 @@ async fn run_job(
     command: String,
     cwd: String,
     timeout_secs: Option<u64>,
+    log_limit: Option<LogLimit>,
+    progress: OutputProgress,
 ) -> anyhow::Result<()> {
-    let log_path = state.paths.log_file(&job_id);
-
-    // Create log file
-    let log_file = File::create(&log_path).await?;
-    let log_file_std = log_file.into_std().await;
+    // Create log and index files
+    let writer = OutputWriter::create(Segment::active(&state.paths, &job_id), log_limit)?
+        .with_progress(progress);
 
-    // Spawn process in new session (detached)
+    // Spawn process in new session (detached); output is read through pipes
     let mut child = Command::new("sh")
         .arg("-c")
         .arg(&command)
         .current_dir(&cwd)
-        .stdout(Stdio::from(log_file_std.try_clone()?))
-        .stderr(Stdio::from(log_file_std))
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
         .process_group(0) // Create new process group (setsid equivalent)
         .spawn()?;
 
@@ -126,10 +262,25 @@ async fn run_job(
         .id()
         .ok_or_else(|| anyhow::anyhow!("failed to get PID of spawned process"))?;
 
+    let (Some(stdout), Some(stderr)) = (child.stdout.take(), child.stderr.take()) else {
+        anyhow::bail!("failed to open output pipes");
+    };
+    let (detach, detach_rx) = oneshot::channel();
+    let capture = tokio::spawn(capture_output(
+        state.paths.clone(),
+        job_id.clone(),
+        stdout,
+        stderr,
+        writer,
+        detach_rx,
+    ));
+
     // Update DB with running status
     {
         let db = state.db.lock().unwrap();
         db.update_started(&job_id, pid)?;
+        db.record_event(&job_id, HistoryKind::Started, Some(&format!("pid {pid}")));
+        state.status_changed(&db, &job_id);
     }
 
     info!("Job {} started with PID {}", job_id, pid);
@@ -139,7 +290,12 @@ async fn run_job(
     // Track running job
     {
         let mut running = state.running_jobs.lock().unwrap();
-        running.insert(job_id.clone(), RunningJob { pid, stop_tx });
+        let job = RunningJob {
+            pid,
+            stop_tx,
+            detach: Some(detach),
+        };
+        running.insert(job_id.clone(), job);
     }
 
     // Event-based monitoring with tokio::select!
@@ -158,6 +314,13 @@ async fn run_job(
             // Timeout expired - escalate: SIGTERM → wait → SIGKILL
             () = tokio::time::sleep(Duration::from_secs(timeout)) => {
                 warn!("Job {} timed out after {}s, sending SIGTERM", job_id, timeout);
+                {
+                    let db = state.db.lock().unwrap();
+                    let after = format!("after {timeout}s");
+                    db.record_event(&job_id, HistoryKind::Timeout, Some(&after));
+                    let signal = signal_detail(pid, false);
+                    db.record_event(&job_id, HistoryKind::Signal, Some(&signal));
+                }
                 kill_process_group(pid, false); // SIGTERM first
 
                 // Give process time to exit gracefully
@@ -167,6 +330,10 @@ async fn run_job(
                     status = child.wait() => JobResult::Completed(status.ok()),
                     () = tokio::time::sleep(Duration::from_secs(GRACEFUL_SHUTDOWN_SECS)) => {
                         warn!("Job {} did not exit after SIGTERM, sending SIGKILL", job_id);
+                        let signal = signal_detail(pid, true);
+                        let db = state.db.lock().unwrap();
+                        db.record_event(&job_id, HistoryKind::Signal, Some(&signal));
+                        drop(db);
                         kill_process_group(pid, true); // Force kill
                         JobResult::Timeout
                     }
@@ -194,10 +361,14 @@ async fn run_job(
     };
 
     // Remove from running jobs
-    {
+    let handed_off = {
         let mut running = state.running_jobs.lock().unwrap();
-        running.remove(&job_id);
-    }
+        running
+            .remove(&job_id)
+            .is_some_and(|job| job.detach.is_none())
+    };
+
+    let capture = drain_capture(&job_id, capture).await;
 
     // Handle result
     match result {
@@ -212,6 +383,8 @@ async fn run_job(
                     job_id, e
                 );
             }
+            db.record_event(&job_id, HistoryKind::Stopped, Some("by timeout"));
+            state.status_changed(&db, &job_id);
             info!("Job {} timed out", job_id);
         }
         JobResult::Completed(exit_status) => {
@@ -229,20 +402,173 @@ async fn run_job(
                         job_id, e
                     );
                 }
+                let detail = match exit_status {
+                    Some(es) => format!("{status}, {}", describe_exit(es)),
+                    None => format!("{status}, exit status unknown"),
+                };
+                db.record_event(&job_id, HistoryKind::Finished, Some(&detail));
+                state.status_changed(&db, &job_id);
             }
             info!("Job {} finished with status {:?}", job_id, status);
         }
     }
 
+    webhook::notify(state, &job_id);
+    desktop::notify(state, &job_id);
+    // Before archiving, so the log is still where JB_LOG_PATH says
+    hooks::run_hooks(state, &job_id).await;
+    // A drain helper may still be appending to the log
+    if !handed_off {
+        archive_when_captured(state, job_id, capture);
+    }
     Ok(())
 }
 
+/// Capture the job's output until its pipes close. When the daemon stops with
+/// `--keep-jobs` the pipes are handed to a drain helper instead, so the job can
+/// keep writing after the daemon (which holds their read ends) is gone.
+async fn capture_output(
+    paths: Paths,
+    job_id: String,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+    writer: OutputWriter,
+    detach: oneshot::Receiver<DetachRequest>,
+) -> std::io::Result<()> {
+    // The sender is dropped with the running job, which is no reason to stop
+    let handoff = async {
+        match detach.await {
+            Ok(reply) => reply,
+            Err(_) => std::future::pending().await,
+        }
+    };
+    let redactor = load_redactor(&paths);
+    if let Some((stdout, stderr, reply)) =
+        capture::capture_until(stdout, stderr, writer, redactor, handoff).await?
+    {
+        let _ = reply.send(spawn_drain(&paths, &job_id, stdout, stderr));
+    }
+    Ok(())
+}
+
+/// Start `jb daemon drain` for the job with its stdout pipe as the helper's
+/// stdin and its stderr pipe as the helper's stdout. Returns the helper's PID.
+fn spawn_drain(
+    paths: &Paths,
+    job_id: &str,
+    stdout: ChildStdout,
+    stderr: ChildStderr,
+) -> std::io::Result<u32> {
+    let helper = std::process::Command::new(std::env::current_exe()?)
+        .arg("--home")
+        .arg(paths.root())
+        .args(["daemon", "drain", job_id])
+        .stdin(Stdio::from(stdout.into_owned_fd()?))
+        .stdout(Stdio::from(stderr.into_owned_fd()?))
+        .stderr(Stdio::null())
+        .process_group(0) // Not killed with the daemon's process group
+        .spawn()?;
+    Ok(helper.id())
+}
+
+/// The drain helper: append a detached job's output to its log until the job
+/// and anything it started have closed the pipes inherited from [`spawn_drain`].
+pub async fn drain(paths: &Paths, job_id: &str) -> anyhow::Result<()> {
+    let job = Database::open(paths)?
+        .get(job_id)?
+        .ok_or_else(|| anyhow::anyhow!("job {job_id} not found"))?;
+    let writer = OutputWriter::resume(Segment::active(paths, job_id), job.log_limit())?;
+    let stdout = pipe::Receiver::from_owned_fd(std::io::stdin().as_fd().try_clone_to_owned()?)?;
+    let stderr = pipe::Receiver::from_owned_fd(std::io::stdout().as_fd().try_clone_to_owned()?)?;
+    capture::capture(stdout, stderr, writer, load_redactor(paths)).await?;
+    info!("Output of detached job {job_id} closed");
+    Ok(())
+}
+
+/// Redaction rules from the current config, read per job so edits apply without a
+/// restart. Bad rules fall back to the built-in ones.
+fn load_redactor(paths: &Paths) -> Option<Redactor> {
+    let config = load_config(paths, "redaction").redact;
+    Redactor::from_config(&config).unwrap_or_else(|e| {
+        warn!("Ignoring redaction config: {e:#}");
+        Redactor::from_config(&RedactConfig::default())
+            .ok()
+            .flatten()
+    })
+}
+
+/// Index the job's output for search and compress its logs in the background,
+/// once nothing writes to them anymore.
+fn archive_when_captured(
+    state: &Arc<DaemonState>,
+    job_id: String,
+    capture: Option<JoinHandle<std::io::Result<()>>>,
+) {
+    let active = Segment::active(&state.paths, &job_id);
+    let state = Arc::clone(state);
+    tokio::spawn(async move {
+        if let Some(capture) = capture {
+            let _ = capture.await;
+        }
+        state.forget_output(&job_id);
+        let indexed = active.clone();
+        match tokio::task::spawn_blocking(move || output::read_indexable(&indexed)).await {
+            Ok(Ok(text)) => {
+                if let Err(e) = state.db.lock().unwrap().index_output(&job_id, &text) {
+                    warn!("Failed to index output of job {}: {}", job_id, e);
+                }
+            }
+            Ok(Err(e)) => warn!(
+                "Failed to read output of job {} for indexing: {}",
+                job_id, e
+            ),
+            Err(e) => error!("Reading output of job {} panicked: {}", job_id, e),
+        }
+        match tokio::task::spawn_blocking(move || compress::compress_logs(&active)).await {
+            Ok(Ok(0)) => {}
+            Ok(Ok(n)) => info!("Compressed {} log file(s) of job {}", n, job_id),
+            Ok(Err(e)) => warn!("Failed to compress logs of job {}: {}", job_id, e),
+            Err(e) => error!("Log compression for job {} panicked: {}", job_id, e),
+        }
+    });
+}
+
+/// Wait briefly for the capture task to flush the last output. Background processes
+/// started by the job can hold the pipes open; they must not delay the final status,
+/// so the task is left running (still capturing) if it doesn't finish in time and
+/// returned to the caller.
+async fn drain_capture(
+    job_id: &str,
+    mut capture: JoinHandle<std::io::Result<()>>,
+) -> Option<JoinHandle<std::io::Result<()>>> {
+    match tokio::time::timeout(Duration::from_millis(CAPTURE_DRAIN_MILLIS), &mut capture).await {
+        Ok(Ok(Ok(()))) => {}
+        Ok(Ok(Err(e))) => error!("Failed to capture output of job {}: {}", job_id, e),
+        Ok(Err(e)) => error!("Capture task for job {} panicked: {}", job_id, e),
+        Err(_) => {
+            info!("Job {} exited with its output pipes still open", job_id);
+            return Some(capture);
+        }
+    }
+    None
+}
+
 enum JobResult {
     Completed(Option<std::process::ExitStatus>),
     Stopped,
     Timeout,
 }
 
+/// `exit 2` or `killed by signal 9`
+fn describe_exit(status: std::process::ExitStatus) -> String {
+    use std::os::unix::process::ExitStatusExt;
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("exit {code}"),
+        (None, Some(signal)) => format!("killed by signal {signal}"),
+        (None, None) => "exit status unknown".to_string(),
+    }
+}
+
 pub fn stop_job(state: &Arc<DaemonState>, job_id: &str, force: bool) -> Response {
     // Get job info and signal stop
     let job = {
@@ -251,7 +577,7 @@ pub fn stop_job(state: &Arc<DaemonState>, job_id: &str, force: bool) -> Response
     };
 
     let Some((pid, stop_tx)) = job else {
-        return Response::Error(format!("Job {job_id} is not running"));
+        return ErrorReply::not_running(job_id).into();
     };
 
     // Signal the run_job task to stop (will break out of select!)
@@ -262,9 +588,16 @@ pub fn stop_job(state: &Arc<DaemonState>, job_id: &str, force: bool) -> Response
 
     {
         let db = state.db.lock().unwrap();
+        db.record_event(
+            job_id,
+            HistoryKind::Signal,
+            Some(&signal_detail(pid, force)),
+        );
         if let Err(e) = db.update_finished(job_id, Status::Stopped, None) {
             error!("Failed to update job {} status after stop: {}", job_id, e);
         }
+        db.record_event(job_id, HistoryKind::Stopped, Some("by request"));
+        state.status_changed(&db, job_id);
     }
 
     info!("Job {} stopped", job_id);
@@ -288,15 +621,15 @@ pub async fn wait_for_job(
                     return Response::Job(Box::new(job));
                 }
             }
-            Ok(None) => return Response::Error(format!("Job not found: {job_id}")),
-            Err(e) => return Response::Error(e.to_string()),
+            Ok(None) => return ErrorReply::not_found(job_id).into(),
+            Err(e) => return ErrorReply::internal(e.to_string()).into(),
         }
 
         // Check timeout
         if let Some(t) = timeout
             && start.elapsed() >= t
         {
-            return Response::Error("Wait timed out".to_string());
+            return ErrorReply::timeout(job_id).into();
         }
 
         // Poll interval
@@ -307,26 +640,15 @@ pub async fn wait_for_job(
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::core::{Paths, Status};
+    use crate::core::Status;
+    use crate::core::ipc::ErrorCode;
     use crate::daemon::state::DaemonState;
+    use crate::daemon::test_support::{do_spawn, new_job, test_state};
     use std::sync::Arc;
     use std::time::Duration;
     use tempfile::TempDir;
 
-    fn test_state(tmp: &TempDir) -> Arc<DaemonState> {
-        let paths = Paths::with_root(tmp.path().to_path_buf());
-        paths.ensure_dirs().unwrap();
-        Arc::new(DaemonState::new(&paths).unwrap())
-    }
-
-    fn do_spawn(state: &Arc<DaemonState>, cmd: &str, tmp: &TempDir) -> String {
-        let cwd = tmp.path().to_string_lossy().to_string();
-        match spawn_job(state, cmd.into(), None, cwd.clone(), cwd, None, None) {
-            Response::Job(j) => j.id.clone(),
-            other => panic!("expected Job response, got {other:?}"),
-        }
-    }
-
+    #[allow(clippy::collapsible_if)] // predates let chains
     async fn poll_terminal(state: &Arc<DaemonState>, id: &str) -> Status {
         for _ in 0..100 {
             if let Ok(Some(job)) = state.get_job(id) {
@@ -394,6 +716,58 @@ mod tests {
         assert!(content.contains("hello_world_marker"), "log: {content:?}");
     }
 
+    #[tokio::test]
+    async fn test_job_streams_indexed_separately() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let id = do_spawn(&state, "echo to_stdout; echo to_stderr >&2", &tmp);
+
+        poll_terminal(&state, &id).await;
+
+        let index = crate::core::output::read_index(&state.paths.index_file(&id))
+            .unwrap()
+            .expect("index should exist");
+        let bytes = crate::core::output::StreamBytes::from_chunks(&index.chunks);
+        assert_eq!(bytes.stdout, "to_stdout\n".len() as u64);
+        assert_eq!(bytes.stderr, "to_stderr\n".len() as u64);
+    }
+
+    #[tokio::test]
+    async fn test_job_log_rotated_at_limit() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let limit = LogLimit {
+            max_bytes: 64,
+            keep: 1,
+        };
+
+        let resp = spawn_job(
+            &state,
+            NewJob {
+                log_max_size: Some(limit.max_bytes),
+                log_keep: Some(limit.keep),
+                ..new_job(
+                    &tmp,
+                    "for i in $(seq 1 50); do echo line-$i; sleep 0.01; done",
+                )
+            },
+        );
+        let Response::Job(job) = resp else {
+            panic!("expected Job, got {resp:?}");
+        };
+        assert_eq!(job.log_limit(), Some(limit));
+        poll_terminal(&state, &job.id).await;
+
+        let active = Segment::active(&state.paths, &job.id);
+        let segments = active.existing();
+        assert_eq!(segments.len(), 2, "one rotated segment kept");
+        for segment in &segments {
+            assert!(std::fs::metadata(&segment.log).unwrap().len() <= 64);
+        }
+        let last = std::fs::read_to_string(&active.log).unwrap();
+        assert!(last.ends_with("line-50\n"), "active segment: {last:?}");
+    }
+
     #[tokio::test]
     async fn test_job_pid_recorded() {
         let tmp = TempDir::new().unwrap();
@@ -412,16 +786,13 @@ mod tests {
     async fn test_spawn_bad_cwd_marks_job_failed() {
         let tmp = TempDir::new().unwrap();
         let state = test_state(&tmp);
-        let cwd = tmp.path().to_string_lossy().to_string();
 
         let resp = spawn_job(
             &state,
-            "echo hi".into(),
-            None,
-            "/nonexistent/path/that/does/not/exist/ever".into(),
-            cwd,
-            None,
-            None,
+            NewJob {
+                cwd: "/nonexistent/path/that/does/not/exist/ever".into(),
+                ..new_job(&tmp, "echo hi")
+            },
         );
         let id = match resp {
             Response::Job(j) => j.id,
@@ -436,25 +807,20 @@ mod tests {
     async fn test_spawn_idempotency_key_returns_existing_job() {
         let tmp = TempDir::new().unwrap();
         let state = test_state(&tmp);
-        let cwd = tmp.path().to_string_lossy().to_string();
 
         let resp1 = spawn_job(
             &state,
-            "echo 1".into(),
-            None,
-            cwd.clone(),
-            cwd.clone(),
-            None,
-            Some("mykey".into()),
+            NewJob {
+                idempotency_key: Some("mykey".into()),
+                ..new_job(&tmp, "echo 1")
+            },
         );
         let resp2 = spawn_job(
             &state,
-            "echo 2".into(),
-            None,
-            cwd.clone(),
-            cwd.clone(),
-            None,
-            Some("mykey".into()),
+            NewJob {
+                idempotency_key: Some("mykey".into()),
+                ..new_job(&tmp, "echo 2")
+            },
         );
 
         let id1 = match resp1 {
@@ -475,16 +841,13 @@ mod tests {
     async fn test_spawn_duplicate_name_returns_user_error() {
         let tmp = TempDir::new().unwrap();
         let state = test_state(&tmp);
-        let cwd = tmp.path().to_string_lossy().to_string();
 
         let resp1 = spawn_job(
             &state,
-            "sleep 5".into(),
-            Some("myjob".into()),
-            cwd.clone(),
-            cwd.clone(),
-            None,
-            None,
+            NewJob {
+                name: Some("myjob".into()),
+                ..new_job(&tmp, "sleep 5")
+            },
         );
         assert!(
             matches!(resp1, Response::Job(_)),
@@ -493,19 +856,87 @@ mod tests {
 
         let resp2 = spawn_job(
             &state,
-            "echo hi".into(),
-            Some("myjob".into()),
-            cwd.clone(),
-            cwd,
-            None,
-            None,
+            NewJob {
+                name: Some("myjob".into()),
+                ..new_job(&tmp, "echo hi")
+            },
         );
         assert!(
-            matches!(resp2, Response::UserError(_)),
-            "duplicate name should return UserError, got {resp2:?}"
+            matches!(&resp2, Response::Failed(r) if r.code == ErrorCode::NameInUse),
+            "duplicate name should fail with name_in_use, got {resp2:?}"
         );
     }
 
+    fn batch_entry(tmp: &TempDir, cmd: &str, name: Option<&str>, key: Option<&str>) -> NewJob {
+        NewJob {
+            name: name.map(Into::into),
+            idempotency_key: key.map(Into::into),
+            ..new_job(tmp, cmd)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_spawn_jobs_creates_all_in_order() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let earlier = match spawn_jobs(&state, vec![batch_entry(&tmp, "true", None, Some("k1"))]) {
+            Response::Jobs(jobs) => jobs[0].id.clone(),
+            other => panic!("expected Jobs, got {other:?}"),
+        };
+
+        let batch = vec![
+            batch_entry(&tmp, "echo a", Some("a"), None),
+            batch_entry(&tmp, "echo again", None, Some("k1")),
+            batch_entry(&tmp, "exit 3", Some("b"), None),
+        ];
+        let jobs = match spawn_jobs(&state, batch) {
+            Response::Jobs(jobs) => jobs,
+            other => panic!("expected Jobs, got {other:?}"),
+        };
+        assert_eq!(jobs.len(), 3);
+        assert_eq!(jobs[0].name.as_deref(), Some("a"));
+        // A known key gets the earlier job back rather than a new one
+        assert_eq!(jobs[1].id, earlier);
+        assert_ne!(jobs[0].id, jobs[2].id);
+        assert_eq!(poll_terminal(&state, &jobs[0].id).await, Status::Completed);
+        assert_eq!(poll_terminal(&state, &jobs[2].id).await, Status::Failed);
+        assert_eq!(state.list_jobs(None, None).unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_spawn_jobs_refuses_whole_batch() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let running = batch_entry(&tmp, "sleep 5", Some("web"), None);
+        assert!(matches!(
+            spawn_jobs(&state, vec![running]),
+            Response::Jobs(_)
+        ));
+
+        let batch = vec![
+            batch_entry(&tmp, "echo a", Some("a"), None),
+            batch_entry(&tmp, "echo b", Some("web"), None),
+        ];
+        let resp = spawn_jobs(&state, batch);
+        assert!(
+            matches!(&resp, Response::Failed(r) if r.code == ErrorCode::NameInUse
+                && r.message.starts_with("Batch entry 2: ")),
+            "got {resp:?}"
+        );
+
+        let batch = vec![
+            batch_entry(&tmp, "echo a", None, Some("k")),
+            batch_entry(&tmp, "echo b", None, Some("k")),
+        ];
+        let resp = spawn_jobs(&state, batch);
+        assert!(
+            matches!(&resp, Response::Failed(r) if r.code == ErrorCode::Rejected),
+            "got {resp:?}"
+        );
+        // Only the job from the first call exists
+        assert_eq!(state.list_jobs(None, None).unwrap().len(), 1);
+    }
+
     #[tokio::test]
     async fn test_stop_job_sets_stopped_status() {
         let tmp = TempDir::new().unwrap();
@@ -528,6 +959,20 @@ mod tests {
 
         let status = poll_terminal(&state, &id).await;
         assert_eq!(status, Status::Stopped);
+
+        let history = state.db.lock().unwrap().history(&id).unwrap();
+        let kinds: Vec<HistoryKind> = history.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HistoryKind::Created,
+                HistoryKind::Started,
+                HistoryKind::Signal,
+                HistoryKind::Stopped
+            ]
+        );
+        assert!(history[2].detail.as_deref().unwrap().starts_with("SIGKILL"));
+        assert_eq!(history[3].detail.as_deref(), Some("by request"));
     }
 
     #[tokio::test]
@@ -537,8 +982,8 @@ mod tests {
 
         let resp = stop_job(&state, "zzzz", false);
         assert!(
-            matches!(resp, Response::Error(_)),
-            "stopping missing job should error"
+            matches!(&resp, Response::Failed(r) if r.code == ErrorCode::NotRunning),
+            "stopping missing job should fail with not_running, got {resp:?}"
         );
     }
 
@@ -546,16 +991,13 @@ mod tests {
     async fn test_job_with_name_stored_correctly() {
         let tmp = TempDir::new().unwrap();
         let state = test_state(&tmp);
-        let cwd = tmp.path().to_string_lossy().to_string();
 
         let resp = spawn_job(
             &state,
-            "true".into(),
-            Some("named-job".into()),
-            cwd.clone(),
-            cwd,
-            None,
-            None,
+            NewJob {
+                name: Some("named-job".into()),
+                ..new_job(&tmp, "true")
+            },
         );
         let id = match resp {
             Response::Job(j) => j.id,
@@ -1,13 +1,28 @@
+use crate::core::event::{HistoryKind, JobEvent, signal_detail};
 use crate::core::{Database, Job, Paths, Status, kill_process_group};
 use std::collections::HashMap;
-use std::sync::Mutex;
-use std::time::Instant;
-use tokio::sync::watch;
-use tracing::warn;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, oneshot, watch};
+use tracing::{info, warn};
+
+/// Bytes of output a job has written so far. The capture path updates it after
+/// every write; receivers see the channel close once the job is finished.
+pub type OutputProgress = Arc<watch::Sender<u64>>;
+
+/// Asks a job's capture task to hand its pipes to a drain helper; the reply is
+/// the helper's PID.
+pub type DetachRequest = oneshot::Sender<std::io::Result<u32>>;
+
+/// Longest a capture task may take to hand its pipes over on shutdown
+const HANDOFF_TIMEOUT: Duration = Duration::from_secs(5);
 
 pub struct RunningJob {
     pub pid: u32,
     pub stop_tx: watch::Sender<bool>,
+    /// Taken once the job's output is handed off by `detach_running_jobs`
+    pub detach: Option<oneshot::Sender<DetachRequest>>,
 }
 
 pub struct DaemonState {
@@ -15,8 +30,19 @@ pub struct DaemonState {
     pub paths: Paths,
     pub started_at: Instant,
     pub running_jobs: Mutex<HashMap<String, RunningJob>>,
+    /// Output progress of jobs whose output is still being captured
+    outputs: Mutex<HashMap<String, watch::Receiver<u64>>>,
+    /// Open client connections, for idle detection
+    connections: AtomicUsize,
+    /// Last time a client connected or disconnected
+    last_activity: Mutex<Instant>,
+    /// Jobs as they are created and change status
+    events: broadcast::Sender<JobEvent>,
 }
 
+/// Events buffered per watcher before the slowest starts missing some
+const EVENTS_CAPACITY: usize = 256;
+
 impl DaemonState {
     pub fn new(paths: &Paths) -> anyhow::Result<Self> {
         let db = Database::open(paths)?;
@@ -29,6 +55,10 @@ impl DaemonState {
             paths: paths.clone(),
             started_at: Instant::now(),
             running_jobs: Mutex::new(HashMap::new()),
+            outputs: Mutex::new(HashMap::new()),
+            connections: AtomicUsize::new(0),
+            last_activity: Mutex::new(Instant::now()),
+            events: broadcast::channel(EVENTS_CAPACITY).0,
         })
     }
 
@@ -45,6 +75,32 @@ impl DaemonState {
         self.running_jobs.lock().unwrap().len()
     }
 
+    /// Count a client connection as open until the returned guard is dropped.
+    pub fn track_connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.connections.fetch_add(1, Ordering::SeqCst);
+        *self.last_activity.lock().unwrap() = Instant::now();
+        ConnectionGuard {
+            state: Arc::clone(self),
+        }
+    }
+
+    /// True when nothing is running or pending, no client is connected,
+    /// and no client has connected for at least `timeout`.
+    pub fn is_idle(&self, timeout: Duration) -> bool {
+        if self.connections.load(Ordering::SeqCst) > 0 || self.running_count() > 0 {
+            return false;
+        }
+        if self.last_activity.lock().unwrap().elapsed() < timeout {
+            return false;
+        }
+        // Pending jobs are about to be spawned
+        self.db
+            .lock()
+            .unwrap()
+            .count(Some(Status::Pending))
+            .is_ok_and(|n| n == 0)
+    }
+
     pub fn total_jobs(&self) -> usize {
         self.db.lock().unwrap().count(None).unwrap_or(0)
     }
@@ -61,8 +117,41 @@ impl DaemonState {
         self.db.lock().unwrap().list(status, limit)
     }
 
-    /// Interrupt all running jobs on graceful shutdown.
-    pub fn interrupt_running_jobs(&self) {
+    /// Start publishing a job's output progress to subscribers.
+    pub fn publish_output(&self, id: &str) -> OutputProgress {
+        let (tx, rx) = watch::channel(0);
+        self.outputs.lock().unwrap().insert(id.to_string(), rx);
+        Arc::new(tx)
+    }
+
+    /// Watch a job's output progress; `None` if it is no longer being captured.
+    pub fn subscribe_output(&self, id: &str) -> Option<watch::Receiver<u64>> {
+        self.outputs.lock().unwrap().get(id).cloned()
+    }
+
+    /// Stop tracking a job's output once nothing writes to it anymore.
+    pub fn forget_output(&self, id: &str) {
+        self.outputs.lock().unwrap().remove(id);
+    }
+
+    /// Announce a job's new status to event watchers. Takes the locked
+    /// database, since callers have just written the change through it.
+    pub fn status_changed(&self, db: &Database, id: &str) {
+        if self.events.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(Some(job)) = db.get(id) {
+            let _ = self.events.send(JobEvent::now(job));
+        }
+    }
+
+    /// Receive an event as each job is created and each time its status changes.
+    pub fn watch_events(&self) -> broadcast::Receiver<JobEvent> {
+        self.events.subscribe()
+    }
+
+    /// Interrupt all running jobs on graceful shutdown. Returns their IDs.
+    pub fn interrupt_running_jobs(&self) -> Vec<String> {
         // Drain the running jobs map before acquiring the DB lock to avoid
         // potential deadlock if another path acquires these locks in opposite order.
         let jobs: Vec<(String, RunningJob)> = {
@@ -71,11 +160,75 @@ impl DaemonState {
         };
 
         let db = self.db.lock().unwrap();
+        let mut interrupted = Vec::with_capacity(jobs.len());
         for (id, job) in jobs {
             warn!("Interrupting job {id} on shutdown");
             let _ = job.stop_tx.send(true);
             kill_process_group(job.pid, false);
+            db.record_event(
+                &id,
+                HistoryKind::Signal,
+                Some(&signal_detail(job.pid, false)),
+            );
             let _ = db.update_finished(&id, Status::Interrupted, None);
+            db.record_event(&id, HistoryKind::Interrupted, Some("daemon shutdown"));
+            self.status_changed(&db, &id);
+            interrupted.push(id);
+        }
+        interrupted
+    }
+
+    /// Leave all running jobs alive on shutdown, handing their output pipes to
+    /// drain helpers so their writes don't fail once the daemon is gone.
+    /// Their DB rows stay `running`; orphan recovery marks them interrupted once they exit.
+    pub async fn detach_running_jobs(&self) {
+        // Entries are not drained: dropping `stop_tx` would wake `run_job` as if stopped.
+        let jobs: Vec<(String, u32, Option<oneshot::Sender<DetachRequest>>)> = {
+            let mut running = self.running_jobs.lock().unwrap();
+            running
+                .iter_mut()
+                .map(|(id, job)| (id.clone(), job.pid, job.detach.take()))
+                .collect()
+        };
+        for (id, pid, detach) in jobs {
+            info!("Leaving job {id} (PID {pid}) running on shutdown");
+            let detail = match hand_off(detach).await {
+                Ok(Some(helper)) => {
+                    format!("daemon stopped with --keep-jobs, output drained by PID {helper}")
+                }
+                Ok(None) => "daemon stopped with --keep-jobs".to_string(),
+                Err(e) => {
+                    warn!("Failed to hand off output of job {id}: {e}");
+                    format!("daemon stopped with --keep-jobs, output not captured: {e}")
+                }
+            };
+            let db = self.db.lock().unwrap();
+            db.record_event(&id, HistoryKind::Detached, Some(&detail));
         }
     }
 }
+
+/// Ask a capture task to hand its pipes to a drain helper. `None` if the job has
+/// already closed them, so there is nothing left to capture.
+async fn hand_off(detach: Option<oneshot::Sender<DetachRequest>>) -> std::io::Result<Option<u32>> {
+    let (reply, helper) = oneshot::channel();
+    if detach.is_none_or(|detach| detach.send(reply).is_err()) {
+        return Ok(None);
+    }
+    match tokio::time::timeout(HANDOFF_TIMEOUT, helper).await {
+        Ok(Ok(pid)) => pid.map(Some),
+        Ok(Err(_)) => Ok(None),
+        Err(_) => Err(std::io::Error::other("timed out")),
+    }
+}
+
+pub struct ConnectionGuard {
+    state: Arc<DaemonState>,
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.state.connections.fetch_sub(1, Ordering::SeqCst);
+        *self.state.last_activity.lock().unwrap() = Instant::now();
+    }
+}
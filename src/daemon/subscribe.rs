@@ -0,0 +1,261 @@
+use crate::core::event::EventFilter;
+use crate::core::ipc::{ErrorReply, Response};
+use crate::core::output::{OutputChunk, OutputTail, ResumeFilter, Segment};
+use crate::daemon::server::write_message;
+use crate::daemon::state::DaemonState;
+use anyhow::Result;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::UnixStream;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+/// Poll interval for jobs whose output this daemon isn't capturing
+/// (left running by a previous daemon)
+const POLL_MILLIS: u64 = 100;
+
+/// Output bytes per `Output` frame
+const FRAME_BYTES: usize = 64 * 1024;
+
+/// Push a job's output to a subscriber as it is captured, then the job itself
+/// once it is terminal. Returns early if the client hangs up.
+pub async fn stream_output(
+    stream: &mut UnixStream,
+    state: &Arc<DaemonState>,
+    id: &str,
+    from_offset: u64,
+    version: u32,
+) -> Result<()> {
+    let failure = match state.get_job(id) {
+        Ok(Some(job)) => Ok(job),
+        Ok(None) => Err(ErrorReply::not_found(id)),
+        Err(e) => Err(ErrorReply::internal(e.to_string())),
+    };
+    let job = match failure {
+        Ok(job) => job,
+        Err(reply) => {
+            return write_message(stream, &Response::Failed(reply).for_version(version)).await;
+        }
+    };
+
+    let active = Segment::active(&state.paths, &job.id);
+    // Subscribe before the first read so no write goes unnoticed
+    let mut progress = state.subscribe_output(&job.id);
+    let mut capture_done = false;
+    let mut tail: Option<OutputTail> = None;
+    let mut resume = ResumeFilter::new(from_offset);
+    let mut probe = [0u8; 1];
+
+    loop {
+        // Once the job is terminal, whatever is on disk is complete
+        let job = state
+            .get_job(&job.id)?
+            .ok_or_else(|| anyhow::anyhow!("job {} disappeared", job.id))?;
+        let finished = match progress {
+            Some(_) => capture_done,
+            None => job.status.is_terminal(),
+        };
+
+        // Read through the index, except for jobs captured without one
+        if tail.is_none() && (active.index.exists() || (finished && active.exists())) {
+            tail = Some(if active.index.exists() {
+                OutputTail::open_indexed(active.clone(), None)?
+            } else {
+                OutputTail::open(active.clone(), None)?
+            });
+        }
+        if let Some(tail) = tail.as_mut() {
+            let chunks: Vec<OutputChunk> = tail
+                .read_output()?
+                .into_iter()
+                .filter_map(|c| resume.apply(c))
+                .collect();
+            send_chunks(stream, chunks).await?;
+        }
+
+        if finished {
+            if job.status.is_terminal() {
+                return write_message(stream, &Response::Finished(Box::new(job))).await;
+            }
+            // Capture ended without a final status: watch the database instead
+            progress = None;
+            continue;
+        }
+
+        tokio::select! {
+            changed = async { progress.as_mut().expect("checked").changed().await },
+                if progress.is_some() => {
+                capture_done = changed.is_err();
+            }
+            () = tokio::time::sleep(Duration::from_millis(POLL_MILLIS)), if progress.is_none() => {}
+            // Subscribers send nothing more; a read returns only when they hang up
+            _ = stream.read(&mut probe) => return Ok(()),
+        }
+    }
+}
+
+/// Push lifecycle events matching `filter` to a watcher until it hangs up.
+/// `Ok` is sent first, once events can no longer be missed.
+pub async fn stream_events(
+    stream: &mut UnixStream,
+    state: &Arc<DaemonState>,
+    filter: &EventFilter,
+) -> Result<()> {
+    let mut events = state.watch_events();
+    write_message(stream, &Response::Ok).await?;
+    let mut probe = [0u8; 1];
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(event) => {
+                    if filter.matches(&event) {
+                        write_message(stream, &Response::Event(Box::new(event))).await?;
+                    }
+                }
+                Err(RecvError::Lagged(missed)) => warn!("Event watcher missed {missed} events"),
+                Err(RecvError::Closed) => return Ok(()),
+            },
+            // Watchers send nothing more; a read returns only when they hang up
+            _ = stream.read(&mut probe) => return Ok(()),
+        }
+    }
+}
+
+/// Send chunks in frames of about `FRAME_BYTES`.
+async fn send_chunks(stream: &mut UnixStream, chunks: Vec<OutputChunk>) -> Result<()> {
+    let mut frame = Vec::new();
+    let mut frame_bytes = 0;
+    for chunk in chunks {
+        frame_bytes += chunk.data.len();
+        frame.push(chunk);
+        if frame_bytes >= FRAME_BYTES {
+            write_message(stream, &Response::Output(std::mem::take(&mut frame))).await?;
+            frame_bytes = 0;
+        }
+    }
+    if !frame.is_empty() {
+        write_message(stream, &Response::Output(frame)).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Status;
+    use crate::core::event::EventKind;
+    use crate::core::ipc::{NewJob, PROTOCOL_VERSION};
+    use crate::core::output::Stream;
+    use crate::daemon::spawner;
+    use crate::daemon::test_support::{do_spawn, new_job, test_state};
+    use tempfile::TempDir;
+
+    async fn read_frame(stream: &mut UnixStream) -> Response {
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len).await.unwrap();
+        let mut buf = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut buf).await.unwrap();
+        serde_json::from_slice(&buf).unwrap()
+    }
+
+    /// Subscribe and collect the pushed chunks until the final frame
+    async fn collect(
+        state: &Arc<DaemonState>,
+        id: &str,
+        from_offset: u64,
+    ) -> (Vec<OutputChunk>, Status) {
+        let (mut server, mut client) = UnixStream::pair().unwrap();
+        let state = Arc::clone(state);
+        let id = id.to_string();
+        tokio::spawn(async move {
+            stream_output(&mut server, &state, &id, from_offset, PROTOCOL_VERSION).await
+        });
+
+        let mut chunks = Vec::new();
+        loop {
+            let frame = tokio::time::timeout(Duration::from_secs(5), read_frame(&mut client))
+                .await
+                .expect("no frame within 5s");
+            match frame {
+                Response::Output(c) => chunks.extend(c),
+                Response::Finished(job) => return (chunks, job.status),
+                other => panic!("unexpected frame {other:?}"),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_subscription_pushes_output_then_status() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let id = do_spawn(&state, "echo out; sleep 0.2; echo err >&2; exit 2", &tmp);
+
+        let (chunks, status) = collect(&state, &id, 0).await;
+        assert_eq!(status, Status::Failed);
+        let parts: Vec<(Option<Stream>, &[u8])> = chunks
+            .iter()
+            .map(|c| (c.stream, c.data.as_slice()))
+            .collect();
+        assert_eq!(
+            parts,
+            vec![
+                (Some(Stream::Stdout), &b"out\n"[..]),
+                (Some(Stream::Stderr), &b"err\n"[..])
+            ]
+        );
+        assert_eq!(chunks[1].offset, 4);
+    }
+
+    #[tokio::test]
+    async fn test_subscription_resumes_from_offset() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let id = do_spawn(&state, "printf 'abcdef'", &tmp);
+
+        let (chunks, status) = collect(&state, &id, 2).await;
+        assert_eq!(status, Status::Completed);
+        let data: Vec<u8> = chunks.into_iter().flat_map(|c| c.data).collect();
+        assert_eq!(data, b"cdef");
+    }
+
+    #[tokio::test]
+    async fn test_events_follow_job_lifecycle() {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let (mut server, mut client) = UnixStream::pair().unwrap();
+        let watcher = Arc::clone(&state);
+        let filter = EventFilter {
+            name: Some("watched".into()),
+            ..EventFilter::default()
+        };
+        tokio::spawn(async move { stream_events(&mut server, &watcher, &filter).await });
+        assert!(matches!(read_frame(&mut client).await, Response::Ok));
+
+        do_spawn(&state, "true", &tmp);
+        let new = NewJob {
+            name: Some("watched".into()),
+            ..new_job(&tmp, "exit 3")
+        };
+        spawner::spawn_job(&state, new);
+
+        let mut kinds = Vec::new();
+        while kinds.len() < 3 {
+            let frame = tokio::time::timeout(Duration::from_secs(5), read_frame(&mut client))
+                .await
+                .expect("no event within 5s");
+            match frame {
+                Response::Event(event) => {
+                    assert_eq!(event.job.name.as_deref(), Some("watched"));
+                    kinds.push(event.kind);
+                }
+                other => panic!("unexpected frame {other:?}"),
+            }
+        }
+        assert_eq!(
+            kinds,
+            vec![EventKind::Created, EventKind::Started, EventKind::Finished]
+        );
+    }
+}
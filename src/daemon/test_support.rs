@@ -0,0 +1,42 @@
+//! Fixtures shared by the daemon's tests
+
+use crate::core::Paths;
+use crate::core::hooks::Hooks;
+use crate::core::ipc::{NewJob, Response};
+use crate::daemon::spawner;
+use crate::daemon::state::DaemonState;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+/// Daemon state over a fresh home in `tmp`
+pub fn test_state(tmp: &TempDir) -> Arc<DaemonState> {
+    let paths = Paths::with_root(tmp.path().to_path_buf());
+    paths.ensure_dirs().unwrap();
+    Arc::new(DaemonState::new(&paths).unwrap())
+}
+
+/// A job running `cmd` in `tmp`, with no options
+pub fn new_job(tmp: &TempDir, cmd: &str) -> NewJob {
+    let cwd = tmp.path().to_string_lossy().to_string();
+    NewJob {
+        command: cmd.into(),
+        name: None,
+        cwd: cwd.clone(),
+        project: cwd,
+        timeout_secs: None,
+        idempotency_key: None,
+        log_max_size: None,
+        log_keep: None,
+        hooks: Hooks::default(),
+        webhooks: Vec::new(),
+        notify: None,
+    }
+}
+
+/// Start `cmd` in `tmp` with no options and return the job's ID
+pub fn do_spawn(state: &Arc<DaemonState>, cmd: &str, tmp: &TempDir) -> String {
+    match spawner::spawn_job(state, new_job(tmp, cmd)) {
+        Response::Job(j) => j.id.clone(),
+        other => panic!("expected Job response, got {other:?}"),
+    }
+}
@@ -0,0 +1,302 @@
+use crate::core::Job;
+use crate::core::Status;
+use crate::core::event::{HistoryKind, JobEvent};
+use crate::core::webhook::{Delivery, SIGNATURE_HEADER, Webhook, signature};
+use crate::daemon::load_config;
+use crate::daemon::state::DaemonState;
+use chrono::Utc;
+use reqwest::header::{CONTENT_TYPE, HeaderValue};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+/// Tries per webhook before giving up
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the second try; doubled before each one after
+const FIRST_RETRY: Duration = Duration::from_secs(2);
+
+/// Longest one try may take, connecting included
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Post a finished job to its webhooks and the global ones from config, in
+/// the background. Each endpoint is retried on its own.
+pub fn notify(state: &Arc<DaemonState>, job_id: &str) {
+    let state = Arc::clone(state);
+    let job_id = job_id.to_string();
+    tokio::spawn(async move { deliver_all(&state, &job_id, FIRST_RETRY).await });
+}
+
+async fn deliver_all(state: &Arc<DaemonState>, job_id: &str, first_retry: Duration) {
+    let job = match state.get_job(job_id) {
+        Ok(Some(job)) => job,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Failed to load job {job_id} for webhooks: {e}");
+            return;
+        }
+    };
+    // Interrupted by a daemon shutdown: retries wouldn't outlive the daemon
+    if job.status == Status::Interrupted {
+        return;
+    }
+    let global = load_config(&state.paths, "webhooks").webhooks;
+    let targets: Vec<Webhook> = job
+        .webhooks
+        .iter()
+        .chain(&global)
+        .filter(|w| w.wants(job.status))
+        .cloned()
+        .collect();
+    if targets.is_empty() {
+        return;
+    }
+
+    let client = match reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .user_agent(concat!("jb/", env!("CARGO_PKG_VERSION")))
+        .build()
+    {
+        Ok(client) => client,
+        Err(e) => {
+            warn!("Failed to create webhook client: {e}");
+            return;
+        }
+    };
+    let payload = Payload::new(job);
+    // Counted as a client so an idle timeout doesn't cut retries short
+    let _connection = state.track_connection();
+    let mut deliveries = JoinSet::new();
+    for webhook in targets {
+        let (state, client, payload) = (Arc::clone(state), client.clone(), payload.clone());
+        deliveries.spawn(async move {
+            deliver(&state, &client, &webhook, &payload, first_retry).await;
+        });
+    }
+    deliveries.join_all().await;
+}
+
+/// What is posted: the job as a [`JobEvent`], serialized once for all targets
+#[derive(Clone)]
+struct Payload {
+    job_id: String,
+    kind: &'static str,
+    body: Arc<[u8]>,
+}
+
+impl Payload {
+    fn new(job: Job) -> Self {
+        let job_id = job.id.clone();
+        let event = JobEvent::now(job);
+        let body = serde_json::to_vec(&event).expect("jobs serialize to JSON");
+        Self {
+            job_id,
+            kind: event.kind.as_str(),
+            body: body.into(),
+        }
+    }
+}
+
+/// Post `payload` to `webhook` until it is accepted, the endpoint refuses it
+/// for good, or `MAX_ATTEMPTS` run out, recording every try.
+async fn deliver(
+    state: &DaemonState,
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    payload: &Payload,
+    first_retry: Duration,
+) {
+    let mut wait = first_retry;
+    for attempt in 1..=MAX_ATTEMPTS {
+        let (delivery, retry) = post(client, webhook, payload, attempt).await;
+        let outcome = match (&delivery.error, retry) {
+            (None, _) => Some(format!("delivered to {}", webhook.url)),
+            (Some(error), false) => Some(format!("{} refused it: {error}", webhook.url)),
+            (Some(error), true) if attempt == MAX_ATTEMPTS => Some(format!(
+                "gave up on {} after {attempt} attempts: {error}",
+                webhook.url
+            )),
+            (Some(_), true) => None,
+        };
+        {
+            let db = state.db.lock().unwrap();
+            if let Err(e) = db.record_delivery(&payload.job_id, &delivery) {
+                warn!("Failed to record webhook delivery: {e}");
+            }
+            if let Some(outcome) = &outcome {
+                db.record_event(&payload.job_id, HistoryKind::Webhook, Some(outcome));
+            }
+        }
+        if let Some(outcome) = outcome {
+            info!("Webhook for job {}: {outcome}", payload.job_id);
+            return;
+        }
+        tokio::time::sleep(wait).await;
+        wait *= 2;
+    }
+}
+
+/// One try, and whether a failure is worth retrying
+async fn post(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    payload: &Payload,
+    attempt: u32,
+) -> (Delivery, bool) {
+    let at = Utc::now();
+    let mut request = client
+        .post(&webhook.url)
+        .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+        .header("X-Jb-Event", payload.kind)
+        .header("X-Jb-Attempt", attempt.to_string())
+        .body(payload.body.to_vec());
+    if let Some(secret) = &webhook.secret {
+        request = request.header(SIGNATURE_HEADER, signature(secret, &payload.body));
+    }
+    let (status_code, error, retry) = match request.send().await {
+        Ok(response) => {
+            let status = response.status();
+            if status.is_success() {
+                (Some(status.as_u16()), None, false)
+            } else {
+                // Server trouble and rate limits pass; other refusals won't
+                let retry = status.is_server_error()
+                    || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    || status == reqwest::StatusCode::REQUEST_TIMEOUT;
+                (Some(status.as_u16()), Some(format!("HTTP {status}")), retry)
+            }
+        }
+        Err(e) => (None, Some(describe(&e)), true),
+    };
+    let delivery = Delivery {
+        url: webhook.url.clone(),
+        attempt,
+        at,
+        status_code,
+        error,
+    };
+    (delivery, retry)
+}
+
+/// A request error with its causes, which hold the useful part
+fn describe(error: &reqwest::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(error);
+    while let Some(cause) = source {
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::daemon::test_support::test_state;
+    use axum::Router;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use std::sync::Mutex;
+    use tempfile::TempDir;
+
+    /// Requests a stand-in endpoint received, as (headers, body)
+    type Received = Arc<Mutex<Vec<(HeaderMap, Vec<u8>)>>>;
+
+    /// A local endpoint answering with `statuses` in turn (the last one repeats)
+    async fn stand_in(statuses: Vec<StatusCode>) -> (String, Received) {
+        let received: Received = Arc::default();
+        let log = Arc::clone(&received);
+        let app = Router::new().route(
+            "/hook",
+            post(move |headers: HeaderMap, body: axum::body::Bytes| {
+                let log = Arc::clone(&log);
+                let statuses = statuses.clone();
+                async move {
+                    let mut log = log.lock().unwrap();
+                    log.push((headers, body.to_vec()));
+                    statuses[(log.len() - 1).min(statuses.len() - 1)]
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        (format!("http://{addr}/hook"), received)
+    }
+
+    fn setup(webhook: Webhook) -> (Arc<DaemonState>, TempDir) {
+        let tmp = TempDir::new().unwrap();
+        let state = test_state(&tmp);
+        let mut job = Job::new(
+            "ab12".into(),
+            "make".into(),
+            tmp.path().to_path_buf(),
+            tmp.path().to_path_buf(),
+        )
+        .with_webhooks(vec![webhook]);
+        job.status = Status::Failed;
+        job.exit_code = Some(2);
+        state.db.lock().unwrap().insert(&job).unwrap();
+        (state, tmp)
+    }
+
+    #[tokio::test]
+    async fn test_retried_until_delivered_and_signed() {
+        let (url, received) = stand_in(vec![StatusCode::SERVICE_UNAVAILABLE, StatusCode::OK]).await;
+        let webhook = Webhook {
+            secret: Some("s3cret".into()),
+            ..Webhook::new(url)
+        };
+        let (state, _tmp) = setup(webhook);
+        deliver_all(&state, "ab12", Duration::from_millis(10)).await;
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        assert_eq!(headers["x-jb-attempt"], "2");
+        assert_eq!(headers["x-jb-event"], "finished");
+        assert_eq!(
+            headers["x-jb-signature-256"],
+            signature("s3cret", body).as_str()
+        );
+        assert!(!String::from_utf8_lossy(body).contains("s3cret"));
+        let event: JobEvent = serde_json::from_slice(body).unwrap();
+        assert_eq!(event.job.id, "ab12");
+        assert_eq!(event.job.exit_code, Some(2));
+
+        let db = state.db.lock().unwrap();
+        let deliveries = db.deliveries("ab12").unwrap();
+        assert_eq!(deliveries.len(), 2);
+        assert_eq!(deliveries[0].status_code, Some(503));
+        assert!(!deliveries[0].succeeded());
+        assert!(deliveries[1].succeeded());
+        let history = db.history("ab12").unwrap();
+        assert_eq!(history[0].kind, HistoryKind::Webhook);
+    }
+
+    #[tokio::test]
+    async fn test_refusal_not_retried_and_filter_applied() {
+        let (url, received) = stand_in(vec![StatusCode::BAD_REQUEST]).await;
+        let (state, _tmp) = setup(Webhook::new(url.clone()));
+        deliver_all(&state, "ab12", Duration::from_millis(10)).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+
+        let successes_only = Webhook {
+            events: vec![Status::Completed],
+            ..Webhook::new(url)
+        };
+        let (state, _tmp) = setup(successes_only);
+        deliver_all(&state, "ab12", Duration::from_millis(10)).await;
+        assert_eq!(received.lock().unwrap().len(), 1);
+        assert!(
+            state
+                .db
+                .lock()
+                .unwrap()
+                .deliveries("ab12")
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::core::ipc::{ErrorCode, ErrorReply};
+use std::io;
+
+/// Errors from talking to the daemon and looking up jobs
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// No job has this ID or name
+    #[error("No job found with ID or name '{0}'")]
+    NotFound(String),
+
+    /// A running job already has the name a new job asked for
+    #[error("Name '{name}' is in use by running job {job_id}")]
+    NameInUse { name: String, job_id: String },
+
+    /// The job can't be stopped because it isn't running
+    #[error("Job {0} is not running")]
+    NotRunning(String),
+
+    /// The job was still running when the wait's timeout passed
+    #[error("Timed out waiting for job {0}")]
+    Timeout(String),
+
+    /// The daemon refused the request, e.g. for an invalid argument
+    #[error("{0}")]
+    Rejected(String),
+
+    /// The daemon failed to carry out the request
+    #[error("{0}")]
+    Daemon(String),
+
+    /// No daemon was running and one couldn't be started
+    #[error("Daemon failed to start within 5 seconds")]
+    StartFailed,
+
+    /// The running daemon speaks a protocol this client can't
+    #[error("Daemon is incompatible with this jb ({0}); restart it with `jb daemon restart`")]
+    Incompatible(String),
+
+    /// The daemon sent a reply that doesn't fit the request
+    #[error("Unexpected response from daemon: {0}")]
+    UnexpectedResponse(String),
+
+    /// The jb state directory couldn't be determined
+    #[error("Cannot locate jb state directory: {0}")]
+    Home(String),
+
+    #[error(transparent)]
+    Io(#[from] io::Error),
+
+    /// A message that isn't valid protocol JSON
+    #[error("Invalid message from daemon: {0}")]
+    Protocol(#[from] serde_json::Error),
+}
+
+impl From<ErrorReply> for Error {
+    /// The error for a `Failed` reply, by its code
+    fn from(reply: ErrorReply) -> Self {
+        let ErrorReply {
+            code,
+            message,
+            job,
+            name,
+        } = reply;
+        match (code, job, name) {
+            (ErrorCode::NotFound, Some(id), _) => Self::NotFound(id),
+            (ErrorCode::NotRunning, Some(id), _) => Self::NotRunning(id),
+            (ErrorCode::Timeout, Some(id), _) => Self::Timeout(id),
+            (ErrorCode::NameInUse, Some(job_id), Some(name)) => Self::NameInUse { name, job_id },
+            (ErrorCode::UnsupportedVersion, ..) => Self::Incompatible(message),
+            (ErrorCode::Internal, ..) => Self::Daemon(message),
+            _ => Self::Rejected(message),
+        }
+    }
+}
+
+impl Error {
+    /// Whether the error is about the request rather than jb itself, and so
+    /// is reported without a trace
+    #[must_use]
+    pub fn is_user_error(&self) -> bool {
+        matches!(
+            self,
+            Self::NotFound(_)
+                | Self::NameInUse { .. }
+                | Self::NotRunning(_)
+                | Self::Timeout(_)
+                | Self::Rejected(_)
+                | Self::Incompatible(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reply_classified_by_code() {
+        assert!(matches!(
+            Error::from(ErrorReply::not_found("ab12")),
+            Error::NotFound(id) if id == "ab12"
+        ));
+        let err = Error::from(ErrorReply::name_in_use("web", "x9k2"));
+        assert!(
+            matches!(&err, Error::NameInUse { name, job_id } if name == "web" && job_id == "x9k2")
+        );
+        assert_eq!(err.to_string(), "Name 'web' is in use by running job x9k2");
+        assert!(matches!(
+            Error::from(ErrorReply::not_running("ab12")),
+            Error::NotRunning(id) if id == "ab12"
+        ));
+        assert!(matches!(
+            Error::from(ErrorReply::timeout("ab12")),
+            Error::Timeout(id) if id == "ab12"
+        ));
+        assert!(matches!(
+            Error::from(ErrorReply::internal("disk full")),
+            Error::Daemon(_)
+        ));
+        // The message is for people; a look-alike doesn't change the code
+        assert!(matches!(
+            Error::from(ErrorReply::rejected("Job not found: ab12")),
+            Error::Rejected(_)
+        ));
+    }
+}
@@ -0,0 +1,36 @@
+//! Client library for jb, the background job manager.
+//!
+//! [`DaemonClient`] drives the jb daemon over its Unix socket, the same way the
+//! `jb` CLI does: start jobs, stop them, wait for them, list them, stream
+//! their output and watch their lifecycle events. The daemon is started on
+//! first use if it isn't running.
+//!
+//! ```no_run
+//! use jb::{DaemonClient, RunSpec};
+//! use std::time::Duration;
+//!
+//! # async fn example() -> Result<(), jb::Error> {
+//! let mut client = DaemonClient::connect_or_start().await?;
+//! let job = client.run(&RunSpec::new("cargo build")).await?;
+//! match client.wait(&job.id, Some(Duration::from_secs(600))).await {
+//!     Ok(done) => println!("{} exited with {:?}", done.id, done.exit_code),
+//!     Err(jb::Error::Timeout(_)) => client.stop(&job.id, false).await?,
+//!     Err(e) => return Err(e),
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! State lives under `~/.jb` (or `$JB_HOME`); use the `*_at` constructors with
+//! [`Paths::with_root`] for another directory. The [`core`] module has the
+//! job types, the wire protocol ([`core::ipc`]) and direct read access to the
+//! job database and logs.
+
+pub mod client;
+pub mod core;
+pub mod error;
+
+pub use client::{DaemonClient, Events, RunSpec, Subscription, SubscriptionEvent};
+pub use core::event::{EventFilter, EventKind, JobEvent};
+pub use core::{Job, Paths, Status};
+pub use error::Error;
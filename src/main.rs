@@ -1,12 +1,16 @@
-mod client;
 mod commands;
-mod core;
 mod daemon;
 
+use jb::{client, core, error};
+
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use clap_complete::Shell;
+use commands::logs::LogFormat;
 use core::UserError;
+use core::hooks::Hooks;
+use core::output::Stream;
+use std::path::PathBuf;
 
 #[derive(Parser)]
 #[command(name = "jb")]
@@ -19,6 +23,10 @@ struct Cli {
     /// Output as JSON
     #[arg(long, global = true)]
     json: bool,
+
+    /// State directory for database, logs and daemon (default: $JB_HOME or ~/.jb)
+    #[arg(long, global = true, value_name = "DIR")]
+    home: Option<PathBuf>,
 }
 
 #[derive(Subcommand)]
@@ -26,7 +34,8 @@ enum Commands {
     /// Start a background job
     Run {
         /// Command to execute
-        command: String,
+        #[arg(required_unless_present = "batch")]
+        command: Option<String>,
 
         /// Human-readable job name
         #[arg(short, long)]
@@ -51,6 +60,42 @@ enum Commands {
         /// Follow output until job completes
         #[arg(short, long)]
         follow: bool,
+
+        /// Rotate the job's log past this size (e.g., 100M, 1G)
+        #[arg(long, value_name = "SIZE")]
+        log_max_size: Option<String>,
+
+        /// Rotated log segments to keep (0 discards old output; default 3)
+        #[arg(long, value_name = "N")]
+        log_keep: Option<u32>,
+
+        /// Shell command to run if the job exits 0 (gets JB_JOB_ID, JB_STATUS, JB_EXIT_CODE, JB_LOG_PATH)
+        #[arg(long, value_name = "CMD")]
+        on_success: Option<String>,
+
+        /// Shell command to run if the job fails
+        #[arg(long, value_name = "CMD")]
+        on_failure: Option<String>,
+
+        /// Shell command to run whenever the job ends, including stop and timeout
+        #[arg(long, value_name = "CMD")]
+        on_exit: Option<String>,
+
+        /// POST the finished job as JSON to this URL (repeatable)
+        #[arg(long, value_name = "URL")]
+        webhook: Vec<String>,
+
+        /// Show a desktop notification when the job finishes
+        #[arg(long, overrides_with = "no_notify")]
+        notify: bool,
+
+        /// No desktop notification, even if `[notify] default` is on
+        #[arg(long)]
+        no_notify: bool,
+
+        /// Start every job in FILE (a JSON array, or one JSON object per line; `-` or no FILE reads stdin) in one request
+        #[arg(long, value_name = "FILE", num_args = 0..=1, default_missing_value = "-", conflicts_with_all = ["command", "name", "timeout", "dir", "key", "wait", "follow", "log_max_size", "log_keep", "on_success", "on_failure", "on_exit", "webhook", "notify", "no_notify"])]
+        batch: Option<String>,
     },
 
     /// List jobs
@@ -71,18 +116,31 @@ enum Commands {
         /// Show all jobs (no limit)
         #[arg(short, long)]
         all: bool,
+
+        /// Only jobs whose output contains these words (via the search index)
+        #[arg(long, value_name = "TEXT")]
+        output_contains: Option<String>,
     },
 
     /// Show job or system status
     Status {
         /// Job ID or name (omit for system status)
         id: Option<String>,
+
+        /// Also show the job's recorded history: start, signals, timeout, who stopped it
+        #[arg(short, long, requires = "id")]
+        events: bool,
     },
 
     /// Show job output
     Logs {
-        /// Job ID or name
-        id: String,
+        /// Job ID or name; several can be followed at once
+        #[arg(required_unless_present = "name")]
+        ids: Vec<String>,
+
+        /// Follow every job whose name matches a glob (e.g. 'svc-*'); the latest of each name
+        #[arg(long, value_name = "PATTERN", requires = "follow")]
+        name: Option<String>,
 
         /// Show last N lines (default: 50 if flag present)
         #[arg(short, long, num_args = 0..=1, default_missing_value = "50")]
@@ -95,6 +153,87 @@ enum Commands {
         /// Pipe output through pager (less -R)
         #[arg(long)]
         pager: bool,
+
+        /// Show only the job's stdout
+        #[arg(long, conflicts_with = "stderr")]
+        stdout: bool,
+
+        /// Show only the job's stderr
+        #[arg(long)]
+        stderr: bool,
+
+        /// Prefix each line with the time it was received
+        #[arg(long)]
+        timestamps: bool,
+
+        /// Only lines received since a time (e.g., 5m, 2h, or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only lines received before a time (e.g., 1m, or RFC 3339)
+        #[arg(long, conflicts_with = "follow")]
+        until: Option<String>,
+
+        /// Show an excerpt of about N tokens: head, tail and error/warning lines, with skipped lines counted
+        #[arg(long, value_name = "TOKENS", conflicts_with_all = ["follow", "tail"], value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+        budget: Option<usize>,
+
+        /// Only output after a cursor from a previous read (`0` to start); prints the next cursor
+        #[arg(long, value_name = "CURSOR", conflicts_with_all = ["follow", "tail", "pager", "timestamps", "since", "until", "budget"])]
+        since_cursor: Option<String>,
+
+        /// Output format: text, or asciicast (an asciinema v2 recording with the real timing)
+        #[arg(long, value_enum, default_value_t = LogFormat::Text, conflicts_with_all = ["follow", "tail", "pager", "timestamps", "since", "until", "budget", "since_cursor"])]
+        format: LogFormat,
+    },
+
+    /// Play a job's output back in the terminal with its original timing
+    Replay {
+        /// Job ID or name
+        id: String,
+
+        /// Playback speed multiplier (pauses over 2s are shortened first)
+        #[arg(long, default_value_t = 1.0)]
+        speed: f64,
+
+        /// Replay only the job's stdout
+        #[arg(long, conflicts_with = "stderr")]
+        stdout: bool,
+
+        /// Replay only the job's stderr
+        #[arg(long)]
+        stderr: bool,
+    },
+
+    /// Search the output of many jobs
+    Grep {
+        /// Regular expression to search for
+        pattern: String,
+
+        /// Case-insensitive match
+        #[arg(short, long)]
+        ignore_case: bool,
+
+        /// Only jobs with this status (pending, running, completed, failed, stopped, interrupted)
+        #[arg(short, long)]
+        status: Option<String>,
+
+        /// Only jobs whose name matches a glob (e.g. 'build', 'svc-*')
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only jobs created since a time (e.g., 1d, 2h, or RFC 3339)
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Lines of context before and after each match
+        #[arg(short = 'C', long, value_name = "N", default_value_t = 2)]
+        context: usize,
+
+        /// Search the full-text index instead: the pattern is words or an FTS5 query
+        /// ("exit code", panic*, disk AND full), and jobs are ranked by relevance
+        #[arg(long, conflicts_with_all = ["ignore_case", "context"])]
+        indexed: bool,
     },
 
     /// Stop a running job
@@ -138,9 +277,33 @@ enum Commands {
         all: bool,
     },
 
-    /// Run the daemon (internal use)
-    #[command(hide = true)]
-    Daemon,
+    /// Manage the background daemon
+    Daemon {
+        #[command(subcommand)]
+        action: Option<DaemonAction>,
+    },
+
+    /// Print job lifecycle events (created, started, finished, stopped) as they happen
+    Events {
+        /// Only jobs in the project containing this directory
+        #[arg(short, long, value_name = "DIR")]
+        project: Option<String>,
+
+        /// Only jobs whose name matches a glob (e.g. 'build', 'svc-*')
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Only events that leave the job in this status (e.g. failed)
+        #[arg(short, long)]
+        status: Option<String>,
+
+        /// Exit after this many events
+        #[arg(short = 'c', long, value_name = "N")]
+        count: Option<usize>,
+    },
+
+    /// Serve jobs to AI agents over the Model Context Protocol (stdio)
+    Mcp,
 
     /// Generate shell completions
     Completions {
@@ -153,35 +316,108 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum DaemonAction {
+    /// Show daemon status (default)
+    Status,
+
+    /// Stop the daemon
+    Stop {
+        /// Leave running jobs alive instead of interrupting them (a helper keeps capturing their output)
+        #[arg(long)]
+        keep_jobs: bool,
+    },
+
+    /// Restart the daemon
+    Restart {
+        /// Leave running jobs alive instead of interrupting them (a helper keeps capturing their output)
+        #[arg(long)]
+        keep_jobs: bool,
+    },
+
+    /// Show the daemon's own log
+    Logs {
+        /// Show last N lines (default: 50 if flag present)
+        #[arg(short, long, num_args = 0..=1, default_missing_value = "50")]
+        tail: Option<usize>,
+
+        /// Follow the log as it's written
+        #[arg(short, long)]
+        follow: bool,
+    },
+
+    /// Run the daemon in the foreground (internal use)
+    #[command(hide = true)]
+    Run,
+
+    /// Capture a detached job's output from inherited pipes (internal use)
+    #[command(hide = true)]
+    Drain {
+        /// Job ID
+        id: String,
+    },
+}
+
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt()
-        .with_env_filter(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing::Level::WARN.into()),
-        )
-        .init();
-
-    if let Err(e) = run().await {
+    let cli = Cli::parse();
+
+    if let Some(home) = &cli.home
+        && let Err(e) = core::Paths::set_home(home)
+    {
+        eprintln!("Error: invalid --home: {e}");
+        std::process::exit(1);
+    }
+
+    // The daemon has no terminal: its tracing output goes to its own log file
+    let is_daemon = matches!(
+        cli.command,
+        Some(Commands::Daemon {
+            action: Some(DaemonAction::Run | DaemonAction::Drain { .. })
+        })
+    );
+    let daemon_logging = is_daemon
+        && core::Paths::new()
+            .and_then(|paths| {
+                paths.ensure_dirs()?;
+                daemon::logging::init(&paths)
+            })
+            .is_ok();
+    if !daemon_logging {
+        tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::from_default_env()
+                    .add_directive(tracing::Level::WARN.into()),
+            )
+            .with_writer(std::io::stderr)
+            .init();
+    }
+
+    if let Err(e) = run(cli).await {
         // Check if this is a UserError (clean exit without stack trace)
         if let Some(user_err) = e.downcast_ref::<UserError>() {
             eprintln!("Error: {user_err}");
             std::process::exit(1);
         }
+        if let Some(err) = e.downcast_ref::<jb::Error>()
+            && err.is_user_error()
+        {
+            eprintln!("Error: {err}");
+            std::process::exit(1);
+        }
         // For other errors, use anyhow's default formatting
         eprintln!("Error: {e:?}");
         std::process::exit(1);
     }
 }
 
-async fn run() -> Result<()> {
-    let cli = Cli::parse();
-
+async fn run(cli: Cli) -> Result<()> {
     let command = cli.command.unwrap_or(Commands::List {
         status: None,
         failed: false,
         limit: None,
         all: false,
+        output_contains: None,
     });
 
     match command {
@@ -193,20 +429,131 @@ async fn run() -> Result<()> {
             key,
             wait,
             follow,
-        } => commands::run::execute(command, name, timeout, dir, key, wait, follow, cli.json).await,
+            log_max_size,
+            log_keep,
+            on_success,
+            on_failure,
+            on_exit,
+            webhook,
+            notify,
+            no_notify,
+            batch,
+        } => {
+            if let Some(batch) = batch {
+                return commands::run::execute_batch(&batch, cli.json).await;
+            }
+            let opts = commands::run::RunOptions {
+                command: command.unwrap_or_default(),
+                name,
+                timeout,
+                dir,
+                key,
+                wait,
+                follow,
+                log_max_size,
+                log_keep,
+                hooks: Hooks {
+                    on_success,
+                    on_failure,
+                    on_exit,
+                },
+                webhooks: webhook,
+                notify: match (notify, no_notify) {
+                    (true, _) => Some(true),
+                    (_, true) => Some(false),
+                    _ => None,
+                },
+            };
+            commands::run::execute(opts, cli.json).await
+        }
         Commands::List {
             status,
             failed,
             limit,
             all,
-        } => commands::list::execute(status, failed, limit, all, cli.json),
-        Commands::Status { id } => commands::status::execute(id, cli.json),
+            output_contains,
+        } => commands::list::execute(
+            status,
+            failed,
+            limit,
+            all,
+            output_contains.as_deref(),
+            cli.json,
+        ),
+        Commands::Status { id, events } => commands::status::execute(id, events, cli.json),
         Commands::Logs {
-            id,
+            ids,
+            name,
             tail,
             follow,
             pager,
-        } => commands::logs::execute(&id, tail, follow, pager),
+            stdout,
+            stderr,
+            timestamps,
+            since,
+            until,
+            budget,
+            since_cursor,
+            format,
+        } => {
+            let stream = if stdout {
+                Some(Stream::Stdout)
+            } else if stderr {
+                Some(Stream::Stderr)
+            } else {
+                None
+            };
+            let opts = commands::logs::LogOptions {
+                tail,
+                follow,
+                pager,
+                stream,
+                timestamps,
+                since,
+                until,
+                budget,
+                since_cursor,
+                format,
+                json: cli.json,
+            };
+            match (ids.as_slice(), name) {
+                ([id], None) => commands::logs::execute(id, &opts).await,
+                (ids, name) => commands::logs::execute_many(ids, name.as_deref(), &opts).await,
+            }
+        }
+        Commands::Grep {
+            pattern,
+            ignore_case,
+            status,
+            name,
+            since,
+            context,
+            indexed,
+        } => commands::grep::execute(&commands::grep::GrepOptions {
+            pattern,
+            ignore_case,
+            status,
+            name,
+            since,
+            context,
+            indexed,
+            json: cli.json,
+        }),
+        Commands::Replay {
+            id,
+            speed,
+            stdout,
+            stderr,
+        } => {
+            let stream = if stdout {
+                Some(Stream::Stdout)
+            } else if stderr {
+                Some(Stream::Stderr)
+            } else {
+                None
+            };
+            commands::replay::execute(&id, speed, stream).await
+        }
         Commands::Stop { id, force } => commands::stop::execute(id, force, cli.json).await,
         Commands::Wait { id, timeout } => commands::wait::execute(id, timeout).await,
         Commands::Retry { id } => commands::retry::execute(id, cli.json).await,
@@ -215,7 +562,32 @@ async fn run() -> Result<()> {
             status,
             all,
         } => commands::clean::execute(&older_than, status, all),
-        Commands::Daemon => commands::daemon::execute().await,
+        Commands::Daemon { action } => match action.unwrap_or(DaemonAction::Status) {
+            DaemonAction::Status => commands::daemon::status(cli.json).await,
+            DaemonAction::Stop { keep_jobs } => commands::daemon::stop(keep_jobs, cli.json).await,
+            DaemonAction::Restart { keep_jobs } => {
+                commands::daemon::restart(keep_jobs, cli.json).await
+            }
+            DaemonAction::Logs { tail, follow } => commands::daemon::logs(tail, follow),
+            DaemonAction::Run => commands::daemon::run().await,
+            DaemonAction::Drain { id } => commands::daemon::drain(&id).await,
+        },
+        Commands::Events {
+            project,
+            name,
+            status,
+            count,
+        } => {
+            commands::events::execute(commands::events::EventsOptions {
+                project,
+                name,
+                status,
+                count,
+                json: cli.json,
+            })
+            .await
+        }
+        Commands::Mcp => commands::mcp::execute().await,
         Commands::Completions { shell, install } => commands::completions::execute(shell, install),
     }
 }
@@ -0,0 +1,50 @@
+//! `jb daemon stop --keep-jobs` against the real binary: the drain helper is
+//! started from the daemon's executable, so this can't run in-process.
+
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+use tempfile::TempDir;
+
+fn jb(home: &Path, args: &[&str]) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_jb"))
+        .env("JB_HOME", home)
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "jb {args:?}: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_kept_job_keeps_writing_after_daemon_stops() {
+    let home = TempDir::new().unwrap();
+    let done = home.path().join("done");
+    let script = format!(
+        "for i in $(seq 1 15); do echo line $i; sleep 0.1; done; echo last >&2; touch {}",
+        done.display()
+    );
+    let id = jb(home.path(), &["run", &script]);
+    let id = id.trim();
+    std::thread::sleep(Duration::from_millis(400));
+    jb(home.path(), &["daemon", "stop", "--keep-jobs"]);
+
+    // Writing to a pipe nobody reads would have killed it with SIGPIPE
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !done.exists() {
+        assert!(Instant::now() < deadline, "job did not survive the daemon");
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    std::thread::sleep(Duration::from_millis(300));
+
+    let log = std::fs::read_to_string(home.path().join("logs").join(format!("{id}.log"))).unwrap();
+    assert!(
+        log.contains("line 1\n") && log.contains("line 15\n"),
+        "{log}"
+    );
+    assert!(log.ends_with("last\n"), "{log}");
+    let _ = Command::new(env!("CARGO_BIN_EXE_jb"))
+        .env("JB_HOME", home.path())
+        .args(["daemon", "stop"])
+        .output();
+}